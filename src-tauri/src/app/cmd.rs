@@ -1,7 +1,546 @@
-use crate::utils;
-use tauri::{command, AppHandle};
+use crate::{
+  app::{
+    dashboard::{self, Buffer, Dashboard},
+    instance, launch, mock, popup, prefs, shortcut, theme, tray, window_state, workspace,
+  },
+  cli::Document,
+  conf::{AppConf, ConfErrors},
+  engine,
+  engine::{
+    batch::{self, Applied, Backup, FileDiff, Pipeline},
+    files,
+    format::{self, FormatOptions},
+    fuzzy::FuzzyHit,
+    http::{self, HttpRequest, HttpResponse},
+    importers::{self, HarEntry, ImportSource},
+    lint::{self, LintReport, ReportFormat},
+    mock::MockConfig,
+    repair,
+    requests::{self, Collection, Environment, Environments, HistoryEntry},
+    search::{self, FolderQuery, Hit, Replaced, SearchQuery},
+    snippets::{self, Snippet},
+    spans,
+    tabular::{self, Column, TableFormat},
+    transform::Transform,
+    workspace::{self as folder, TreeEntry, Workspace},
+  },
+  utils,
+};
+use log::info;
+use serde_json::Value;
+use std::{
+  collections::BTreeMap,
+  path::{Path, PathBuf},
+  sync::{
+    atomic::{AtomicU64, Ordering},
+    Mutex,
+  },
+};
+use tauri::{command, AppHandle, Window};
+
+static FOLDER_SEARCH_ID: AtomicU64 = AtomicU64::new(0);
+// id of the running folder search of each window, replacing or removing it cancels that search
+static FOLDER_SEARCHES: Mutex<BTreeMap<String, u64>> = Mutex::new(BTreeMap::new());
 
 #[command]
 pub fn run_check_update(app: AppHandle, silent: bool, has_msg: Option<bool>) {
   utils::run_check_update(app, silent, has_msg);
 }
+
+#[command]
+pub fn transform_document(
+  json: Value,
+  pointer: Option<String>,
+  op: Transform,
+) -> Result<Value, String> {
+  engine::apply_at(json, pointer.as_deref(), |v| op.apply(v)).map_err(|e| e.to_string())
+}
+
+#[command]
+pub fn table_columns(json: Value, pointer: Option<String>) -> Result<Vec<String>, String> {
+  tabular::rows_at(&json, pointer.as_deref())
+    .map(|rows| tabular::columns(rows))
+    .map_err(|e| e.to_string())
+}
+
+#[command]
+pub fn export_table(
+  json: Value,
+  pointer: Option<String>,
+  columns: Option<Vec<Column>>,
+  format: TableFormat,
+  path: String,
+) -> Result<usize, String> {
+  tabular::export(&json, pointer.as_deref(), columns, format, Path::new(&path))
+    .map_err(|e| e.to_string())
+}
+
+#[command]
+pub fn import_table(path: String, sheet: Option<String>) -> Result<Value, String> {
+  tabular::import(Path::new(&path), sheet.as_deref()).map_err(|e| e.to_string())
+}
+
+// JSON Pointer of a selection in the text editor, `None` for the whole document
+#[command]
+pub fn selection_pointer(text: String, from: usize, to: usize) -> Result<Option<String>, String> {
+  spans::pointer_at(&text, from, to).map_err(|e| e.to_string())
+}
+
+#[command]
+pub fn search_document(text: String, query: SearchQuery) -> Result<Vec<Hit>, String> {
+  search::search_text(&text, &query).map_err(|e| e.to_string())
+}
+
+#[command]
+pub fn replace_in_document(
+  text: String,
+  query: SearchQuery,
+  replacement: String,
+) -> Result<Replaced, String> {
+  let json: Value = serde_json::from_str(&text).map_err(|e| e.to_string())?;
+  search::replace(json, &query, &replacement).map_err(|e| e.to_string())
+}
+
+// document given on the command line for the calling window, handed out once
+#[command]
+pub fn launch_document(window: Window) -> Option<Document> {
+  launch::take(window.label())
+}
+
+// `document` in a new window, used instead of replacing a document that has edits
+#[command]
+pub fn open_window(app: AppHandle, document: Document) -> Result<(), String> {
+  let windows = launch::open_windows(&app, vec![document]).map_err(|e| e.to_string())?;
+  if let Some(win) = windows.last() {
+    win.set_focus().ok();
+  }
+  Ok(())
+}
+
+// answer of a window to a control channel `get-document` request
+#[command]
+pub fn control_reply(id: u64, json: Value) {
+  instance::reply(id, json);
+}
+
+#[command]
+pub fn open_document(window: Window, path: String) -> Result<Value, String> {
+  let value = files::read_value(Path::new(&path)).map_err(|e| e.to_string())?;
+  tray::add_recent(&path);
+  window_state::show_file(&window, &path);
+  Ok(value)
+}
+
+#[command]
+pub fn tray_state() -> tray::TrayState {
+  tray::state()
+}
+
+// Scratchpad of the tray window: pasted text is repaired, then pretty-printed or minified
+#[command]
+pub fn format_json(text: String, minify: bool) -> Result<String, String> {
+  let value = repair::repair(&text).map_err(|e| e.to_string())?;
+  let options = FormatOptions {
+    indent: if minify { 0 } else { 2 },
+    final_newline: false,
+    ..Default::default()
+  };
+  format::format(&value, &options).map_err(|e| e.to_string())
+}
+
+#[command]
+pub fn open_tray_document(app: AppHandle, document: Document) -> Result<(), String> {
+  tray::open(&app, document).map_err(|e| e.to_string())
+}
+
+// async: the windows answer through IPC, which the main thread must stay free for
+#[command]
+pub async fn refresh_popup_search(app: AppHandle) -> Result<usize, String> {
+  popup::refresh(&app).map_err(|e| e.to_string())
+}
+
+#[command]
+pub fn popup_search(query: String) -> Vec<FuzzyHit> {
+  popup::search(&query)
+}
+
+#[command]
+pub fn open_popup_hit(app: AppHandle, target: popup::Target) -> Result<(), String> {
+  popup::open(&app, target).map_err(|e| e.to_string())
+}
+
+#[command]
+pub fn list_snippets() -> Result<Vec<Snippet>, String> {
+  snippets::list(&popup::snippets_dir()).map_err(|e| e.to_string())
+}
+
+#[command]
+pub fn save_snippet(name: String, json: Value) -> Result<Snippet, String> {
+  snippets::save(&popup::snippets_dir(), &name, &json).map_err(|e| e.to_string())
+}
+
+#[command]
+pub fn delete_snippet(name: String) -> Result<(), String> {
+  snippets::delete(&popup::snippets_dir(), &name).map_err(|e| e.to_string())
+}
+
+#[command]
+pub fn get_dashboard(window: Window) -> Result<Dashboard, String> {
+  dashboard::state(window.label()).map_err(|e| e.to_string())
+}
+
+#[command]
+pub fn pin_document(path: String, pinned: bool) -> Result<(), String> {
+  dashboard::set_pinned(&path, pinned).map_err(|e| e.to_string())
+}
+
+// `json` is `null` when the window's document matches what was loaded
+#[command]
+pub fn save_buffer(window: Window, path: Option<String>, json: Value) -> Result<(), String> {
+  if json.is_null() {
+    dashboard::discard_window_buffer(window.label());
+    return Ok(());
+  }
+  dashboard::save_buffer(window.label(), path, json).map_err(|e| e.to_string())
+}
+
+#[command]
+pub fn recover_buffer(id: String) -> Result<Buffer, String> {
+  dashboard::recover_buffer(&id).map_err(|e| e.to_string())
+}
+
+#[command]
+pub fn discard_buffer(id: String) -> Result<(), String> {
+  dashboard::discard_buffer(&id).map_err(|e| e.to_string())
+}
+
+#[command]
+pub fn read_template(name: String) -> Result<Value, String> {
+  files::named_file(&dashboard::templates_dir(), &name)
+    .and_then(|path| files::read_value(&path))
+    .map_err(|e| e.to_string())
+}
+
+#[command]
+pub fn save_template(name: String, json: Value) -> Result<Snippet, String> {
+  snippets::save(&dashboard::templates_dir(), &name, &json).map_err(|e| e.to_string())
+}
+
+#[command]
+pub fn delete_template(name: String) -> Result<(), String> {
+  snippets::delete(&dashboard::templates_dir(), &name).map_err(|e| e.to_string())
+}
+
+#[command]
+pub fn clipboard_json(app: AppHandle) -> Result<Value, String> {
+  shortcut::clipboard_json(&app).map_err(|e| e.to_string())
+}
+
+// Results are streamed as `folder-search-hits` events, followed by one `folder-search-done`
+#[command]
+pub fn search_folder(
+  window: Window,
+  root: String,
+  query: FolderQuery,
+  max_size: Option<u64>,
+) -> Result<u64, String> {
+  query.validate().map_err(|e| e.to_string())?;
+  let id = FOLDER_SEARCH_ID.fetch_add(1, Ordering::SeqCst) + 1;
+  let label = window.label().to_string();
+  info!("search_folder: id={} window={} root={}", id, label, root);
+  FOLDER_SEARCHES.lock().unwrap().insert(label.clone(), id);
+  std::thread::spawn(move || {
+    let result = search::search_folder(
+      Path::new(&root),
+      &query,
+      max_size.unwrap_or(files::DEFAULT_MAX_SIZE),
+      || FOLDER_SEARCHES.lock().unwrap().get(&label) != Some(&id),
+      |file| {
+        window
+          .emit(
+            "folder-search-hits",
+            serde_json::json!({ "id": id, "path": file.path, "hits": file.hits }),
+          )
+          .ok();
+      },
+    );
+    let done = match result {
+      Ok(stats) => serde_json::json!({ "id": id, "stats": stats }),
+      Err(err) => serde_json::json!({ "id": id, "error": err.to_string() }),
+    };
+    window.emit("folder-search-done", done).ok();
+    let mut searches = FOLDER_SEARCHES.lock().unwrap();
+    if searches.get(&label) == Some(&id) {
+      searches.remove(&label);
+    }
+  });
+  Ok(id)
+}
+
+#[command]
+pub fn cancel_folder_search(window: Window) {
+  FOLDER_SEARCHES.lock().unwrap().remove(window.label());
+}
+
+// Runs off the main thread, remote schemas are downloaded while validating
+#[command]
+pub async fn lint_folder(root: String, max_size: Option<u64>) -> Result<LintReport, String> {
+  info!("lint_folder: root={}", root);
+  tauri::async_runtime::spawn_blocking(move || {
+    lint::lint_folder(
+      Path::new(&root),
+      max_size.unwrap_or(files::DEFAULT_MAX_SIZE),
+    )
+  })
+  .await
+  .map_err(|e| e.to_string())?
+  .map_err(|e| e.to_string())
+}
+
+#[command]
+pub fn export_lint_report(
+  report: LintReport,
+  format: ReportFormat,
+  path: String,
+) -> Result<(), String> {
+  lint::export(&report, format, Path::new(&path)).map_err(|e| e.to_string())
+}
+
+#[command]
+pub async fn fetch_url(request: HttpRequest) -> Result<HttpResponse, String> {
+  info!("fetch_url: {} {}", request.method, request.url);
+  let client = http::client(&AppConf::read().client_options()).map_err(|e| e.to_string())?;
+  http::send(&client, &request)
+    .await
+    .map_err(|e| e.to_string())
+}
+
+fn requests_dir() -> PathBuf {
+  utils::app_root().join("requests")
+}
+
+#[command]
+pub fn list_collections() -> Result<Vec<Collection>, String> {
+  Collection::list(&requests_dir()).map_err(|e| e.to_string())
+}
+
+#[command]
+pub fn save_collection(collection: Collection) -> Result<(), String> {
+  collection.save(&requests_dir()).map_err(|e| e.to_string())
+}
+
+#[command]
+pub fn delete_collection(name: String) -> Result<(), String> {
+  Collection::delete(&requests_dir(), &name).map_err(|e| e.to_string())
+}
+
+#[command]
+pub fn get_environments() -> Result<Environments, String> {
+  Environments::load(&requests_dir()).map_err(|e| e.to_string())
+}
+
+#[command]
+pub fn save_environments(environments: Environments) -> Result<(), String> {
+  environments
+    .save(&requests_dir())
+    .map_err(|e| e.to_string())
+}
+
+// Resolve `{{variables}}` of `environment` (or the active one), send, and keep the response in the history
+#[command]
+pub async fn send_request(
+  request: HttpRequest,
+  environment: Option<String>,
+) -> Result<HistoryEntry, String> {
+  let dir = requests_dir();
+  let environments = Environments::load(&dir).map_err(|e| e.to_string())?;
+  let variables = environments
+    .variables(environment.as_deref())
+    .map_err(|e| e.to_string())?;
+  let resolved = requests::resolve(&request, &variables).map_err(|e| e.to_string())?;
+  info!("send_request: {} {}", request.method, request.url);
+  let client = http::client(&AppConf::read().client_options()).map_err(|e| e.to_string())?;
+  let response = http::send(&client, &resolved)
+    .await
+    .map_err(|e| e.to_string())?;
+  let environment = environment.or(environments.active);
+  requests::record(&dir, environment, request, response).map_err(|e| e.to_string())
+}
+
+#[command]
+pub fn request_history() -> Result<Vec<HistoryEntry>, String> {
+  requests::history(&requests_dir()).map_err(|e| e.to_string())
+}
+
+#[command]
+pub fn clear_request_history() -> Result<(), String> {
+  requests::clear_history(&requests_dir()).map_err(|e| e.to_string())
+}
+
+#[command]
+pub fn diff_responses(a: String, b: String) -> Result<String, String> {
+  let dir = requests_dir();
+  let a = requests::history_entry(&dir, &a).map_err(|e| e.to_string())?;
+  let b = requests::history_entry(&dir, &b).map_err(|e| e.to_string())?;
+  requests::diff(&a, &b).map_err(|e| e.to_string())
+}
+
+// Add imported requests to `collection`, Postman variables become an environment of the same name
+#[command]
+pub fn import_requests(source: ImportSource, collection: String) -> Result<Collection, String> {
+  let dir = requests_dir();
+  let imported = importers::import(&source).map_err(|e| e.to_string())?;
+  let mut target = Collection::load(&dir, &collection)
+    .map_err(|e| e.to_string())?
+    .unwrap_or(Collection {
+      name: collection.clone(),
+      requests: vec![],
+    });
+  info!(
+    "import_requests: {} requests into {}",
+    imported.requests.len(),
+    collection
+  );
+  target.merge(imported.requests);
+  target.save(&dir).map_err(|e| e.to_string())?;
+  if !imported.variables.is_empty() {
+    let mut environments = Environments::load(&dir).map_err(|e| e.to_string())?;
+    environments.upsert(Environment {
+      name: collection,
+      variables: imported.variables,
+    });
+    environments.save(&dir).map_err(|e| e.to_string())?;
+  }
+  Ok(target)
+}
+
+#[command]
+pub fn read_har(path: String) -> Result<Vec<HarEntry>, String> {
+  let text = std::fs::read_to_string(&path).map_err(|e| e.to_string())?;
+  importers::parse_har(&text).map_err(|e| e.to_string())
+}
+
+#[command]
+pub fn list_pipelines() -> Result<Vec<Pipeline>, String> {
+  Pipeline::list(&utils::app_root().join("pipelines")).map_err(|e| e.to_string())
+}
+
+#[command]
+pub fn save_pipeline(pipeline: Pipeline) -> Result<(), String> {
+  pipeline
+    .save(&utils::app_root().join("pipelines"))
+    .map_err(|e| e.to_string())
+}
+
+#[command]
+pub fn delete_pipeline(name: String) -> Result<(), String> {
+  Pipeline::delete(&utils::app_root().join("pipelines"), &name).map_err(|e| e.to_string())
+}
+
+// Dry run, nothing is written
+#[command]
+pub async fn preview_pipeline(root: String, pipeline: Pipeline) -> Result<Vec<FileDiff>, String> {
+  tauri::async_runtime::spawn_blocking(move || {
+    batch::preview(Path::new(&root), &pipeline, files::DEFAULT_MAX_SIZE)
+  })
+  .await
+  .map_err(|e| e.to_string())?
+  .map_err(|e| e.to_string())
+}
+
+#[command]
+pub async fn apply_pipeline(root: String, pipeline: Pipeline) -> Result<Applied, String> {
+  info!("apply_pipeline: name={} root={}", pipeline.name, root);
+  tauri::async_runtime::spawn_blocking(move || {
+    batch::apply(
+      Path::new(&root),
+      &pipeline,
+      files::DEFAULT_MAX_SIZE,
+      &utils::app_root().join("backups"),
+    )
+  })
+  .await
+  .map_err(|e| e.to_string())?
+  .map_err(|e| e.to_string())
+}
+
+#[command]
+pub fn list_backups() -> Result<Vec<Backup>, String> {
+  batch::backups(&utils::app_root().join("backups")).map_err(|e| e.to_string())
+}
+
+#[command]
+pub fn undo_backup(id: String) -> Result<Vec<String>, String> {
+  info!("undo_backup: id={}", id);
+  batch::undo(&utils::app_root().join("backups"), &id).map_err(|e| e.to_string())
+}
+
+#[command]
+pub fn start_mock(
+  app: AppHandle,
+  window: Window,
+  config: MockConfig,
+  document: Value,
+) -> Result<MockConfig, String> {
+  mock::start(&app, window.label(), config, document).map_err(|e| e.to_string())
+}
+
+#[command]
+pub fn stop_mock(app: AppHandle) {
+  mock::stop(&app);
+}
+
+#[command]
+pub fn update_mock_document(window: Window, document: Value) {
+  mock::set_document(window.label(), document);
+}
+
+#[command]
+pub fn mock_status() -> Option<MockConfig> {
+  mock::status()
+}
+
+#[command]
+pub fn mock_port() -> u16 {
+  AppConf::read()
+    .mock_port
+    .unwrap_or(engine::mock::DEFAULT_PORT)
+}
+
+#[command]
+pub fn open_workspace(window: Window, root: String) -> Result<Workspace, String> {
+  workspace::open(&window, Path::new(&root)).map_err(|e| e.to_string())
+}
+
+#[command]
+pub fn list_workspace_dir(root: String, dir: String) -> Result<Vec<TreeEntry>, String> {
+  folder::list_dir(Path::new(&root), Path::new(&dir)).map_err(|e| e.to_string())
+}
+
+#[command]
+pub fn close_workspace(window: Window) {
+  workspace::close(&window);
+}
+
+#[command]
+pub fn last_workspace() -> Option<String> {
+  AppConf::read().workspace
+}
+
+#[command]
+pub fn get_conf() -> AppConf {
+  AppConf::read()
+}
+
+// Change some settings, rejected ones come back as `[{ key, message }]` and nothing is saved.
+// The others apply right away, `titlebar` and `hide_dock_icon` on the next start.
+#[command]
+pub fn set_conf(app: AppHandle, patch: Value) -> Result<AppConf, ConfErrors> {
+  prefs::set_json(&app, patch)
+}
+
+// "light" or "dark", "system" resolved to the OS mode, changes arrive as `theme-changed`
+#[command]
+pub fn get_theme() -> String {
+  theme::effective().into()
+}
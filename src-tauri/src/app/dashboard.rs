@@ -0,0 +1,209 @@
+use crate::{
+  app::tray,
+  conf::AppConf,
+  engine::{
+    files,
+    snippets::{self, Snippet},
+  },
+  utils,
+};
+use anyhow::{anyhow, Result};
+use log::info;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::{
+  fs,
+  path::{Path, PathBuf},
+  sync::OnceLock,
+  time::{SystemTime, UNIX_EPOCH},
+};
+
+// What the start dashboard shows while a window has no document
+#[derive(Serialize, Debug)]
+pub struct Dashboard {
+  // `main_dashboard` / `tray_dashboard` for the asking window
+  pub enabled: bool,
+  pub recent: Vec<FileEntry>,
+  pub pinned: Vec<FileEntry>,
+  pub buffers: Vec<BufferEntry>,
+  pub templates: Vec<Snippet>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct FileEntry {
+  pub path: String,
+  pub name: String,
+  // moved or deleted files stay listed until unpinned
+  pub exists: bool,
+}
+
+// Unsaved edits of a window, written while typing so they survive a crash or quit
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Buffer {
+  // file the edits belong to, `None` for new documents
+  pub path: Option<String>,
+  // seconds since the epoch
+  pub saved_at: u64,
+  pub json: Value,
+}
+
+#[derive(Serialize, Debug)]
+pub struct BufferEntry {
+  pub id: String,
+  pub path: Option<String>,
+  pub saved_at: u64,
+}
+
+pub fn templates_dir() -> PathBuf {
+  utils::app_root().join("templates")
+}
+
+fn buffers_dir() -> PathBuf {
+  utils::app_root().join("buffers")
+}
+
+fn pinned_file() -> PathBuf {
+  utils::app_root().join("pinned.json")
+}
+
+pub fn state(window: &str) -> Result<Dashboard> {
+  let app_conf = AppConf::read();
+  let enabled = if window == tray::LABEL {
+    app_conf.tray_dashboard
+  } else {
+    app_conf.main_dashboard
+  };
+  Ok(Dashboard {
+    enabled,
+    recent: tray::recent().iter().map(|p| file_entry(p)).collect(),
+    pinned: pinned().iter().map(|p| file_entry(p)).collect(),
+    buffers: recovered()?,
+    templates: snippets::list(&templates_dir())?,
+  })
+}
+
+fn file_entry(path: &str) -> FileEntry {
+  FileEntry {
+    path: path.into(),
+    name: Path::new(path)
+      .file_name()
+      .map_or(path.into(), |name| name.to_string_lossy().into()),
+    exists: Path::new(path).is_file(),
+  }
+}
+
+pub fn pinned() -> Vec<String> {
+  fs::read_to_string(pinned_file())
+    .ok()
+    .and_then(|text| serde_json::from_str(&text).ok())
+    .unwrap_or_default()
+}
+
+pub fn set_pinned(path: &str, pin: bool) -> Result<()> {
+  let mut paths = pinned();
+  paths.retain(|p| p != path);
+  if pin {
+    paths.push(path.into());
+  }
+  files::write_atomic(&pinned_file(), &serde_json::to_string_pretty(&paths)?)
+}
+
+// `<pid>.<start time in ms>`, the pid alone comes back once the OS reuses it
+fn session() -> &'static str {
+  static SESSION: OnceLock<String> = OnceLock::new();
+  SESSION.get_or_init(|| {
+    let started = SystemTime::now()
+      .duration_since(UNIX_EPOCH)
+      .map(|d| d.as_millis())
+      .unwrap_or_default();
+    format!("{}.{}", std::process::id(), started)
+  })
+}
+
+// buffers of this run are named `<session>-<window label>`, all others are from earlier sessions
+fn buffer_id(window: &str) -> String {
+  format!("{}-{}", session(), window)
+}
+
+pub fn save_buffer(window: &str, path: Option<String>, json: Value) -> Result<()> {
+  let dir = buffers_dir();
+  fs::create_dir_all(&dir)?;
+  let saved_at = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
+  let buffer = Buffer {
+    path,
+    saved_at,
+    json,
+  };
+  files::write_atomic(
+    &files::named_file(&dir, &buffer_id(window))?,
+    &serde_json::to_string(&buffer)?,
+  )
+}
+
+// The window's edits were saved, reverted or its window closed
+pub fn discard_window_buffer(window: &str) {
+  discard_buffer(&buffer_id(window)).ok();
+}
+
+pub fn discard_buffer(id: &str) -> Result<()> {
+  let path = files::named_file(&buffers_dir(), id)?;
+  if path.is_file() {
+    info!("buffer_discard: {}", id);
+    fs::remove_file(path)?;
+  }
+  Ok(())
+}
+
+// windows of this run with unsaved edits
+pub fn unsaved() -> usize {
+  let own = format!("{}-", session());
+  fs::read_dir(buffers_dir())
+    .map(|entries| {
+      entries
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.file_name().to_string_lossy().starts_with(&own))
+        .count()
+    })
+    .unwrap_or(0)
+}
+
+// Hand a recovered buffer to a window, it is taken over by that window's own buffer
+pub fn recover_buffer(id: &str) -> Result<Buffer> {
+  let path = files::named_file(&buffers_dir(), id)?;
+  let text = fs::read_to_string(&path).map_err(|e| anyhow!("buffer {}: {}", id, e))?;
+  let buffer = serde_json::from_str(&text)?;
+  fs::remove_file(path)?;
+  Ok(buffer)
+}
+
+// buffers left behind by earlier sessions, newest first
+fn recovered() -> Result<Vec<BufferEntry>> {
+  let dir = buffers_dir();
+  if !dir.is_dir() {
+    return Ok(vec![]);
+  }
+  let own = format!("{}-", session());
+  let mut entries = vec![];
+  for entry in fs::read_dir(dir)? {
+    let path = entry?.path();
+    let Some(id) = path.file_stem().map(|s| s.to_string_lossy().into_owned()) else {
+      continue;
+    };
+    if id.starts_with(&own) || !path.extension().is_some_and(|ext| ext == "json") {
+      continue;
+    }
+    let Some(buffer) = fs::read_to_string(&path)
+      .ok()
+      .and_then(|text| serde_json::from_str::<Buffer>(&text).ok())
+    else {
+      continue;
+    };
+    entries.push(BufferEntry {
+      id,
+      path: buffer.path,
+      saved_at: buffer.saved_at,
+    });
+  }
+  entries.sort_by_key(|entry| std::cmp::Reverse(entry.saved_at));
+  Ok(entries)
+}
@@ -0,0 +1,360 @@
+use crate::{
+  app::{launch, popup, tray},
+  cli,
+  cli::Document,
+  utils,
+};
+use anyhow::{anyhow, bail, Result};
+use log::{error, info};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::{
+  collections::{hash_map::RandomState, BTreeMap},
+  fs,
+  hash::{BuildHasher, Hasher},
+  io::{BufRead, BufReader, Read, Write},
+  net::{Ipv4Addr, SocketAddr, TcpListener, TcpStream},
+  path::{Path, PathBuf},
+  sync::{
+    atomic::{AtomicU64, Ordering},
+    mpsc::{self, Sender},
+    Mutex,
+  },
+  thread,
+  time::{Duration, Instant},
+};
+use tauri::{AppHandle, Manager, Window};
+
+// Address of the running instance. A second launch sends its documents there and exits,
+// so only one process owns the tray icon and writes `editor.conf.json`.
+// Scripts use the same channel: connect to `port`, write one JSON line
+// `{ "token": "...", "op": "get-document" }` and read one `{ "ok": true, "result": ... }` line back.
+#[derive(Serialize, Deserialize, Debug)]
+struct Instance {
+  port: u16,
+  // only processes that can read this file may send requests
+  token: String,
+  pid: u32,
+}
+
+// Operations of the control channel, `window` is a window label (default: the focused window)
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(tag = "op", rename_all = "kebab-case")]
+pub enum Request {
+  // each document opens in a new window
+  Open {
+    documents: Vec<Document>,
+  },
+  Focus {
+    window: Option<String>,
+  },
+  GetDocument {
+    window: Option<String>,
+  },
+  SetDocument {
+    json: Value,
+    window: Option<String>,
+  },
+  // JSONPath (`$...`) or jq on the document of the window, the result is the array of matches
+  RunQuery {
+    expr: String,
+    window: Option<String>,
+  },
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+struct Message {
+  token: String,
+  #[serde(flatten)]
+  request: Request,
+}
+
+#[derive(Serialize, Deserialize, Debug, Default)]
+struct Reply {
+  ok: bool,
+  #[serde(default)]
+  result: Value,
+  #[serde(default)]
+  error: Option<String>,
+}
+
+// how long the editor window may take to hand over its document
+const WINDOW_TIMEOUT: Duration = Duration::from_secs(5);
+// how long a later launch waits for the process holding the lock to accept connections
+const STARTUP_TIMEOUT: Duration = Duration::from_secs(10);
+// longest request or reply line, documents travel inside them
+const MAX_LINE: u64 = 256 * 1024 * 1024;
+
+// `get-document` requests waiting for `control_reply` from their window, by id
+static PENDING: Mutex<BTreeMap<u64, Sender<Value>>> = Mutex::new(BTreeMap::new());
+static NEXT_ID: AtomicU64 = AtomicU64::new(1);
+
+fn instance_file() -> PathBuf {
+  utils::app_root().join("instance.json")
+}
+
+// held with an exclusive lock by the running instance, the OS releases it when the process ends
+fn lock_file() -> PathBuf {
+  utils::app_root().join("instance.lock")
+}
+
+// The control channel of the running instance, claimed before the GUI starts
+pub struct Primary {
+  _lock: fs::File,
+  listener: TcpListener,
+  instance: Instance,
+}
+
+pub enum Launch {
+  // the running instance took the documents
+  Forwarded,
+  Primary(Primary),
+}
+
+// Whichever launch gets the lock first is the running instance, it binds its port and writes
+// `instance.json` right away. Later launches hand `documents` to it.
+pub fn start(documents: &[Document]) -> Result<Launch> {
+  fs::create_dir_all(utils::app_root())?;
+  let lock = fs::OpenOptions::new()
+    .create(true)
+    .truncate(false)
+    .write(true)
+    .open(lock_file())?;
+  match lock.try_lock() {
+    Ok(()) => {
+      let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0))?;
+      let instance = Instance {
+        port: listener.local_addr()?.port(),
+        token: token(),
+        pid: std::process::id(),
+      };
+      write_private(&instance_file(), &serde_json::to_string(&instance)?)?;
+      Ok(Launch::Primary(Primary {
+        _lock: lock,
+        listener,
+        instance,
+      }))
+    }
+    Err(fs::TryLockError::WouldBlock) => {
+      // the lock is taken just before `instance.json` is written
+      let started = Instant::now();
+      while started.elapsed() < STARTUP_TIMEOUT {
+        if forward(documents)? {
+          return Ok(Launch::Forwarded);
+        }
+        thread::sleep(Duration::from_millis(100));
+      }
+      bail!("the running instance does not answer")
+    }
+    Err(fs::TryLockError::Error(e)) => Err(e.into()),
+  }
+}
+
+// Hand `documents` to the running instance, `false` when there is none to take them
+pub fn forward(documents: &[Document]) -> Result<bool> {
+  Ok(
+    send(&Request::Open {
+      documents: documents.to_vec(),
+    })?
+    .is_some(),
+  )
+}
+
+// Result of `request` in the running instance, `None` when no instance is running
+pub fn send(request: &Request) -> Result<Option<Value>> {
+  let Ok(text) = fs::read_to_string(instance_file()) else {
+    return Ok(None);
+  };
+  let Ok(instance) = serde_json::from_str::<Instance>(&text) else {
+    return Ok(None);
+  };
+  let addr = SocketAddr::from((Ipv4Addr::LOCALHOST, instance.port));
+  // a stale file from a crashed instance
+  let Ok(mut stream) = TcpStream::connect_timeout(&addr, Duration::from_secs(1)) else {
+    return Ok(None);
+  };
+  info!("instance_send: pid={}", instance.pid);
+  stream.set_read_timeout(Some(WINDOW_TIMEOUT * 2))?;
+  let message = Message {
+    token: instance.token,
+    request: request.clone(),
+  };
+  writeln!(stream, "{}", serde_json::to_string(&message)?)?;
+  let line = read_line(&stream)?;
+  // the port may have been taken over by a program that does not speak this protocol
+  let Ok(reply) = serde_json::from_str::<Reply>(&line) else {
+    return Ok(None);
+  };
+  match reply.error {
+    Some(error) if !reply.ok => Err(anyhow!("{}", error)),
+    _ => Ok(Some(reply.result)),
+  }
+}
+
+// Serve requests of later launches and scripts, `primary` is kept (and locked) until exit
+pub fn listen(app: &AppHandle, primary: Primary) {
+  info!("instance_listen: port={}", primary.instance.port);
+  let app = app.clone();
+  thread::spawn(move || {
+    // the whole of `primary` moves here, the lock is held as long as the thread runs
+    let Primary {
+      _lock,
+      listener,
+      instance,
+    } = primary;
+    for stream in listener.incoming().flatten() {
+      let app = app.clone();
+      let token = instance.token.clone();
+      // `get-document` waits for a window, other clients must not wait behind it
+      thread::spawn(move || {
+        if let Err(e) = receive(&app, stream, &token) {
+          error!("instance_receive: {}", e);
+        }
+      });
+    }
+  });
+}
+
+fn receive(app: &AppHandle, mut stream: TcpStream, token: &str) -> Result<()> {
+  stream.set_read_timeout(Some(WINDOW_TIMEOUT))?;
+  let line = read_line(&stream)?;
+  let message: Message = serde_json::from_str(&line)?;
+  if message.token != token {
+    bail!("invalid token");
+  }
+  let reply = match handle(app, message.request) {
+    Ok(result) => Reply {
+      ok: true,
+      result,
+      error: None,
+    },
+    Err(e) => Reply {
+      error: Some(format!("{:#}", e)),
+      ..Default::default()
+    },
+  };
+  writeln!(stream, "{}", serde_json::to_string(&reply)?)?;
+  Ok(())
+}
+
+fn handle(app: &AppHandle, request: Request) -> Result<Value> {
+  info!("instance_request: {:?}", request);
+  match request {
+    Request::Open { documents } => {
+      let windows = launch::open_windows(app, documents)?;
+      match windows.last() {
+        Some(win) => focus(win)?,
+        None => focus(&window(app, None)?)?,
+      }
+      Ok(Value::Null)
+    }
+    Request::Focus { window: label } => {
+      focus(&window(app, label)?)?;
+      Ok(Value::Null)
+    }
+    Request::GetDocument { window: label } => document(&window(app, label)?),
+    Request::SetDocument {
+      json,
+      window: label,
+    } => {
+      window(app, label)?.emit("control-set-document", json)?;
+      Ok(Value::Null)
+    }
+    Request::RunQuery {
+      expr,
+      window: label,
+    } => {
+      let document = document(&window(app, label)?)?;
+      Ok(Value::Array(cli::evaluate(&expr, document)?))
+    }
+  }
+}
+
+// `label`, or the focused window, or the main one
+fn window(app: &AppHandle, label: Option<String>) -> Result<Window> {
+  if let Some(label) = label {
+    return app
+      .get_window(&label)
+      .ok_or_else(|| anyhow!("no window `{}`", label));
+  }
+  let mut windows = app.windows();
+  // the tray popover and the search popup have no document
+  windows.remove(tray::LABEL);
+  windows.remove(popup::LABEL);
+  windows
+    .values()
+    .find(|win| win.is_focused().unwrap_or(false))
+    .or_else(|| windows.get("core"))
+    .or_else(|| windows.values().next())
+    .cloned()
+    .ok_or_else(|| anyhow!("no editor window is open"))
+}
+
+fn focus(win: &Window) -> Result<()> {
+  win.show()?;
+  win.unminimize()?;
+  win.set_focus()?;
+  Ok(())
+}
+
+// Ask the window for its document, it answers with `control_reply`
+pub fn document(win: &Window) -> Result<Value> {
+  let id = NEXT_ID.fetch_add(1, Ordering::SeqCst);
+  let (tx, rx) = mpsc::channel();
+  PENDING.lock().unwrap().insert(id, tx);
+  let result = win
+    .emit("control-get-document", id)
+    .map_err(Into::into)
+    .and_then(|_| {
+      rx.recv_timeout(WINDOW_TIMEOUT)
+        .map_err(|_| anyhow!("window `{}` did not answer", win.label()))
+    });
+  PENDING.lock().unwrap().remove(&id);
+  result
+}
+
+pub fn reply(id: u64, json: Value) {
+  if let Some(tx) = PENDING.lock().unwrap().remove(&id) {
+    tx.send(json).ok();
+  }
+}
+
+// one line of at most `MAX_LINE` bytes, a longer one is cut and fails to parse
+fn read_line(stream: &TcpStream) -> Result<String> {
+  let mut line = String::new();
+  BufReader::new(stream.take(MAX_LINE)).read_line(&mut line)?;
+  Ok(line)
+}
+
+// The token is the only thing keeping other local users out. `mode` only applies to new files,
+// so a fresh file is renamed over `path` rather than rewriting one that may be readable by others.
+fn write_private(path: &Path, text: &str) -> Result<()> {
+  let name = path
+    .file_name()
+    .ok_or_else(|| anyhow!("{} is not a file path", path.display()))?;
+  let tmp = path.with_file_name(format!(
+    ".{}.{}.tmp",
+    name.to_string_lossy(),
+    std::process::id()
+  ));
+  fs::remove_file(&tmp).ok();
+  let mut options = fs::OpenOptions::new();
+  options.write(true).create_new(true);
+  #[cfg(unix)]
+  std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+  let result = options
+    .open(&tmp)
+    .and_then(|mut file| file.write_all(text.as_bytes()))
+    .and_then(|_| fs::rename(&tmp, path));
+  if result.is_err() {
+    fs::remove_file(&tmp).ok();
+  }
+  Ok(result?)
+}
+
+// random enough for a local handshake, without pulling in an RNG crate
+fn token() -> String {
+  let mut hasher = RandomState::new().build_hasher();
+  hasher.write_u32(std::process::id());
+  format!("{:016x}", hasher.finish())
+}
@@ -0,0 +1,85 @@
+use crate::{app::window_state, cli::Document, conf::AppConf, deep_link};
+use anyhow::Result;
+use log::{error, info};
+use std::{
+  collections::BTreeMap,
+  path::Path,
+  sync::{
+    atomic::{AtomicUsize, Ordering},
+    Mutex,
+  },
+};
+use tauri::{utils::config::WindowUrl, window::WindowBuilder, AppHandle, Window};
+
+// documents waiting for their window to load, by window label
+static PENDING: Mutex<BTreeMap<String, Document>> = Mutex::new(BTreeMap::new());
+static NEXT_WINDOW: AtomicUsize = AtomicUsize::new(1);
+
+// `document` is handed to the window `label` when it asks with `launch_document`
+pub fn queue(label: &str, document: Document) {
+  PENDING.lock().unwrap().insert(label.into(), document);
+}
+
+pub fn take(label: &str) -> Option<Document> {
+  PENDING.lock().unwrap().remove(label)
+}
+
+// Open every document in a window of its own
+pub fn open_windows(app: &AppHandle, documents: Vec<Document>) -> Result<Vec<Window>> {
+  let app_conf = AppConf::read();
+  let mut windows = vec![];
+  for document in documents {
+    let label = format!("document-{}", NEXT_WINDOW.fetch_add(1, Ordering::SeqCst));
+    let title = match &document {
+      Document::File { path } => Path::new(path)
+        .file_name()
+        .map_or(path.clone(), |name| name.to_string_lossy().into()),
+      Document::Stdin { .. } => "stdin".into(),
+      Document::Fetch { url } => url.clone(),
+      Document::New { .. } => "Untitled".into(),
+    };
+    info!("launch_window: {} {}", label, title);
+    let file = match &document {
+      Document::File { path } => Some(path.clone()),
+      _ => None,
+    };
+    queue(&label, document);
+    let window = WindowBuilder::new(app, &label, WindowUrl::App("index.html".into()))
+      .title(format!("{} - JsonEditor", title))
+      .resizable(true)
+      .inner_size(app_conf.main_width, app_conf.main_height)
+      .theme(AppConf::window_theme())
+      .always_on_top(app_conf.stay_on_top)
+      .build()?;
+    if let Some(path) = file {
+      window_state::show_file(&window, &path);
+      window_state::restore(&window);
+    }
+    windows.push(window);
+  }
+  Ok(windows)
+}
+
+// macOS hands deep links to the running app instead of starting it with them
+pub fn open_links(app: &AppHandle, links: &[String]) {
+  let hosts = AppConf::read().deep_link_hosts;
+  let documents = links
+    .iter()
+    .filter_map(|link| match deep_link::parse(link, &hosts) {
+      Ok(document) => Some(document),
+      Err(e) => {
+        error!("deep_link: {}: {:#}", link, e);
+        tauri::api::dialog::message(None::<&Window>, "JsonEditor", format!("{:#}", e));
+        None
+      }
+    })
+    .collect();
+  match open_windows(app, documents) {
+    Ok(windows) => {
+      if let Some(win) = windows.last() {
+        win.set_focus().ok();
+      }
+    }
+    Err(e) => error!("deep_link: {:#}", e),
+  }
+}
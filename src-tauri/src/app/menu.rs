@@ -1,7 +1,9 @@
 use crate::{
-  conf::{self, AppConf},
+  app::{mock, popup, prefs, shortcut, tray, window_state},
+  conf::{self, AppConf, AppConfPatch},
   utils,
 };
+use log::error;
 
 use tauri::{
   AppHandle, CustomMenuItem, Manager, Menu, MenuItem, Submenu, SystemTray, SystemTrayEvent,
@@ -66,6 +68,21 @@ pub fn init() -> Menu {
     system_tray
   };
 
+  let save_window_state =
+    CustomMenuItem::new("save_window_state".to_string(), "Remember Window Positions");
+  let save_window_state_menu = if app_conf.save_window_state {
+    save_window_state.selected()
+  } else {
+    save_window_state
+  };
+
+  let popup_search = CustomMenuItem::new("popup_search".to_string(), "Popup Search");
+  let popup_search_menu = if app_conf.popup_search {
+    popup_search.selected()
+  } else {
+    popup_search
+  };
+
   let auto_update = app_conf.get_auto_update();
   let preferences_menu = Submenu::new(
     "Preferences",
@@ -76,6 +93,8 @@ pub fn init() -> Menu {
       #[cfg(target_os = "macos")]
       CustomMenuItem::new("hide_dock_icon".to_string(), "Hide Dock Icon").into(),
       system_tray_menu.into(),
+      save_window_state_menu.into(),
+      popup_search_menu.into(),
       MenuItem::Separator.into(),
       Submenu::new(
         "Theme",
@@ -128,6 +147,64 @@ pub fn init() -> Menu {
     ]),
   );
 
+  let file_menu = Submenu::new(
+    "File",
+    Menu::new()
+      .add_item(CustomMenuItem::new("open_file".to_string(), "Open...").accelerator("CmdOrCtrl+O"))
+      .add_item(
+        CustomMenuItem::new("open_url".to_string(), "Open URL...").accelerator("CmdOrCtrl+Shift+L"),
+      )
+      .add_item(CustomMenuItem::new(
+        "http_requests".to_string(),
+        "HTTP Requests...",
+      ))
+      .add_item(CustomMenuItem::new("open_har".to_string(), "Open HAR..."))
+      .add_item(
+        CustomMenuItem::new("open_folder".to_string(), "Open Folder...")
+          .accelerator("CmdOrCtrl+Shift+O"),
+      )
+      .add_item(CustomMenuItem::new(
+        "close_folder".to_string(),
+        "Close Folder",
+      ))
+      .add_item(
+        CustomMenuItem::new("search_folder".to_string(), "Find in Folder...")
+          .accelerator("CmdOrCtrl+Alt+F"),
+      )
+      .add_item(CustomMenuItem::new(
+        "validate_folder".to_string(),
+        "Validate Folder...",
+      ))
+      .add_item(CustomMenuItem::new(
+        "batch_transform".to_string(),
+        "Batch Transform...",
+      ))
+      .add_submenu(Submenu::new(
+        "Mock Server",
+        Menu::new()
+          .add_item(CustomMenuItem::new(
+            "mock_start".to_string(),
+            "Start Mock Server",
+          ))
+          .add_item(CustomMenuItem::new(
+            "mock_stop".to_string(),
+            "Stop Mock Server",
+          )),
+      ))
+      .add_native_item(MenuItem::Separator)
+      .add_item(CustomMenuItem::new(
+        "import_table".to_string(),
+        "Import CSV/XLSX...",
+      ))
+      .add_submenu(Submenu::new(
+        "Export Table",
+        Menu::new()
+          .add_item(CustomMenuItem::new("export_csv".to_string(), "CSV..."))
+          .add_item(CustomMenuItem::new("export_tsv".to_string(), "TSV..."))
+          .add_item(CustomMenuItem::new("export_xlsx".to_string(), "XLSX...")),
+      )),
+  );
+
   let edit_menu = Submenu::new(
     "Edit",
     Menu::new()
@@ -137,7 +214,21 @@ pub fn init() -> Menu {
       .add_native_item(MenuItem::Cut)
       .add_native_item(MenuItem::Copy)
       .add_native_item(MenuItem::Paste)
-      .add_native_item(MenuItem::SelectAll),
+      .add_native_item(MenuItem::SelectAll)
+      .add_native_item(MenuItem::Separator)
+      .add_item(
+        CustomMenuItem::new("search_document".to_string(), "Find and Replace...")
+          .accelerator("CmdOrCtrl+Shift+F"),
+      )
+      .add_item(CustomMenuItem::new(
+        "save_snippet".to_string(),
+        "Save as Snippet...",
+      ))
+      .add_item(CustomMenuItem::new(
+        "save_template".to_string(),
+        "Save as Template...",
+      ))
+      .add_submenu(transform_menu()),
   );
 
   let help_menu = Submenu::new(
@@ -153,10 +244,58 @@ pub fn init() -> Menu {
   Menu::new()
     .add_submenu(app_menu)
     .add_submenu(preferences_menu)
+    .add_submenu(file_menu)
     .add_submenu(edit_menu)
     .add_submenu(help_menu)
 }
 
+// --- Transform Menu
+pub fn transform_menu() -> Submenu {
+  let item = |id: &str, title: &str| CustomMenuItem::new(format!("transform_{}", id), title);
+  Submenu::new(
+    "Transform",
+    Menu::new()
+      .add_item(item("flatten_dotted", "Flatten (a.b.0)"))
+      .add_item(item("flatten_bracket", "Flatten (a.b[0])"))
+      .add_item(item("unflatten_dotted", "Unflatten (a.b.0)"))
+      .add_item(item("unflatten_bracket", "Unflatten (a.b[0])"))
+      .add_native_item(MenuItem::Separator)
+      .add_item(item("keys_camel", "Keys to camelCase"))
+      .add_item(item("keys_snake", "Keys to snake_case"))
+      .add_item(item("keys_kebab", "Keys to kebab-case"))
+      .add_item(item("keys_pascal", "Keys to PascalCase"))
+      .add_native_item(MenuItem::Separator)
+      .add_item(item("strip_nulls", "Strip Nulls"))
+      .add_item(item("strip_empty", "Strip Empty Values"))
+      .add_item(item("sort_asc", "Sort Array Ascending..."))
+      .add_item(item("sort_desc", "Sort Array Descending..."))
+      .add_item(item("dedupe", "Dedupe Array..."))
+      .add_item(item("coerce", "Coerce Numbers and Booleans")),
+  )
+}
+
+// menu id -> `engine::transform::Transform` payload, `by` is asked by the frontend
+fn transform_op(menu_id: &str) -> Option<serde_json::Value> {
+  let op = match menu_id.strip_prefix("transform_")? {
+    "flatten_dotted" => serde_json::json!({ "kind": "flatten", "style": "dotted" }),
+    "flatten_bracket" => serde_json::json!({ "kind": "flatten", "style": "bracket" }),
+    "unflatten_dotted" => serde_json::json!({ "kind": "unflatten", "style": "dotted" }),
+    "unflatten_bracket" => serde_json::json!({ "kind": "unflatten", "style": "bracket" }),
+    "keys_camel" => serde_json::json!({ "kind": "rename_keys", "case": "camel" }),
+    "keys_snake" => serde_json::json!({ "kind": "rename_keys", "case": "snake" }),
+    "keys_kebab" => serde_json::json!({ "kind": "rename_keys", "case": "kebab" }),
+    "keys_pascal" => serde_json::json!({ "kind": "rename_keys", "case": "pascal" }),
+    "strip_nulls" => serde_json::json!({ "kind": "strip_empty", "nulls_only": true }),
+    "strip_empty" => serde_json::json!({ "kind": "strip_empty", "nulls_only": false }),
+    "sort_asc" => serde_json::json!({ "kind": "sort_array", "descending": false }),
+    "sort_desc" => serde_json::json!({ "kind": "sort_array", "descending": true }),
+    "dedupe" => serde_json::json!({ "kind": "dedupe_array" }),
+    "coerce" => serde_json::json!({ "kind": "coerce_scalars" }),
+    _ => return None,
+  };
+  Some(op)
+}
+
 // --- Menu Event
 pub fn menu_handler(event: WindowMenuEvent<tauri::Wry>) {
   let win = Some(event.window()).unwrap();
@@ -177,30 +316,32 @@ pub fn menu_handler(event: WindowMenuEvent<tauri::Wry>) {
       utils::run_check_update(app, false, Some(true));
     }
     // Preferences
-    "restart" => tauri::api::process::restart(&app.env()),
+    "restart" => prefs::restart(app),
     "go_conf" => utils::open_file(utils::app_root()),
     "clear_conf" => utils::clear_conf(&app),
-    "hide_dock_icon" => {
-      AppConf::read()
-        .amend(serde_json::json!({ "hide_dock_icon": true }))
-        .write()
-        .restart(app);
-    }
-    "titlebar" => {
-      let app_conf = AppConf::read();
-      app_conf
-        .clone()
-        .amend(serde_json::json!({ "titlebar": !app_conf.titlebar }))
-        .write()
-        .restart(app);
-    }
+    "hide_dock_icon" => set_and_restart(
+      app,
+      AppConfPatch {
+        hide_dock_icon: Some(true),
+        ..Default::default()
+      },
+    ),
+    "titlebar" => set_and_restart(
+      app,
+      AppConfPatch {
+        titlebar: Some(!AppConf::read().titlebar),
+        ..Default::default()
+      },
+    ),
     "system_tray" => {
-      let app_conf = AppConf::read();
-      app_conf
-        .clone()
-        .amend(serde_json::json!({ "tray": !app_conf.tray }))
-        .write()
-        .restart(app);
+      let tray = !AppConf::read().tray;
+      let patch = AppConfPatch {
+        tray: Some(tray),
+        ..Default::default()
+      };
+      if set(&app, patch) {
+        menu_handle.get_item(menu_id).set_selected(tray).unwrap();
+      }
     }
     "theme_light" | "theme_dark" | "theme_system" => {
       let theme = match menu_id {
@@ -208,10 +349,18 @@ pub fn menu_handler(event: WindowMenuEvent<tauri::Wry>) {
         "theme_system" => "system",
         _ => "light",
       };
-      AppConf::read()
-        .amend(serde_json::json!({ "theme": theme }))
-        .write()
-        .restart(app);
+      let patch = AppConfPatch {
+        theme: Some(theme.into()),
+        ..Default::default()
+      };
+      if set(&app, patch) {
+        for id in ["theme_light", "theme_dark", "theme_system"] {
+          menu_handle
+            .get_item(id)
+            .set_selected(id == menu_id)
+            .unwrap();
+        }
+      }
     }
     "update_prompt" | "update_silent" | "update_disable" => {
       // for id in ["update_prompt", "update_silent", "update_disable"] {
@@ -241,9 +390,13 @@ pub fn menu_handler(event: WindowMenuEvent<tauri::Wry>) {
           "prompt"
         }
       };
-      AppConf::read()
-        .amend(serde_json::json!({ "auto_update": auto_update }))
-        .write();
+      set(
+        &app,
+        AppConfPatch {
+          auto_update: Some(auto_update.into()),
+          ..Default::default()
+        },
+      );
     }
     "stay_on_top" => {
       let app_conf = AppConf::read();
@@ -253,18 +406,87 @@ pub fn menu_handler(event: WindowMenuEvent<tauri::Wry>) {
         .set_selected(stay_on_top)
         .unwrap();
       win.set_always_on_top(stay_on_top).unwrap();
-      app_conf
-        .amend(serde_json::json!({ "stay_on_top": stay_on_top }))
-        .write();
+      set(
+        &app,
+        AppConfPatch {
+          stay_on_top: Some(stay_on_top),
+          ..Default::default()
+        },
+      );
+    }
+    "save_window_state" => {
+      let enabled = !window_state::is_enabled();
+      menu_handle.get_item(menu_id).set_selected(enabled).unwrap();
+      set(
+        &app,
+        AppConfPatch {
+          save_window_state: Some(enabled),
+          ..Default::default()
+        },
+      );
+      if enabled {
+        window_state::record(&win);
+      }
+    }
+    "popup_search" => {
+      let app_conf = AppConf::read();
+      let enabled = !app_conf.popup_search;
+      let accelerator = app_conf
+        .popup_search_shortcut
+        .clone()
+        .unwrap_or_else(|| popup::DEFAULT_SHORTCUT.into());
+      match popup::set_shortcut(&app, enabled.then_some(accelerator)) {
+        Ok(()) => {
+          menu_handle.get_item(menu_id).set_selected(enabled).unwrap();
+          set(
+            &app,
+            AppConfPatch {
+              popup_search: Some(enabled),
+              ..Default::default()
+            },
+          );
+        }
+        Err(e) => shortcut::report(&e),
+      }
     }
     // Help
     "quicktype_log" => utils::open_file(utils::app_root().join("JsonEditor.log")),
     "update_log" => open(&app, conf::UPDATE_LOG_URL.to_string()),
+    // File
+    "open_file" => win.emit("open-file", ()).unwrap(),
+    "open_url" => win.emit("open-url", ()).unwrap(),
+    "http_requests" => win.emit("http-requests", ()).unwrap(),
+    "open_har" => win.emit("open-har", ()).unwrap(),
+    "open_folder" => win.emit("open-folder", ()).unwrap(),
+    "close_folder" => win.emit("close-folder", ()).unwrap(),
+    "search_folder" => win.emit("search-folder", ()).unwrap(),
+    "validate_folder" => win.emit("validate-folder", ()).unwrap(),
+    "batch_transform" => win.emit("batch-transform", ()).unwrap(),
+    "mock_start" => win.emit("mock-start", ()).unwrap(),
+    "mock_stop" => mock::stop(&app),
+    "import_table" => win.emit("import-table", ()).unwrap(),
+    "export_csv" | "export_tsv" | "export_xlsx" => {
+      win
+        .emit("export-table", menu_id.trim_start_matches("export_"))
+        .unwrap();
+    }
+    // Edit
+    "search_document" => win.emit("search-document", ()).unwrap(),
+    "save_snippet" => win.emit("save-snippet", ()).unwrap(),
+    "save_template" => win.emit("save-template", ()).unwrap(),
+    id if id.starts_with("transform_") => {
+      if let Some(op) = transform_op(id) {
+        win.emit("transform", op).unwrap();
+      }
+    }
     _ => (),
   }
 }
 
 // --- SystemTray Menu
+// id of the tray icon, it is created with the app or later from Preferences > System Tray
+pub const TRAY_ID: &str = "main";
+
 pub fn tray_menu() -> SystemTray {
   if cfg!(target_os = "macos") {
     let mut tray_menu = SystemTrayMenu::new();
@@ -283,27 +505,60 @@ pub fn tray_menu() -> SystemTray {
         .add_item(CustomMenuItem::new("show_core".to_string(), "Show Window"));
     }
 
-    SystemTray::new().with_menu(
-      tray_menu
+    SystemTray::new().with_id(TRAY_ID).with_menu(
+      mock_tray_items(tray_menu.add_native_item(SystemTrayMenuItem::Separator))
         .add_native_item(SystemTrayMenuItem::Separator)
         .add_item(CustomMenuItem::new("quit".to_string(), "Quit")),
     )
   } else {
-    SystemTray::new().with_menu(
-      SystemTrayMenu::new()
-        .add_item(CustomMenuItem::new("show_core".to_string(), "Show Window"))
-        .add_native_item(SystemTrayMenuItem::Separator)
-        .add_item(CustomMenuItem::new("quit".to_string(), "Quit")),
+    SystemTray::new().with_id(TRAY_ID).with_menu(
+      mock_tray_items(
+        SystemTrayMenu::new()
+          .add_item(CustomMenuItem::new("show_core".to_string(), "Show Window"))
+          .add_native_item(SystemTrayMenuItem::Separator),
+      )
+      .add_native_item(SystemTrayMenuItem::Separator)
+      .add_item(CustomMenuItem::new("quit".to_string(), "Quit")),
     )
   }
 }
 
+// Start asks the main window for its document, stop is enabled while the server runs
+fn mock_tray_items(tray_menu: SystemTrayMenu) -> SystemTrayMenu {
+  tray_menu
+    .add_item(CustomMenuItem::new(
+      "mock_start".to_string(),
+      "Start Mock Server",
+    ))
+    .add_item(CustomMenuItem::new("mock_stop".to_string(), "Stop Mock Server").disabled())
+}
+
+// Save and apply a setting, the menu only offers valid values so a failure is the file
+// that couldn't be written
+fn set(app: &AppHandle, patch: AppConfPatch) -> bool {
+  match prefs::set(app, patch) {
+    Ok(_) => true,
+    Err(e) => {
+      error!("conf_set: {}", e);
+      tauri::api::dialog::message(app.get_window("core").as_ref(), "JsonEditor", e.to_string());
+      false
+    }
+  }
+}
+
+// Settings that are only read at startup, nothing restarts when they couldn't be saved
+fn set_and_restart(app: AppHandle, patch: AppConfPatch) {
+  if set(&app, patch) {
+    prefs::restart(app);
+  }
+}
+
 // --- SystemTray Event
 pub fn tray_handler(handle: &AppHandle, event: SystemTrayEvent) {
   let app = handle.clone();
 
   match event {
-    SystemTrayEvent::LeftClick { .. } => {
+    SystemTrayEvent::LeftClick { position, size, .. } => {
       let app_conf = AppConf::read();
 
       if !app_conf.hide_dock_icon {
@@ -312,29 +567,26 @@ pub fn tray_handler(handle: &AppHandle, event: SystemTrayEvent) {
         }
       }
 
-      if let Some(tray_win) = handle.get_window("tray") {
-        if tray_win.is_visible().unwrap() {
-          tray_win.hide().unwrap();
-        } else {
-          tray_win.show().unwrap();
-        }
-      }
+      tray::toggle(handle, position, size);
     }
     SystemTrayEvent::MenuItemClick { id, .. } => match id.as_str() {
-      "restart" => tauri::api::process::restart(&handle.env()),
-      "show_dock_icon" => {
-        AppConf::read()
-          .amend(serde_json::json!({ "hide_dock_icon": false }))
-          .write()
-          .restart(app);
-      }
+      "restart" => prefs::restart(app),
+      "show_dock_icon" => set_and_restart(
+        app,
+        AppConfPatch {
+          hide_dock_icon: Some(false),
+          ..Default::default()
+        },
+      ),
       "hide_dock_icon" => {
-        let app_conf = AppConf::read();
-        if !app_conf.hide_dock_icon {
-          app_conf
-            .amend(serde_json::json!({ "hide_dock_icon": true }))
-            .write()
-            .restart(app);
+        if !AppConf::read().hide_dock_icon {
+          set_and_restart(
+            app,
+            AppConfPatch {
+              hide_dock_icon: Some(true),
+              ..Default::default()
+            },
+          );
         }
       }
       "show_core" => {
@@ -348,6 +600,12 @@ pub fn tray_handler(handle: &AppHandle, event: SystemTrayEvent) {
           }
         };
       }
+      "mock_start" => {
+        if let Some(core_win) = app.get_window("core") {
+          core_win.emit("mock-start", ()).unwrap();
+        }
+      }
+      "mock_stop" => mock::stop(&app),
       "quit" => std::process::exit(0),
       _ => (),
     },
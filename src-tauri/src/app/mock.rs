@@ -0,0 +1,89 @@
+use crate::{
+  app::menu,
+  conf::{AppConf, AppConfPatch},
+  engine::mock::{MockConfig, MockServer},
+};
+use anyhow::Result;
+use log::{error, info};
+use serde_json::Value;
+use std::sync::Mutex;
+use tauri::{AppHandle, Manager};
+
+// running mock server, dropping it stops the server
+static MOCK: Mutex<Option<MockServer>> = Mutex::new(None);
+// label of the window whose document is served
+static SOURCE: Mutex<Option<String>> = Mutex::new(None);
+
+// Start serving `document` of the window `label`, or update the running server when the port is
+// unchanged
+pub fn start(
+  app: &AppHandle,
+  label: &str,
+  config: MockConfig,
+  document: Value,
+) -> Result<MockConfig> {
+  let mut mock = MOCK.lock().unwrap();
+  let config = match mock.as_ref() {
+    Some(server) if config.port == 0 || config.port == server.port => {
+      server.set_config(config);
+      server.set_document(document);
+      server.config()
+    }
+    _ => {
+      // free the old port first, the new server may want it
+      *mock = None;
+      let server = MockServer::start(config, document)?;
+      info!("mock_start: http://127.0.0.1:{}", server.port);
+      let patch = AppConfPatch {
+        mock_port: Some(Some(server.port)),
+        ..Default::default()
+      };
+      if let Err(e) = AppConf::set(patch) {
+        error!("conf_set: {}", e);
+      }
+      mock.insert(server).config()
+    }
+  };
+  *SOURCE.lock().unwrap() = Some(label.into());
+  notify(app, Some(&config));
+  Ok(config)
+}
+
+pub fn stop(app: &AppHandle) {
+  if MOCK.lock().unwrap().take().is_some() {
+    *SOURCE.lock().unwrap() = None;
+    info!("mock_stop");
+    notify(app, None);
+  }
+}
+
+// edits of other windows than the one that started the server are ignored
+pub fn set_document(label: &str, document: Value) {
+  if SOURCE.lock().unwrap().as_deref() != Some(label) {
+    return;
+  }
+  if let Some(server) = MOCK.lock().unwrap().as_ref() {
+    server.set_document(document);
+  }
+}
+
+// config of the running server, `None` when stopped
+pub fn status() -> Option<MockConfig> {
+  MOCK.lock().unwrap().as_ref().map(MockServer::config)
+}
+
+// Windows get a `mock-changed` event, the tray toggles its start / stop items
+fn notify(app: &AppHandle, config: Option<&MockConfig>) {
+  app.emit_all("mock-changed", config).ok();
+  // there is no tray handle when the tray is turned off
+  if let Some(tray) = app.tray_handle_by_id(menu::TRAY_ID) {
+    tray
+      .get_item("mock_start")
+      .set_enabled(config.is_none())
+      .ok();
+    tray
+      .get_item("mock_stop")
+      .set_enabled(config.is_some())
+      .ok();
+  }
+}
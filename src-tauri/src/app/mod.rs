@@ -1,3 +1,14 @@
 pub mod cmd;
+pub mod dashboard;
+pub mod instance;
+pub mod launch;
 pub mod menu;
+pub mod mock;
+pub mod popup;
+pub mod prefs;
 pub mod setup;
+pub mod shortcut;
+pub mod theme;
+pub mod tray;
+pub mod window_state;
+pub mod workspace;
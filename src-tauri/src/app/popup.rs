@@ -0,0 +1,193 @@
+use crate::{
+  app::{instance, launch, shortcut, tray},
+  cli::Document,
+  conf::AppConf,
+  engine::{
+    files,
+    fuzzy::{self, FuzzyHit, Source, SourceKind},
+    snippets,
+  },
+  utils,
+};
+use anyhow::{anyhow, Result};
+use log::{error, info};
+use serde::Deserialize;
+use std::{
+  path::{Path, PathBuf},
+  sync::Mutex,
+};
+use tauri::{
+  utils::config::WindowUrl, window::WindowBuilder, AppHandle, GlobalShortcutManager, Manager,
+};
+
+pub const LABEL: &str = "search";
+pub const DEFAULT_SHORTCUT: &str = "CmdOrCtrl+Shift+Space";
+const MAX_HITS: usize = 50;
+
+// snapshot of the documents taken when the popup opens, every keystroke searches it
+static SOURCES: Mutex<Vec<Source>> = Mutex::new(vec![]);
+// accelerator of the popup while it is registered
+static SHORTCUT: Mutex<Option<String>> = Mutex::new(None);
+
+// what the popup sends back for Enter
+#[derive(Deserialize, Debug)]
+pub struct Target {
+  pub kind: SourceKind,
+  pub id: String,
+  pub pointer: String,
+}
+
+pub fn snippets_dir() -> PathBuf {
+  utils::app_root().join("snippets")
+}
+
+pub fn init(app: &AppHandle) {
+  let app_conf = AppConf::read();
+  if app_conf.popup_search {
+    let accelerator = app_conf
+      .popup_search_shortcut
+      .unwrap_or_else(|| DEFAULT_SHORTCUT.into());
+    if let Err(e) = set_shortcut(app, Some(accelerator)) {
+      shortcut::report(&e);
+    }
+  }
+}
+
+// `None` turns the popup off, the previous accelerator is only released once the new one is
+// registered, like `shortcut::set_open_shortcut`
+pub fn set_shortcut(app: &AppHandle, accelerator: Option<String>) -> Result<()> {
+  let mut current = SHORTCUT.lock().unwrap();
+  if *current == accelerator {
+    return Ok(());
+  }
+  if let Some(accelerator) = &accelerator {
+    let handle = app.clone();
+    shortcut::register(app, accelerator, move || {
+      if let Err(e) = toggle(&handle) {
+        error!("popup_search: {}", e);
+      }
+    })?;
+  }
+  if let Some(old) = std::mem::replace(&mut *current, accelerator) {
+    app.global_shortcut_manager().unregister(&old)?;
+  }
+  Ok(())
+}
+
+// accelerator the popup is registered with, `None` while it is off
+pub fn shortcut() -> Option<String> {
+  SHORTCUT.lock().unwrap().clone()
+}
+
+// A spotlight-style window in the middle of the screen, created on first use
+pub fn toggle(app: &AppHandle) -> Result<()> {
+  let win = match app.get_window(LABEL) {
+    Some(win) if win.is_visible()? => return Ok(win.hide()?),
+    Some(win) => win,
+    None => WindowBuilder::new(app, LABEL, WindowUrl::App("index.html".into()))
+      .title("Search")
+      .inner_size(640.0, 420.0)
+      .center()
+      .resizable(false)
+      .decorations(false)
+      .skip_taskbar(true)
+      .always_on_top(true)
+      .theme(AppConf::window_theme())
+      .build()?,
+  };
+  win.center()?;
+  win.show()?;
+  win.set_focus()?;
+  win.emit("popup-search-shown", ())?;
+  Ok(())
+}
+
+// Collect open documents, recent files and snippets, the number of sources is returned.
+// Windows are asked for their documents, so this must not run on the main thread.
+pub fn refresh(app: &AppHandle) -> Result<usize> {
+  let mut sources = vec![];
+  for (label, win) in app.windows() {
+    if label == LABEL || label == tray::LABEL {
+      continue;
+    }
+    match instance::document(&win) {
+      Ok(value) => sources.push(Source {
+        kind: SourceKind::Document,
+        name: win.title().unwrap_or_else(|_| label.clone()),
+        id: label,
+        value,
+      }),
+      Err(e) => error!("popup_search: {}", e),
+    }
+  }
+  for path in tray::recent() {
+    sources.push(file_source(SourceKind::Recent, &path, file_name(&path)));
+  }
+  for snippet in snippets::list(&snippets_dir())? {
+    sources.push(file_source(
+      SourceKind::Snippet,
+      &snippet.path,
+      snippet.name,
+    ));
+  }
+  info!("popup_search_refresh: {} sources", sources.len());
+  let count = sources.len();
+  *SOURCES.lock().unwrap() = sources;
+  Ok(count)
+}
+
+// a file that can no longer be read is still found by its name
+fn file_source(kind: SourceKind, path: &str, name: String) -> Source {
+  Source {
+    kind,
+    id: path.into(),
+    name,
+    value: files::read_value(Path::new(path)).unwrap_or_default(),
+  }
+}
+
+fn file_name(path: &str) -> String {
+  Path::new(path)
+    .file_name()
+    .map_or(path.into(), |name| name.to_string_lossy().into())
+}
+
+pub fn search(query: &str) -> Vec<FuzzyHit> {
+  fuzzy::search(query, &SOURCES.lock().unwrap(), MAX_HITS)
+}
+
+// Show the hit: open documents scroll to it, files open in the main window at the pointer
+// (the frontend opens them in a new window instead when the main one has unsaved edits)
+pub fn open(app: &AppHandle, target: Target) -> Result<()> {
+  if let Some(popup) = app.get_window(LABEL) {
+    popup.hide()?;
+  }
+  let (win, event, payload) = match target.kind {
+    SourceKind::Document => (
+      app
+        .get_window(&target.id)
+        .ok_or_else(|| anyhow!("window `{}` was closed", target.id))?,
+      "reveal-pointer",
+      serde_json::json!({ "pointer": target.pointer }),
+    ),
+    SourceKind::Recent | SourceKind::Snippet => match app.get_window("core") {
+      Some(win) => (
+        win,
+        "open-document",
+        serde_json::json!({ "path": target.id, "pointer": target.pointer }),
+      ),
+      None => {
+        let windows = launch::open_windows(app, vec![Document::File { path: target.id }])?;
+        if let Some(win) = windows.last() {
+          win.set_focus()?;
+        }
+        return Ok(());
+      }
+    },
+  };
+  win.show()?;
+  win.unminimize()?;
+  win.set_focus()?;
+  win.emit(event, payload)?;
+  Ok(())
+}
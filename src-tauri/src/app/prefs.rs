@@ -0,0 +1,111 @@
+use crate::{
+  app::{dashboard, menu, popup, shortcut, theme, tray, window_state},
+  conf::{AppConf, AppConfPatch, ConfErrors},
+};
+use anyhow::{anyhow, Result};
+use log::{error, info};
+use serde_json::Value;
+use tauri::{AppHandle, Manager};
+
+// Save `patch` and apply it to the running app, every window gets `conf-changed`
+pub fn set(app: &AppHandle, patch: AppConfPatch) -> Result<AppConf, ConfErrors> {
+  let old = AppConf::read();
+  let new = AppConf::set(patch)?;
+  apply(app, &old, &new);
+  Ok(new)
+}
+
+// `set` for the JSON object of settings sent by the frontend
+pub fn set_json(app: &AppHandle, json: Value) -> Result<AppConf, ConfErrors> {
+  let old = AppConf::read();
+  let new = AppConf::set_json(json)?;
+  apply(app, &old, &new);
+  Ok(new)
+}
+
+// `titlebar` and `hide_dock_icon` are only read while the app starts, see `restart`
+fn apply(app: &AppHandle, old: &AppConf, new: &AppConf) {
+  if old.tray != new.tray {
+    if let Err(e) = set_tray(app, new.tray) {
+      error!("system_tray: {}", e);
+    }
+  }
+  if old.theme != new.theme {
+    theme::refresh(app);
+  }
+  if old.stay_on_top != new.stay_on_top {
+    if let Some(core) = app.get_window("core") {
+      core.set_always_on_top(new.stay_on_top).ok();
+    }
+  }
+  window_state::set_enabled(new.save_window_state);
+  if old.global_shortcut != new.global_shortcut {
+    if let Err(e) = shortcut::set_open_shortcut(app, new.global_shortcut.clone()) {
+      shortcut::report(&e);
+    }
+  }
+  if old.clipboard_shortcuts != new.clipboard_shortcuts {
+    let shortcuts = new.clipboard_shortcuts.clone().unwrap_or_default();
+    let errors = shortcut::set_clipboard_shortcuts(app, shortcuts);
+    if !errors.is_empty() {
+      shortcut::report(&anyhow!("{}", errors.join("\n")));
+    }
+  }
+  // the menu registers the popup itself to keep the setting off when that fails
+  let popup_shortcut = new.popup_search.then(|| {
+    new
+      .popup_search_shortcut
+      .clone()
+      .unwrap_or_else(|| popup::DEFAULT_SHORTCUT.into())
+  });
+  if popup::shortcut() != popup_shortcut {
+    if let Err(e) = popup::set_shortcut(app, popup_shortcut) {
+      shortcut::report(&e);
+    }
+  }
+  // windows pick up the dashboard settings
+  app.emit_all("conf-changed", new).ok();
+}
+
+// The icon is added and removed at runtime, the popover window is kept hidden when it goes
+fn set_tray(app: &AppHandle, enabled: bool) -> Result<()> {
+  info!("system_tray: {}", enabled);
+  let handle = app.tray_handle_by_id(menu::TRAY_ID);
+  if enabled {
+    if handle.is_none() {
+      menu::tray_menu().build(app)?;
+    }
+    if app.get_window(tray::LABEL).is_none() {
+      tray::init(app)?;
+    }
+    tray::watch_clipboard(app);
+  } else {
+    tray::stop_watching();
+    if let Some(handle) = handle {
+      handle.destroy()?;
+    }
+    if let Some(win) = app.get_window(tray::LABEL) {
+      win.hide()?;
+    }
+  }
+  Ok(())
+}
+
+// Unsaved edits are kept as recoverable buffers, but losing the open windows is still asked
+pub fn restart(app: AppHandle) {
+  let unsaved = dashboard::unsaved();
+  if unsaved == 0 {
+    tauri::api::process::restart(&app.env());
+    return;
+  }
+  let msg = format!(
+    "{} window(s) have unsaved changes. They can be recovered from the start dashboard after the restart.\n\nRestart now?",
+    unsaved
+  );
+  let parent = app.get_window("core");
+  tauri::api::dialog::ask(parent.as_ref(), "Restart JsonEditor", msg, move |is_ok| {
+    if is_ok {
+      tauri::api::process::restart(&app.env());
+    }
+  });
+}
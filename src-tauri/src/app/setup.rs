@@ -1,18 +1,33 @@
-use crate::{conf::AppConf, utils};
-use log::{info};
+use crate::{
+  app::{instance, launch, popup, shortcut, theme, tray, window_state},
+  cli::Document,
+  conf::AppConf,
+  utils,
+};
+use log::{error, info};
 use tauri::{utils::config::WindowUrl, window::WindowBuilder, App};
 
-pub fn init(app: &mut App) -> std::result::Result<(), Box<dyn std::error::Error>> {
+pub fn init(
+  app: &mut App,
+  documents: Vec<Document>,
+  primary: instance::Primary,
+) -> std::result::Result<(), Box<dyn std::error::Error>> {
   info!("stepup");
   let app_conf = AppConf::read();
-  let theme = AppConf::theme_mode();
+  window_state::init();
+  let theme = AppConf::window_theme();
   // let handle = app.app_handle();
 
   let app_conf2 = app_conf.clone();
+  // the first document from the command line opens in the main window, the others get their own
+  let mut documents = documents.into_iter();
   if app_conf.hide_dock_icon {
     #[cfg(target_os = "macos")]
     app.set_activation_policy(tauri::ActivationPolicy::Accessory);
   } else {
+    if let Some(document) = documents.next() {
+      launch::queue("core", document);
+    }
     let app = app.handle();
     tauri::async_runtime::spawn(async move {
       let link = "index.html";
@@ -22,7 +37,7 @@ pub fn init(app: &mut App) -> std::result::Result<(), Box<dyn std::error::Error>
         .resizable(true)
         .fullscreen(false)
         .inner_size(app_conf2.main_width, app_conf2.main_height)
-        .theme(Some(theme))
+        .theme(theme)
         .always_on_top(app_conf2.stay_on_top);
 
       #[cfg(target_os = "macos")]
@@ -32,10 +47,30 @@ pub fn init(app: &mut App) -> std::result::Result<(), Box<dyn std::error::Error>
           .hidden_title(true);
       }
 
-      main_win.build().unwrap();
+      let main_win = main_win.build().unwrap();
+      window_state::restore(&main_win);
     });
   }
 
+  launch::open_windows(&app.handle(), documents.collect())?;
+  instance::listen(&app.handle(), primary);
+  let warnings = AppConf::take_warnings();
+  if !warnings.is_empty() {
+    tauri::api::dialog::message(
+      None::<&tauri::Window>,
+      "JsonEditor Settings",
+      warnings.join("\n\n"),
+    );
+  }
+  shortcut::init(&app.handle());
+  popup::init(&app.handle());
+  theme::init(&app.handle());
+  if app_conf.tray {
+    if let Err(e) = tray::init(&app.handle()) {
+      error!("tray_window: {}", e);
+    }
+  }
+
   // auto_update
   let auto_update = app_conf.get_auto_update();
   if auto_update != "disable" {
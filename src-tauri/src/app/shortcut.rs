@@ -0,0 +1,145 @@
+use crate::{
+  app::launch,
+  cli::Document,
+  conf::AppConf,
+  engine::{
+    clipboard::{self, ClipboardAction, ClipboardShortcut},
+    repair,
+  },
+};
+use anyhow::{anyhow, bail, Result};
+use log::{error, info};
+use serde_json::Value;
+use std::sync::Mutex;
+use tauri::{
+  api::notification::Notification, AppHandle, ClipboardManager, GlobalShortcutManager, Manager,
+  Window,
+};
+
+// accelerator of `global_shortcut` while it is registered
+static OPEN_SHORTCUT: Mutex<Option<String>> = Mutex::new(None);
+// accelerators of `clipboard_shortcuts` that were registered
+static CLIPBOARD_SHORTCUTS: Mutex<Vec<String>> = Mutex::new(vec![]);
+
+pub fn init(app: &AppHandle) {
+  let app_conf = AppConf::read();
+  if let Err(e) = set_open_shortcut(app, app_conf.global_shortcut) {
+    report(&e);
+  }
+  let errors = set_clipboard_shortcuts(app, app_conf.clipboard_shortcuts.unwrap_or_default());
+  if !errors.is_empty() {
+    report(&anyhow!("{}", errors.join("\n")));
+  }
+}
+
+// `global_shortcut` opens the clipboard as a document, the previous accelerator is only released
+// once the new one is registered, so a bad setting keeps the old hotkey working
+pub fn set_open_shortcut(app: &AppHandle, accelerator: Option<String>) -> Result<()> {
+  let mut current = OPEN_SHORTCUT.lock().unwrap();
+  let accelerator = accelerator.filter(|a| !a.trim().is_empty());
+  if *current == accelerator {
+    return Ok(());
+  }
+  if let Some(accelerator) = &accelerator {
+    let handle = app.clone();
+    register(app, accelerator, move || open_clipboard(&handle))?;
+  }
+  if let Some(old) = std::mem::replace(&mut *current, accelerator) {
+    app.global_shortcut_manager().unregister(&old)?;
+  }
+  Ok(())
+}
+
+// Replace the clipboard hotkeys, a binding that fails does not keep the others from working
+pub fn set_clipboard_shortcuts(app: &AppHandle, shortcuts: Vec<ClipboardShortcut>) -> Vec<String> {
+  let mut current = CLIPBOARD_SHORTCUTS.lock().unwrap();
+  let mut errors = vec![];
+  for old in current.drain(..) {
+    if let Err(e) = app.global_shortcut_manager().unregister(&old) {
+      errors.push(format!("{:#}", e));
+    }
+  }
+  for shortcut in shortcuts {
+    let handle = app.clone();
+    let action = shortcut.action;
+    match register(app, &shortcut.accelerator, move || {
+      transform_clipboard(&handle, action)
+    }) {
+      Ok(()) => current.push(shortcut.accelerator),
+      Err(e) => errors.push(format!("{:#}", e)),
+    }
+  }
+  errors
+}
+
+// Register a system-wide hotkey, the accelerator is checked first because
+// the runtime panics on strings it cannot parse
+pub fn register<F>(app: &AppHandle, accelerator: &str, handler: F) -> Result<()>
+where
+  F: Fn() + Send + 'static,
+{
+  clipboard::validate_accelerator(accelerator)?;
+  let mut manager = app.global_shortcut_manager();
+  if manager.is_registered(accelerator)? {
+    bail!("shortcut {} is already in use by JsonEditor", accelerator);
+  }
+  manager
+    .register(accelerator, handler)
+    .map_err(|e| anyhow!("shortcut {} is not available: {}", accelerator, e))?;
+  info!("global_shortcut: {}", accelerator);
+  Ok(())
+}
+
+// The clipboard text as JSON, repaired if it needs to be
+pub fn clipboard_json(app: &AppHandle) -> Result<Value> {
+  let text = app
+    .clipboard_manager()
+    .read_text()?
+    .ok_or_else(|| anyhow!("the clipboard has no text"))?;
+  repair::repair(&text)
+}
+
+// Repair the clipboard text and open it in a window of its own
+fn open_clipboard(app: &AppHandle) {
+  let result =
+    clipboard_json(app).and_then(|json| launch::open_windows(app, vec![Document::New { json }]));
+  match result {
+    Ok(windows) => {
+      if let Some(win) = windows.last() {
+        win.set_focus().ok();
+      }
+    }
+    Err(e) => report(&e.context("Clipboard")),
+  }
+}
+
+// Rewrite the clipboard in place, the result is shown as a notification
+fn transform_clipboard(app: &AppHandle, action: ClipboardAction) {
+  let mut manager = app.clipboard_manager();
+  let result = manager
+    .read_text()
+    .map_err(Into::into)
+    .and_then(|text| text.ok_or_else(|| anyhow!("the clipboard has no text")))
+    .and_then(|text| clipboard::apply(action, &text))
+    .and_then(|text| manager.write_text(text).map_err(Into::into));
+  let body = match &result {
+    Ok(()) => "Clipboard updated".to_string(),
+    Err(e) => {
+      error!("clipboard_shortcut: {:?} {:#}", action, e);
+      format!("{:#}", e)
+    }
+  };
+  let identifier = &app.config().tauri.bundle.identifier;
+  if let Err(e) = Notification::new(identifier)
+    .title(format!("{} - JsonEditor", action.title()))
+    .body(body)
+    .show()
+  {
+    error!("clipboard_shortcut: notification: {}", e);
+  }
+}
+
+pub fn report(e: &anyhow::Error) {
+  error!("global_shortcut: {:#}", e);
+  tauri::api::dialog::message(None::<&Window>, "JsonEditor", format!("{:#}", e));
+}
@@ -0,0 +1,111 @@
+use crate::conf::AppConf;
+use log::info;
+use std::{
+  sync::{
+    atomic::{AtomicBool, Ordering},
+    Mutex,
+  },
+  thread,
+  time::Duration,
+};
+use tauri::{AppHandle, Manager, Theme};
+
+// the OS is still asked this often in case a switch went unnoticed, e.g. without a portal on Linux
+const POLL_INTERVAL: Duration = Duration::from_secs(30);
+
+// effective theme the windows were last told about
+static CURRENT: Mutex<Option<Theme>> = Mutex::new(None);
+// mirrors `theme == "system"`, the watchers idle otherwise
+static FOLLOW_SYSTEM: AtomicBool = AtomicBool::new(false);
+
+// Follow the OS theme, windows get `theme-changed` with "light" or "dark". Switches arrive as
+// `ThemeChanged` of windows created without a theme (macOS, Windows) or as portal signals (Linux).
+// The native frame keeps the theme it was created with, the pages re-theme themselves.
+pub fn init(app: &AppHandle) {
+  refresh(app);
+  #[cfg(target_os = "linux")]
+  watch_portal(app.clone());
+  let app = app.clone();
+  thread::spawn(move || loop {
+    thread::sleep(POLL_INTERVAL);
+    system_changed(&app);
+  });
+}
+
+// `SettingChanged` of the desktop portal as printed by `gdbus monitor`, e.g.
+// `...SettingChanged ('org.freedesktop.appearance', 'color-scheme', <uint32 1>)`
+#[cfg(target_os = "linux")]
+fn watch_portal(app: AppHandle) {
+  use std::{
+    io::{BufRead, BufReader},
+    process::{Command, Stdio},
+  };
+  let child = Command::new("gdbus")
+    .args([
+      "monitor",
+      "--session",
+      "--dest",
+      "org.freedesktop.portal.Desktop",
+      "--object-path",
+      "/org/freedesktop/portal/desktop",
+    ])
+    .stdin(Stdio::null())
+    .stdout(Stdio::piped())
+    .stderr(Stdio::null())
+    .spawn();
+  let mut child = match child {
+    Ok(child) => child,
+    Err(e) => {
+      info!("theme_watch: gdbus: {}, polling only", e);
+      return;
+    }
+  };
+  let Some(stdout) = child.stdout.take() else {
+    return;
+  };
+  thread::spawn(move || {
+    for line in BufReader::new(stdout).lines().map_while(Result::ok) {
+      if line.contains("'org.freedesktop.appearance', 'color-scheme'") {
+        system_changed(&app);
+      }
+    }
+    child.wait().ok();
+    info!("theme_watch: gdbus exited, polling only");
+  });
+}
+
+fn system_changed(app: &AppHandle) {
+  if FOLLOW_SYSTEM.load(Ordering::SeqCst) {
+    update(app, AppConf::system_theme());
+  }
+}
+
+// Called when the `theme` setting or the theme of the OS changed
+pub fn refresh(app: &AppHandle) {
+  FOLLOW_SYSTEM.store(AppConf::get_theme() == "system", Ordering::SeqCst);
+  update(app, AppConf::theme_mode());
+}
+
+fn update(app: &AppHandle, theme: Theme) {
+  let mut current = CURRENT.lock().unwrap();
+  if *current == Some(theme) {
+    return;
+  }
+  *current = Some(theme);
+  drop(current);
+  info!("theme_changed: {}", name(theme));
+  app.emit_all("theme-changed", name(theme)).ok();
+}
+
+// "light" or "dark"
+pub fn effective() -> &'static str {
+  let current = *CURRENT.lock().unwrap();
+  name(current.unwrap_or_else(AppConf::theme_mode))
+}
+
+fn name(theme: Theme) -> &'static str {
+  match theme {
+    Theme::Dark => "dark",
+    _ => "light",
+  }
+}
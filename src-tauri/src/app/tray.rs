@@ -0,0 +1,197 @@
+use crate::{
+  app::launch,
+  cli::Document,
+  conf::AppConf,
+  engine::{files, repair},
+  utils,
+};
+use anyhow::Result;
+use log::{error, info};
+use serde::Serialize;
+use std::{
+  fs,
+  path::PathBuf,
+  sync::{
+    mpsc::{self, RecvTimeoutError, Sender},
+    Mutex,
+  },
+  thread,
+  time::Duration,
+};
+use tauri::{
+  utils::config::WindowUrl, window::WindowBuilder, AppHandle, ClipboardManager, Manager,
+  PhysicalPosition, PhysicalSize, Window,
+};
+
+pub const LABEL: &str = "tray";
+const MAX_RECENT: usize = 10;
+const MAX_CLIPBOARD: usize = 20;
+// larger clipboard contents are not kept in the history
+const MAX_CLIPBOARD_SIZE: usize = 1024 * 1024;
+
+// JSON copied since launch, newest first
+static CLIPBOARD: Mutex<Vec<String>> = Mutex::new(vec![]);
+// stops the clipboard watcher when dropped
+static WATCHER: Mutex<Option<Sender<()>>> = Mutex::new(None);
+
+#[derive(Serialize, Debug)]
+pub struct TrayState {
+  pub recent: Vec<String>,
+  pub clipboard: Vec<String>,
+}
+
+// The popover of the tray icon: a scratchpad, recent documents and clipboard history.
+// It is created hidden and shown by a left click on the icon.
+pub fn init(app: &AppHandle) -> Result<()> {
+  let app_conf = AppConf::read();
+  info!(
+    "tray_window: {}x{}",
+    app_conf.tray_width, app_conf.tray_height
+  );
+  WindowBuilder::new(app, LABEL, WindowUrl::App("index.html".into()))
+    .title("JsonEditor")
+    .inner_size(app_conf.tray_width, app_conf.tray_height)
+    .resizable(false)
+    .decorations(false)
+    .skip_taskbar(true)
+    .always_on_top(true)
+    .visible(false)
+    .theme(AppConf::window_theme())
+    .build()?;
+  watch_clipboard(app);
+  Ok(())
+}
+
+// `position` and `size` are the tray icon's, the window opens below it (above it for bottom taskbars)
+pub fn toggle(app: &AppHandle, position: PhysicalPosition<f64>, size: PhysicalSize<f64>) {
+  let Some(win) = app.get_window(LABEL) else {
+    return;
+  };
+  if win.is_visible().unwrap_or(false) {
+    win.hide().ok();
+    return;
+  }
+  if let Err(e) = place(&win, position, size) {
+    error!("tray_window: {}", e);
+  }
+  win.show().ok();
+  win.set_focus().ok();
+  win.emit("tray-shown", ()).ok();
+}
+
+fn place(win: &Window, icon: PhysicalPosition<f64>, icon_size: PhysicalSize<f64>) -> Result<()> {
+  let size = win.outer_size()?;
+  let (width, height) = (size.width as f64, size.height as f64);
+  let monitor = win
+    .available_monitors()?
+    .into_iter()
+    .find(|m| {
+      let (p, s) = (m.position(), m.size());
+      icon.x >= p.x as f64
+        && icon.x < p.x as f64 + s.width as f64
+        && icon.y >= p.y as f64
+        && icon.y < p.y as f64 + s.height as f64
+    })
+    .or(win.current_monitor()?);
+  let mut x = icon.x + icon_size.width / 2.0 - width / 2.0;
+  let mut y = icon.y + icon_size.height;
+  if let Some(monitor) = monitor {
+    let (left, top) = (monitor.position().x as f64, monitor.position().y as f64);
+    let (right, bottom) = (
+      left + monitor.size().width as f64,
+      top + monitor.size().height as f64,
+    );
+    if icon.y > top + (bottom - top) / 2.0 {
+      y = icon.y - height;
+    }
+    x = x.min(right - width).max(left);
+    y = y.min(bottom - height).max(top);
+  }
+  win.set_position(PhysicalPosition::new(x, y))?;
+  Ok(())
+}
+
+pub fn state() -> TrayState {
+  TrayState {
+    recent: recent(),
+    clipboard: CLIPBOARD.lock().unwrap().clone(),
+  }
+}
+
+// Open a recent file or a clipboard entry in an editor window
+pub fn open(app: &AppHandle, document: Document) -> Result<()> {
+  if let Some(win) = launch::open_windows(app, vec![document])?.last() {
+    win.set_focus()?;
+  }
+  if let Some(tray) = app.get_window(LABEL) {
+    tray.hide()?;
+  }
+  Ok(())
+}
+
+fn recent_file() -> PathBuf {
+  utils::app_root().join("recent.json")
+}
+
+pub fn recent() -> Vec<String> {
+  fs::read_to_string(recent_file())
+    .ok()
+    .and_then(|text| serde_json::from_str(&text).ok())
+    .unwrap_or_default()
+}
+
+// Remember a document opened from disk, newest first
+pub fn add_recent(path: &str) {
+  let mut paths = recent();
+  paths.retain(|p| p != path);
+  paths.insert(0, path.into());
+  paths.truncate(MAX_RECENT);
+  let result = serde_json::to_string_pretty(&paths)
+    .map_err(Into::into)
+    .and_then(|text| files::write_atomic(&recent_file(), &text));
+  if let Err(e) = result {
+    error!("recent_documents: {}", e);
+  }
+}
+
+// Keep the JSON the user copies, other clipboard contents are ignored. Runs while the tray is
+// on, `stop_watching` ends it
+pub fn watch_clipboard(app: &AppHandle) {
+  let mut watcher = WATCHER.lock().unwrap();
+  if watcher.is_some() {
+    return;
+  }
+  let (tx, rx) = mpsc::channel::<()>();
+  *watcher = Some(tx);
+  let app = app.clone();
+  thread::spawn(move || {
+    let mut last = None;
+    // nothing is ever sent, dropping the sender wakes the thread to stop
+    while let Err(RecvTimeoutError::Timeout) = rx.recv_timeout(Duration::from_secs(1)) {
+      let Ok(Some(text)) = app.clipboard_manager().read_text() else {
+        continue;
+      };
+      if last.as_ref() == Some(&text) {
+        continue;
+      }
+      last = Some(text.clone());
+      let is_json = text.len() <= MAX_CLIPBOARD_SIZE
+        && repair::repair(&text).is_ok_and(|value| value.is_object() || value.is_array());
+      if !is_json {
+        continue;
+      }
+      let mut history = CLIPBOARD.lock().unwrap();
+      history.retain(|entry| *entry != text);
+      history.insert(0, text);
+      history.truncate(MAX_CLIPBOARD);
+      drop(history);
+      if let Some(win) = app.get_window(LABEL) {
+        win.emit("tray-clipboard", ()).ok();
+      }
+    }
+  });
+}
+
+pub fn stop_watching() {
+  WATCHER.lock().unwrap().take();
+}
@@ -0,0 +1,217 @@
+use crate::{conf::AppConf, engine::files, utils};
+use anyhow::Result;
+use log::{error, info};
+use serde::{Deserialize, Serialize};
+use std::{
+  collections::BTreeMap,
+  fs,
+  path::PathBuf,
+  sync::{
+    atomic::{AtomicBool, Ordering},
+    mpsc::{self, Receiver, Sender},
+    Mutex, OnceLock,
+  },
+  thread,
+  time::Duration,
+};
+use tauri::{Monitor, PhysicalPosition, PhysicalSize, Window};
+
+// Geometry of a window in physical pixels, `x`/`y`/`width`/`height` are the
+// last un-maximized bounds so un-maximizing after a restore goes back to them
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct WindowGeometry {
+  pub x: i32,
+  pub y: i32,
+  pub width: u32,
+  pub height: u32,
+  pub maximized: bool,
+  // name of the monitor the window was on
+  pub monitor: Option<String>,
+}
+
+// mirrors `save_window_state`, move and resize events are too frequent to read the config
+static ENABLED: AtomicBool = AtomicBool::new(false);
+// the main window under its label, document windows under the path of their file: labels
+// are handed out again and a window can open another file after it was launched
+static STATES: Mutex<BTreeMap<String, WindowGeometry>> = Mutex::new(BTreeMap::new());
+const MAIN_WINDOW: &str = "core";
+// file shown by each document window, by window label
+static DOCUMENTS: Mutex<BTreeMap<String, String>> = Mutex::new(BTreeMap::new());
+// changes for the writer thread, started by the first one
+static CHANGES: OnceLock<Sender<()>> = OnceLock::new();
+const SAVE_DELAY: Duration = Duration::from_millis(500);
+
+fn state_file() -> PathBuf {
+  utils::app_root().join("window-state.json")
+}
+
+pub fn init() {
+  ENABLED.store(AppConf::read().save_window_state, Ordering::SeqCst);
+  *STATES.lock().unwrap() = fs::read_to_string(state_file())
+    .ok()
+    .and_then(|text| serde_json::from_str(&text).ok())
+    .unwrap_or_default();
+}
+
+pub fn is_enabled() -> bool {
+  ENABLED.load(Ordering::SeqCst)
+}
+
+// kept in sync by `prefs::set`
+pub fn set_enabled(enabled: bool) {
+  ENABLED.store(enabled, Ordering::SeqCst);
+}
+
+// where the geometry of `win` is kept, none for a window without a file
+fn key(win: &Window) -> Option<String> {
+  if win.label() == MAIN_WINDOW {
+    return Some(MAIN_WINDOW.into());
+  }
+  DOCUMENTS.lock().unwrap().get(win.label()).cloned()
+}
+
+// `win` shows the file at `path`, its moves are saved for that file from now on
+pub fn show_file(win: &Window, path: &str) {
+  if win.label() != MAIN_WINDOW {
+    DOCUMENTS
+      .lock()
+      .unwrap()
+      .insert(win.label().into(), path.into());
+  }
+}
+
+// the label can go to another window once this one is closed
+pub fn forget(label: &str) {
+  DOCUMENTS.lock().unwrap().remove(label);
+}
+
+// Put `win` where it was last time, moved onto a connected monitor if that one is gone
+pub fn restore(win: &Window) {
+  if !is_enabled() {
+    return;
+  }
+  let Some(state) = key(win).and_then(|key| STATES.lock().unwrap().get(&key).cloned()) else {
+    return;
+  };
+  info!("window_restore: {} {:?}", win.label(), state);
+  let result = win
+    .available_monitors()
+    .map_err(Into::into)
+    .and_then(|monitors| {
+      let state = clamp(state, &monitors);
+      win.set_size(PhysicalSize::new(state.width, state.height))?;
+      win.set_position(PhysicalPosition::new(state.x, state.y))?;
+      if state.maximized {
+        win.maximize()?;
+      }
+      Ok(())
+    });
+  if let Err(e) = result {
+    error!("window_restore: {}", e);
+  }
+}
+
+// Called on move, resize and close
+pub fn record(win: &Window) {
+  if !is_enabled() || win.is_minimized().unwrap_or(false) {
+    return;
+  }
+  let Some(key) = key(win) else {
+    return;
+  };
+  match geometry(win, &key) {
+    Ok(geometry) => {
+      let mut states = STATES.lock().unwrap();
+      if states.get(&key) == Some(&geometry) {
+        return;
+      }
+      states.insert(key, geometry);
+      drop(states);
+      save_later();
+    }
+    Err(e) => error!("window_record: {}", e),
+  }
+}
+
+fn geometry(win: &Window, key: &str) -> Result<WindowGeometry> {
+  let maximized = win.is_maximized()?;
+  let monitor = win.current_monitor()?.and_then(|m| m.name().cloned());
+  // a maximized window keeps the bounds it had before
+  if maximized {
+    if let Some(state) = STATES.lock().unwrap().get(key) {
+      return Ok(WindowGeometry {
+        maximized,
+        monitor,
+        ..state.clone()
+      });
+    }
+  }
+  let position = win.outer_position()?;
+  let size = win.inner_size()?;
+  Ok(WindowGeometry {
+    x: position.x,
+    y: position.y,
+    width: size.width,
+    height: size.height,
+    maximized,
+    monitor,
+  })
+}
+
+fn save_later() {
+  let changes = CHANGES.get_or_init(|| {
+    let (tx, rx) = mpsc::channel();
+    thread::spawn(move || save_when_idle(rx));
+    tx
+  });
+  changes.send(()).ok();
+}
+
+// Writes the states once no change came in for `SAVE_DELAY`, a window being dragged
+// sends a stream of them
+fn save_when_idle(changes: Receiver<()>) {
+  while changes.recv().is_ok() {
+    while changes.recv_timeout(SAVE_DELAY).is_ok() {}
+    let result = serde_json::to_string_pretty(&*STATES.lock().unwrap())
+      .map_err(Into::into)
+      .and_then(|text| files::write_atomic(&state_file(), &text));
+    if let Err(e) = result {
+      error!("window_state_save: {}", e);
+    }
+  }
+}
+
+// Keep the window on its monitor when it is still connected, otherwise on the one it
+// overlaps most (or the first), shrunk to fit and moved inside its bounds
+fn clamp(mut state: WindowGeometry, monitors: &[Monitor]) -> WindowGeometry {
+  let overlap = |m: &Monitor| {
+    let (p, s) = (m.position(), m.size());
+    let w = (state.x + state.width as i32).min(p.x + s.width as i32) - state.x.max(p.x);
+    let h = (state.y + state.height as i32).min(p.y + s.height as i32) - state.y.max(p.y);
+    w.max(0) as i64 * h.max(0) as i64
+  };
+  let monitor = monitors
+    .iter()
+    .find(|m| state.monitor.is_some() && m.name() == state.monitor.as_ref() && overlap(m) > 0)
+    .or_else(|| {
+      monitors
+        .iter()
+        .filter(|m| overlap(m) > 0)
+        .max_by_key(|m| overlap(m))
+    })
+    .or_else(|| monitors.first());
+  let Some(monitor) = monitor else {
+    return state;
+  };
+  let (p, s) = (monitor.position(), monitor.size());
+  state.width = state.width.min(s.width);
+  state.height = state.height.min(s.height);
+  state.x = state
+    .x
+    .clamp(p.x, p.x + s.width as i32 - state.width as i32);
+  state.y = state
+    .y
+    .clamp(p.y, p.y + s.height as i32 - state.height as i32);
+  state.monitor = monitor.name().cloned();
+  state
+}
@@ -0,0 +1,155 @@
+use crate::{
+  conf::{AppConf, AppConfPatch},
+  engine::{
+    files::Ignores,
+    workspace::{self, Workspace},
+  },
+};
+use anyhow::Result;
+use log::{error, info};
+use notify::{recommended_watcher, Event, RecommendedWatcher, RecursiveMode, Watcher};
+use std::{
+  collections::BTreeMap,
+  path::{Path, PathBuf},
+  sync::{
+    mpsc::{self, Sender},
+    Mutex, OnceLock,
+  },
+  thread,
+};
+use tauri::Window;
+use walkdir::WalkDir;
+
+// watched folder of each window by label, dropping a watch stops it
+static WATCHES: Mutex<BTreeMap<String, Watch>> = Mutex::new(BTreeMap::new());
+// directories created in a watched folder, they are added on a thread of their own
+// because a watcher can't be changed from inside its event handler
+static NEW_DIRS: OnceLock<Sender<(String, PathBuf)>> = OnceLock::new();
+
+struct Watch {
+  root: PathBuf,
+  watcher: RecommendedWatcher,
+}
+
+// The folder opens even when it can't be watched, e.g. once the OS runs out of watches
+pub fn open(window: &Window, root: &Path) -> Result<Workspace> {
+  let ws = workspace::open(root)?;
+  info!("workspace_open: {} {}", window.label(), ws.root);
+  if let Err(e) = watch(window, PathBuf::from(&ws.root)) {
+    error!("workspace_watch: {}: {:#}", ws.root, e);
+  }
+  save_workspace(Some(ws.root.clone()));
+  Ok(ws)
+}
+
+pub fn close(window: &Window) {
+  info!("workspace_close: {}", window.label());
+  unwatch(window.label());
+  save_workspace(None);
+}
+
+// Stop watching the folder of a closed window
+pub fn unwatch(label: &str) {
+  // dropped after the lock is released, a watcher waits for its event thread to stop
+  let watch = WATCHES.lock().unwrap().remove(label);
+  drop(watch);
+}
+
+// reopened on the next start
+fn save_workspace(root: Option<String>) {
+  let patch = AppConfPatch {
+    workspace: Some(root),
+    ..Default::default()
+  };
+  if let Err(e) = AppConf::set(patch) {
+    error!("conf_set: {}", e);
+  }
+}
+
+// Changes below the root are sent to the window as `workspace-changed` events. Directories are
+// watched one by one so that `.gitignore`d trees such as `node_modules` take no watches.
+fn watch(window: &Window, root: PathBuf) -> Result<()> {
+  let win = window.clone();
+  let events_root = root.clone();
+  let mut watcher = recommended_watcher(move |res: notify::Result<Event>| match res {
+    Ok(event) => changed(&win, &events_root, event),
+    Err(err) => error!("workspace_watch: {}", err),
+  })?;
+  let result = watch_tree(&mut watcher, &root, &root);
+  // a partly watched folder is kept, it still reports the directories it got
+  let old = WATCHES
+    .lock()
+    .unwrap()
+    .insert(window.label().into(), Watch { root, watcher });
+  drop(old);
+  result
+}
+
+// `dir` and the directories below it that `.gitignore` does not exclude
+fn watch_tree(watcher: &mut RecommendedWatcher, root: &Path, dir: &Path) -> Result<()> {
+  let mut ignores = match dir.parent() {
+    Some(parent) if dir != root => Ignores::for_dir(root, parent),
+    _ => Ignores::default(),
+  };
+  let dirs = WalkDir::new(dir)
+    .follow_links(false)
+    .into_iter()
+    .filter_entry(move |entry| {
+      if !entry.file_type().is_dir()
+        || (entry.path() != root && ignores.is_ignored(entry.path(), true))
+      {
+        return false;
+      }
+      ignores.load(entry.path());
+      true
+    })
+    .filter_map(|entry| entry.ok());
+  for entry in dirs {
+    watcher.watch(entry.path(), RecursiveMode::NonRecursive)?;
+  }
+  Ok(())
+}
+
+fn changed(win: &Window, root: &Path, event: Event) {
+  let paths: Vec<&PathBuf> = event
+    .paths
+    .iter()
+    .filter(|path| {
+      let parent = path.parent().unwrap_or(root);
+      !Ignores::for_dir(root, parent).is_ignored(path, path.is_dir())
+    })
+    .collect();
+  if paths.is_empty() {
+    return;
+  }
+  if event.kind.is_create() {
+    for dir in paths.iter().filter(|path| path.is_dir()) {
+      watch_new_dir(win.label(), dir);
+    }
+  }
+  win
+    .emit(
+      "workspace-changed",
+      serde_json::json!({ "paths": paths, "kind": format!("{:?}", event.kind) }),
+    )
+    .ok();
+}
+
+fn watch_new_dir(label: &str, dir: &Path) {
+  let new_dirs = NEW_DIRS.get_or_init(|| {
+    let (tx, rx) = mpsc::channel::<(String, PathBuf)>();
+    thread::spawn(move || {
+      for (label, dir) in rx {
+        let mut watches = WATCHES.lock().unwrap();
+        let Some(watch) = watches.get_mut(&label).filter(|w| dir.starts_with(&w.root)) else {
+          continue;
+        };
+        if let Err(e) = watch_tree(&mut watch.watcher, &watch.root, &dir) {
+          error!("workspace_watch: {}: {:#}", dir.display(), e);
+        }
+      }
+    });
+    tx
+  });
+  new_dirs.send((label.into(), dir.into())).ok();
+}
@@ -0,0 +1,712 @@
+use crate::{
+  app::instance::{self, Request},
+  conf::AppConf,
+  deep_link,
+  engine::{
+    batch, files,
+    format::{self, FormatOptions},
+    jsonpath::JsonPath,
+    lint::{self, ReportFormat, Severity},
+    tabular::{self, TableFormat},
+  },
+};
+use anyhow::{anyhow, bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use similar::TextDiff;
+use std::{
+  env, fs,
+  io::{self, Read, Write},
+  path::{Path, PathBuf},
+};
+
+pub const EXIT_OK: i32 = 0;
+// validation errors, differences, unformatted files or no query results
+pub const EXIT_FOUND: i32 = 1;
+// bad arguments, unreadable or invalid input
+pub const EXIT_ERROR: i32 = 2;
+
+pub const USAGE: &str = "\
+Usage:
+  json-editor [FILE...]                open files in the editor, `-` reads stdin
+  json-editor jsoneditor://open?path=FILE | fetch?url=URL | new?data=BASE64
+  json-editor format [OPTIONS] [FILE...]
+      --indent N  --tabs  --compact  --sort-keys
+      -w, --write   rewrite the files instead of printing them
+      --check       list files that are not formatted, exit 1 if any
+  json-editor validate [--schema FILE|URL] [--report json|sarif] PATH...
+      parse errors, duplicate keys, format and schema problems of files and folders
+  json-editor convert INPUT OUTPUT [--pointer /path] [--sheet NAME]
+      between .json/.jsonc/.jsonl and .csv/.tsv/.xlsx, `-` is stdin / stdout JSON
+  json-editor diff [--unified] LEFT RIGHT
+      RFC 6902 patch from LEFT to RIGHT, exit 1 if they differ
+  json-editor query [-r] [-c] EXPR [FILE...]
+      EXPR starting with `$` is JSONPath, anything else is a jq program
+  json-editor remote [--window LABEL] OPERATION
+      drive the running editor: open FILE...|-, focus, get-document,
+      set-document [FILE|-], run-query EXPR
+
+Exit codes: 0 success, 1 problems / differences / no results, 2 errors";
+
+// Document to open in the editor window
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Document {
+  File { path: String },
+  // piped in with `-`, it has no file to save to
+  Stdin { json: Value },
+  // from `jsoneditor://` links: a URL the window loads, or inline data
+  Fetch { url: String },
+  New { json: Value },
+}
+
+#[derive(Debug)]
+pub enum Command {
+  // start the GUI with these inputs
+  Open(Vec<String>),
+  Format {
+    inputs: Vec<String>,
+    options: FormatOptions,
+    write: bool,
+    check: bool,
+  },
+  Validate {
+    inputs: Vec<String>,
+    schema: Option<String>,
+    report: Option<ReportFormat>,
+  },
+  Convert {
+    input: String,
+    output: String,
+    pointer: Option<String>,
+    sheet: Option<String>,
+  },
+  Diff {
+    left: String,
+    right: String,
+    unified: bool,
+  },
+  Query {
+    expr: String,
+    inputs: Vec<String>,
+    raw: bool,
+    compact: bool,
+  },
+  // ask the running editor, see `app::instance::Request`
+  Remote {
+    op: String,
+    args: Vec<String>,
+    window: Option<String>,
+    raw: bool,
+    compact: bool,
+  },
+  Help,
+  Version,
+}
+
+// Arguments after the program name
+pub fn parse(args: Vec<String>) -> Result<Command> {
+  let Some(name) = args.first() else {
+    return Ok(Command::Open(vec![]));
+  };
+  let rest = args[1..].to_vec();
+  let command = match name.as_str() {
+    "-h" | "--help" | "help" => Command::Help,
+    "-V" | "--version" => Command::Version,
+    "format" => {
+      let args = Args::split(
+        rest,
+        &["indent"],
+        &["tabs", "compact", "sort-keys", "write", "check"],
+      )?;
+      let mut options = FormatOptions {
+        tabs: args.switch("tabs"),
+        sort_keys: args.switch("sort-keys"),
+        ..Default::default()
+      };
+      if let Some(indent) = args.value("indent") {
+        options.indent = indent
+          .parse()
+          .map_err(|_| anyhow!("--indent expects a number, got `{}`", indent))?;
+      }
+      if args.switch("compact") {
+        options.indent = 0;
+        options.tabs = false;
+      }
+      let (write, check) = (args.switch("write"), args.switch("check"));
+      if write && check {
+        bail!("--write and --check cannot be used together");
+      }
+      Command::Format {
+        inputs: args.positional,
+        options,
+        write,
+        check,
+      }
+    }
+    "validate" => {
+      let args = Args::split(rest, &["schema", "report"], &[])?;
+      let report = match args.value("report") {
+        None => None,
+        Some("json") => Some(ReportFormat::Json),
+        Some("sarif") => Some(ReportFormat::Sarif),
+        Some(other) => bail!("--report expects json or sarif, got `{}`", other),
+      };
+      if args.positional.is_empty() {
+        bail!("validate expects files or folders");
+      }
+      Command::Validate {
+        schema: args.value("schema").map(Into::into),
+        report,
+        inputs: args.positional,
+      }
+    }
+    "convert" => {
+      let args = Args::split(rest, &["pointer", "sheet"], &[])?;
+      let [input, output] = args.exactly::<2>("convert expects INPUT and OUTPUT")?;
+      Command::Convert {
+        input,
+        output,
+        pointer: args.value("pointer").map(Into::into),
+        sheet: args.value("sheet").map(Into::into),
+      }
+    }
+    "diff" => {
+      let args = Args::split(rest, &[], &["unified"])?;
+      let [left, right] = args.exactly::<2>("diff expects LEFT and RIGHT")?;
+      if left == "-" && right == "-" {
+        bail!("only one side of diff can be stdin");
+      }
+      Command::Diff {
+        left,
+        right,
+        unified: args.switch("unified"),
+      }
+    }
+    "query" => {
+      let mut args = Args::split(rest, &[], &["raw", "compact"])?;
+      if args.positional.is_empty() {
+        bail!("query expects an expression");
+      }
+      Command::Query {
+        expr: args.positional.remove(0),
+        raw: args.switch("raw"),
+        compact: args.switch("compact"),
+        inputs: args.positional,
+      }
+    }
+    "remote" => {
+      let mut args = Args::split(rest, &["window"], &["raw", "compact"])?;
+      if args.positional.is_empty() {
+        bail!("remote expects an operation");
+      }
+      let op = args.positional.remove(0);
+      match op.as_str() {
+        "open" if args.positional.is_empty() => bail!("remote open expects files"),
+        "run-query" if args.positional.len() != 1 => bail!("remote run-query expects EXPR"),
+        "set-document" if args.positional.len() > 1 => {
+          bail!("remote set-document expects one FILE or -")
+        }
+        "open" | "focus" | "get-document" | "set-document" | "run-query" => (),
+        _ => bail!("unknown remote operation `{}`", op),
+      }
+      Command::Remote {
+        op,
+        window: args.value("window").map(Into::into),
+        raw: args.switch("raw"),
+        compact: args.switch("compact"),
+        args: args.positional,
+      }
+    }
+    _ => {
+      let args = Args::split(args, &[], &[])?;
+      Command::Open(args.positional)
+    }
+  };
+  Ok(command)
+}
+
+// Parsed options of one command: `--name value`, `--name=value`, switches and positionals
+struct Args {
+  positional: Vec<String>,
+  options: Vec<(String, Option<String>)>,
+}
+
+impl Args {
+  fn split(args: Vec<String>, values: &[&str], switches: &[&str]) -> Result<Args> {
+    let mut parsed = Args {
+      positional: vec![],
+      options: vec![],
+    };
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+      if arg == "-" || !arg.starts_with('-') {
+        parsed.positional.push(arg);
+        continue;
+      }
+      if arg == "--" {
+        parsed.positional.extend(args.by_ref());
+        break;
+      }
+      // Finder passes a process serial number to apps it launches on older macOS
+      if arg.starts_with("-psn_") {
+        continue;
+      }
+      let long = match arg.as_str() {
+        "-w" => "--write",
+        "-r" => "--raw",
+        "-c" => "--compact",
+        "-s" => "--schema",
+        arg => arg,
+      };
+      let Some(long) = long.strip_prefix("--") else {
+        bail!("unknown option `{}`", arg);
+      };
+      let (name, inline) = match long.split_once('=') {
+        Some((name, value)) => (name, Some(value.to_string())),
+        None => (long, None),
+      };
+      if values.contains(&name) {
+        let value = match inline {
+          Some(value) => value,
+          None => args
+            .next()
+            .ok_or_else(|| anyhow!("--{} expects a value", name))?,
+        };
+        parsed.options.push((name.into(), Some(value)));
+      } else if switches.contains(&name) && inline.is_none() {
+        parsed.options.push((name.into(), None));
+      } else {
+        bail!("unknown option `{}`", arg);
+      }
+    }
+    Ok(parsed)
+  }
+
+  fn switch(&self, name: &str) -> bool {
+    self.options.iter().any(|(n, _)| n == name)
+  }
+
+  fn value(&self, name: &str) -> Option<&str> {
+    self
+      .options
+      .iter()
+      .rev()
+      .find(|(n, _)| n == name)
+      .and_then(|(_, value)| value.as_deref())
+  }
+
+  fn exactly<const N: usize>(&self, message: &str) -> Result<[String; N]> {
+    self
+      .positional
+      .clone()
+      .try_into()
+      .map_err(|_| anyhow!("{}", message))
+  }
+}
+
+// Documents for the editor: files are opened by the window, stdin is read right away.
+// The OS starts `json-editor jsoneditor://...` for deep links.
+pub fn documents(inputs: &[String]) -> Result<Vec<Document>> {
+  let cwd = env::current_dir()?;
+  inputs
+    .iter()
+    .map(|input| {
+      if deep_link::is_link(input) {
+        return deep_link::parse(input, &AppConf::read().deep_link_hosts);
+      }
+      if input == "-" {
+        return Ok(Document::Stdin {
+          json: parse_text(&read_stdin()?).context("stdin")?,
+        });
+      }
+      let path = cwd.join(input);
+      if !path.is_file() {
+        bail!("{}: no such file", input);
+      }
+      Ok(Document::File {
+        path: path.to_string_lossy().into(),
+      })
+    })
+    .collect()
+}
+
+// Release builds on Windows use the GUI subsystem and get no console of their own, output of
+// headless commands goes to the console of the terminal they were started from
+#[cfg(windows)]
+pub fn attach_console() {
+  const ATTACH_PARENT_PROCESS: u32 = u32::MAX;
+  extern "system" {
+    fn AttachConsole(process_id: u32) -> i32;
+  }
+  // fails without a parent console (started from Explorer) or when redirected output is
+  // inherited already, both are fine
+  unsafe {
+    AttachConsole(ATTACH_PARENT_PROCESS);
+  }
+}
+
+#[cfg(not(windows))]
+pub fn attach_console() {}
+
+// Run a headless command, the result is the process exit code
+pub fn run(command: Command) -> i32 {
+  let result = match command {
+    Command::Open(_) => Ok(EXIT_OK),
+    Command::Help => out(&format!("{}\n", USAGE)).map(|_| EXIT_OK),
+    Command::Version => {
+      out(&format!("json-editor {}\n", env!("CARGO_PKG_VERSION"))).map(|_| EXIT_OK)
+    }
+    Command::Format {
+      inputs,
+      options,
+      write,
+      check,
+    } => format_inputs(inputs, &options, write, check),
+    Command::Validate {
+      inputs,
+      schema,
+      report,
+    } => validate(&inputs, schema.as_deref(), report),
+    Command::Convert {
+      input,
+      output,
+      pointer,
+      sheet,
+    } => convert(&input, &output, pointer.as_deref(), sheet.as_deref()),
+    Command::Diff {
+      left,
+      right,
+      unified,
+    } => diff(&left, &right, unified),
+    Command::Query {
+      expr,
+      inputs,
+      raw,
+      compact,
+    } => query(&expr, inputs, raw, compact),
+    Command::Remote {
+      op,
+      args,
+      window,
+      raw,
+      compact,
+    } => remote(&op, &args, window, raw, compact),
+  };
+  match result {
+    Ok(code) => code,
+    // `json-editor query ... | head` closes the pipe early
+    Err(e)
+      if e
+        .downcast_ref::<io::Error>()
+        .is_some_and(|e| e.kind() == io::ErrorKind::BrokenPipe) =>
+    {
+      EXIT_OK
+    }
+    Err(e) => {
+      eprintln!("json-editor: {:#}", e);
+      EXIT_ERROR
+    }
+  }
+}
+
+fn out(text: &str) -> Result<()> {
+  let mut stdout = io::stdout().lock();
+  stdout.write_all(text.as_bytes())?;
+  stdout.flush()?;
+  Ok(())
+}
+
+fn read_stdin() -> Result<String> {
+  let mut text = String::new();
+  io::stdin().read_to_string(&mut text)?;
+  Ok(text)
+}
+
+// JSON (comments allowed), or JSON Lines as an array
+pub fn parse_text(text: &str) -> Result<Value> {
+  match serde_json::from_str(&files::strip_comments(text)) {
+    Ok(value) => Ok(value),
+    Err(e) => {
+      let lines: Result<Vec<Value>, _> = text
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(serde_json::from_str)
+        .collect();
+      match lines {
+        Ok(values) if values.len() > 1 => Ok(Value::Array(values)),
+        _ => Err(e.into()),
+      }
+    }
+  }
+}
+
+fn read_input(input: &str) -> Result<Value> {
+  if input == "-" {
+    return parse_text(&read_stdin()?).context("stdin");
+  }
+  files::read_value(Path::new(input)).with_context(|| input.to_string())
+}
+
+fn inputs_or_stdin(inputs: Vec<String>) -> Vec<String> {
+  if inputs.is_empty() {
+    vec!["-".into()]
+  } else {
+    inputs
+  }
+}
+
+fn format_inputs(
+  inputs: Vec<String>,
+  options: &FormatOptions,
+  write: bool,
+  check: bool,
+) -> Result<i32> {
+  let mut unformatted = 0;
+  for input in inputs_or_stdin(inputs) {
+    let (text, path) = if input == "-" {
+      if write {
+        bail!("--write needs file arguments");
+      }
+      (read_stdin()?, None)
+    } else {
+      let path = PathBuf::from(&input);
+      (
+        fs::read_to_string(&path).with_context(|| input.clone())?,
+        Some(path),
+      )
+    };
+    let formatted = match &path {
+      Some(path) if files::is_jsonl(path) => format_lines(&text, options)?,
+      _ => {
+        if (write || check) && files::strip_comments(&text) != text {
+          eprintln!("{}: skipped, formatting would drop its comments", input);
+          continue;
+        }
+        format::format(&parse_text(&text).with_context(|| input.clone())?, options)?
+      }
+    };
+    if check {
+      if formatted != text {
+        out(&format!("{}\n", input))?;
+        unformatted += 1;
+      }
+    } else if write {
+      if let Some(path) = path.filter(|_| formatted != text) {
+        files::write_atomic(&path, &formatted)?;
+      }
+    } else {
+      out(&formatted)?;
+    }
+  }
+  Ok(if unformatted > 0 { EXIT_FOUND } else { EXIT_OK })
+}
+
+// JSON Lines stay one compact document per line
+fn format_lines(text: &str, options: &FormatOptions) -> Result<String> {
+  let mut formatted = String::new();
+  for (n, line) in text.lines().enumerate() {
+    if line.trim().is_empty() {
+      continue;
+    }
+    let value: Value = serde_json::from_str(line).with_context(|| format!("line {}", n + 1))?;
+    let value = if options.sort_keys {
+      format::sort_keys(value)
+    } else {
+      value
+    };
+    formatted.push_str(&serde_json::to_string(&value)?);
+    formatted.push('\n');
+  }
+  Ok(formatted)
+}
+
+fn validate(inputs: &[String], schema: Option<&str>, report: Option<ReportFormat>) -> Result<i32> {
+  let cwd = env::current_dir()?;
+  let paths: Vec<PathBuf> = inputs.iter().map(|input| cwd.join(input)).collect();
+  if let Some((input, _)) = inputs.iter().zip(&paths).find(|(_, path)| !path.exists()) {
+    bail!("{}: no such file or folder", input);
+  }
+  let result = lint::lint_paths(&cwd, &paths, schema, files::DEFAULT_MAX_SIZE)?;
+  match report {
+    Some(format) => out(&format!(
+      "{}\n",
+      serde_json::to_string_pretty(&lint::to_value(&result, format)?)?
+    ))?,
+    None => {
+      for file in &result.reports {
+        let path = Path::new(&file.path);
+        let path = path.strip_prefix(&cwd).unwrap_or(path).display();
+        for problem in &file.problems {
+          let severity = match problem.severity {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+          };
+          let pointer = problem
+            .pointer
+            .as_deref()
+            .filter(|p| !p.is_empty())
+            .map(|p| format!(" at {}", p))
+            .unwrap_or_default();
+          out(&format!(
+            "{}:{}:{}: {}[{}]: {}{}\n",
+            path,
+            problem.line,
+            problem.column,
+            severity,
+            problem.rule.id(),
+            problem.message,
+            pointer
+          ))?;
+        }
+      }
+      eprintln!(
+        "{} files checked, {} errors, {} warnings",
+        result.files, result.errors, result.warnings
+      );
+    }
+  }
+  Ok(if result.errors > 0 {
+    EXIT_FOUND
+  } else {
+    EXIT_OK
+  })
+}
+
+fn convert(input: &str, output: &str, pointer: Option<&str>, sheet: Option<&str>) -> Result<i32> {
+  let value = match TableFormat::from_path(Path::new(input)) {
+    Some(_) => tabular::import(Path::new(input), sheet).with_context(|| input.to_string())?,
+    None => read_input(input)?,
+  };
+  let path = Path::new(output);
+  if output == "-" || files::is_json_file(path) {
+    let value = match pointer {
+      Some(pointer) => value
+        .pointer(pointer)
+        .ok_or_else(|| anyhow!("pointer not found: {}", pointer))?,
+      None => &value,
+    };
+    let text = if files::is_jsonl(path) {
+      let items = value
+        .as_array()
+        .ok_or_else(|| anyhow!("JSON Lines output needs an array"))?;
+      items
+        .iter()
+        .map(|item| Ok(format!("{}\n", serde_json::to_string(item)?)))
+        .collect::<Result<String>>()?
+    } else {
+      format::format(value, &FormatOptions::default())?
+    };
+    if output == "-" {
+      out(&text)?;
+    } else {
+      files::write_atomic(path, &text)?;
+    }
+  } else {
+    let format =
+      TableFormat::from_path(path).ok_or_else(|| anyhow!("unsupported output type: {}", output))?;
+    let rows = tabular::export(&value, pointer, None, format, path)?;
+    eprintln!("{} rows written to {}", rows, output);
+  }
+  Ok(EXIT_OK)
+}
+
+fn diff(left: &str, right: &str, unified: bool) -> Result<i32> {
+  let (a, b) = (read_input(left)?, read_input(right)?);
+  if a == b {
+    return Ok(EXIT_OK);
+  }
+  if unified {
+    let options = FormatOptions::default();
+    let (a, b) = (format::format(&a, &options)?, format::format(&b, &options)?);
+    out(
+      &TextDiff::from_lines(&a, &b)
+        .unified_diff()
+        .header(left, right)
+        .to_string(),
+    )?;
+  } else {
+    out(&format!(
+      "{}\n",
+      serde_json::to_string_pretty(&json_patch::diff(&a, &b))?
+    ))?;
+  }
+  Ok(EXIT_FOUND)
+}
+
+fn query(expr: &str, inputs: Vec<String>, raw: bool, compact: bool) -> Result<i32> {
+  let mut found = 0;
+  for input in inputs_or_stdin(inputs) {
+    let results = evaluate(expr, read_input(&input)?)?;
+    found += results.len();
+    print_values(&results, raw, compact)?;
+  }
+  Ok(if found > 0 { EXIT_OK } else { EXIT_FOUND })
+}
+
+// Matches of `expr`: JSONPath when it starts with `$`, a jq program otherwise
+pub fn evaluate(expr: &str, value: Value) -> Result<Vec<Value>> {
+  if expr.trim_start().starts_with('$') {
+    Ok(
+      JsonPath::parse(expr)?
+        .select(&value)
+        .into_iter()
+        .map(|(_, v)| v.clone())
+        .collect(),
+    )
+  } else {
+    batch::jq(expr, value)
+  }
+}
+
+fn print_values(values: &[Value], raw: bool, compact: bool) -> Result<()> {
+  for value in values {
+    let text = match value {
+      Value::String(s) if raw => s.clone(),
+      _ if compact => serde_json::to_string(value)?,
+      _ => serde_json::to_string_pretty(value)?,
+    };
+    out(&format!("{}\n", text))?;
+  }
+  Ok(())
+}
+
+fn remote(
+  op: &str,
+  args: &[String],
+  window: Option<String>,
+  raw: bool,
+  compact: bool,
+) -> Result<i32> {
+  let request = match op {
+    "open" => Request::Open {
+      documents: documents(args)?,
+    },
+    "focus" => Request::Focus { window },
+    "get-document" => Request::GetDocument { window },
+    "set-document" => Request::SetDocument {
+      json: read_input(args.first().map_or("-", String::as_str))?,
+      window,
+    },
+    "run-query" => Request::RunQuery {
+      expr: args.first().cloned().unwrap_or_default(),
+      window,
+    },
+    _ => unreachable!("checked by parse"),
+  };
+  let Some(result) = instance::send(&request)? else {
+    bail!("JsonEditor is not running");
+  };
+  match request {
+    Request::GetDocument { .. } => print_values(&[result], raw, compact)?,
+    Request::RunQuery { .. } => {
+      let results = result.as_array().cloned().unwrap_or_default();
+      print_values(&results, raw, compact)?;
+      if results.is_empty() {
+        return Ok(EXIT_FOUND);
+      }
+    }
+    _ => (),
+  }
+  Ok(EXIT_OK)
+}
@@ -1,26 +1,123 @@
+use anyhow::{anyhow, Result};
 use log::{error, info};
-use serde_json::Value;
-use std::{collections::BTreeMap, path::PathBuf};
-use tauri::{Manager, Theme};
+use serde::{Deserialize, Deserializer, Serialize};
+use serde_json::{Map, Value};
+use std::{
+  fmt, fs,
+  io::ErrorKind,
+  path::{Path, PathBuf},
+  sync::{
+    atomic::{AtomicBool, Ordering},
+    Mutex,
+  },
+  time::{SystemTime, UNIX_EPOCH},
+};
+use tauri::Theme;
 
 #[cfg(target_os = "macos")]
 use tauri::TitleBarStyle;
 
-use crate::utils::{app_root, create_file, exists};
+use crate::{
+  engine::{
+    clipboard::{self, ClipboardShortcut},
+    files,
+    http::ClientOptions,
+  },
+  utils::app_root,
+};
 
 pub const APP_CONF_PATH: &str = "editor.conf.json";
 pub const UPDATE_LOG_URL: &str = "https://github.com/jxo-me/JsonEditor/blob/main/UPDATE_LOG.md";
+// bumped together with a new entry in `MIGRATIONS`
+pub const CONF_VERSION: u32 = 2;
+
+// `MIGRATIONS[n]` upgrades a version `n` file to `n + 1`
+const MIGRATIONS: &[fn(&mut Map<String, Value>)] = &[migrate_v0, migrate_v1];
+
+// problems found while loading the config, shown once the app is up
+static WARNINGS: Mutex<Vec<String>> = Mutex::new(vec![]);
+// a broken file is copied aside once per run, not on every read
+static BACKED_UP: AtomicBool = AtomicBool::new(false);
+// why the file is left alone instead of overwritten: its backup failed, or it is
+// from a newer version that the settings of this one would downgrade
+static READ_ONLY: Mutex<Option<String>> = Mutex::new(None);
+// `set` reads, merges and writes the file, two at once would lose one of the changes
+static SAVING: Mutex<()> = Mutex::new(());
 
 macro_rules! pub_struct {
-    ($name:ident {$($field:ident: $t:ty,)*}) => {
+    ($name:ident, $patch:ident {$($field:ident: $t:ty,)*}) => {
       #[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
       pub struct $name {
-        $(pub $field: $t),*
+        $(pub $field: $t,)*
+        // keys this version does not know, e.g. from a newer one, written back as they were
+        #[serde(skip)]
+        unknown: Map<String, Value>,
+      }
+
+      // Settings to change in `$name`, fields left at `None` keep their value
+      #[derive(serde::Deserialize, Debug, Default)]
+      #[serde(deny_unknown_fields)]
+      pub struct $patch {
+        $(#[serde(default, deserialize_with = "present")] pub $field: Option<$t>),*
+      }
+
+      impl $name {
+        fn merge(mut self, patch: $patch) -> Self {
+          $(if let Some(value) = patch.$field {
+            self.$field = value;
+          })*
+          self
+        }
       }
     }
 }
 
-pub_struct!(AppConf {
+// A key that is present counts, `null` included, so optional settings can be cleared
+fn present<'de, D, T>(deserializer: D) -> std::result::Result<Option<T>, D::Error>
+where
+  D: Deserializer<'de>,
+  T: Deserialize<'de>,
+{
+  T::deserialize(deserializer).map(Some)
+}
+
+// A rejected setting, `key` is `None` when the update failed as a whole
+#[derive(Serialize, Debug, Clone)]
+pub struct ConfError {
+  pub key: Option<String>,
+  pub message: String,
+}
+
+// Everything wrong with one update, sent to the frontend as a list
+#[derive(Serialize, Debug, Clone)]
+#[serde(transparent)]
+pub struct ConfErrors(pub Vec<ConfError>);
+
+impl ConfErrors {
+  fn whole(message: String) -> Self {
+    Self(vec![ConfError { key: None, message }])
+  }
+}
+
+impl fmt::Display for ConfErrors {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    let messages: Vec<String> = self
+      .0
+      .iter()
+      .map(|e| match &e.key {
+        Some(key) => format!("{}: {}", key, e.message),
+        None => e.message.clone(),
+      })
+      .collect();
+    f.write_str(&messages.join("; "))
+  }
+}
+
+impl std::error::Error for ConfErrors {}
+
+pub_struct!(AppConf, AppConfPatch {
+    // format of the file, older files are migrated on read
+    version: u32,
     titlebar: bool,
     hide_dock_icon: bool,
     // macOS and Windows: light / dark / system
@@ -29,13 +126,20 @@ pub_struct!(AppConf {
     auto_update: String,
     stay_on_top: bool,
     save_window_state: bool,
+    // opens the clipboard as a new document, e.g. `CmdOrCtrl+Shift+J`
     global_shortcut: Option<String>,
+    // hotkeys that rewrite the clipboard in place: [{ "accelerator": "CmdOrCtrl+Alt+P", "action": "prettify" }]
+    clipboard_shortcuts: Option<Vec<ClipboardShortcut>>,
     speech_lang: String,
 
     // Main Window
     isinit: bool,
+    // spotlight-style search over documents, recent files and snippets
     popup_search: bool,
+    // hotkey of the search popup, default `CmdOrCtrl+Shift+Space`
+    popup_search_shortcut: Option<String>,
     main_close: bool,
+    // start dashboard (recent, pinned, recovered, templates) while a window has no document
     main_dashboard: bool,
     main_width: f64,
     main_height: f64,
@@ -44,13 +148,30 @@ pub_struct!(AppConf {
     tray_width: f64,
     tray_height: f64,
     tray: bool,
+    // the same dashboard in the tray popover
     tray_dashboard: bool,
+
+    // Workspace: last opened folder
+    workspace: Option<String>,
+
+    // Network: proxy URL for Open URL, unset uses the system proxy
+    proxy: Option<String>,
+    // skip TLS certificate verification (self-signed dev servers)
+    accept_invalid_certs: Option<bool>,
+    // PEM file with an extra root certificate
+    ca_cert: Option<String>,
+
+    // Mock Server: last used port
+    mock_port: Option<u16>,
+
+    // Deep links: hosts `jsoneditor://fetch` may load from (and their subdomains), none by default
+    deep_link_hosts: Vec<String>,
 });
 
 impl AppConf {
   pub fn new() -> Self {
-    info!("conf_init");
     Self {
+      version: CONF_VERSION,
       titlebar: true,
       hide_dock_icon: false,
       save_window_state: false,
@@ -65,75 +186,200 @@ impl AppConf {
       isinit: true,
       main_close: false,
       stay_on_top: false,
-      main_dashboard: false,
+      main_dashboard: true,
       tray_dashboard: false,
       main_width: 1440.0,
       main_height: 900.0,
-      tray_width: 1440.0,
-      tray_height: 900.0,
+      tray_width: 360.0,
+      tray_height: 540.0,
       global_shortcut: None,
+      clipboard_shortcuts: None,
+      popup_search_shortcut: None,
+      workspace: None,
+      proxy: None,
+      accept_invalid_certs: None,
+      ca_cert: None,
+      mock_port: None,
+      deep_link_hosts: vec![],
+      unknown: Map::new(),
     }
   }
   pub fn file_path() -> PathBuf {
     app_root().join(APP_CONF_PATH)
   }
 
+  // Missing or invalid fields fall back to their defaults one by one, a file that can't be
+  // read at all is backed up before the defaults are used
   pub fn read() -> Self {
-    match std::fs::read_to_string(Self::file_path()) {
-      Ok(v) => {
-        if let Ok(v2) = serde_json::from_str::<AppConf>(&v) {
-          v2
-        } else {
-          error!("conf_read_parse_error");
-          Self::default()
+    let path = Self::file_path();
+    let text = match fs::read_to_string(&path) {
+      Ok(text) => text,
+      Err(err) if err.kind() == ErrorKind::NotFound => {
+        info!("conf_init");
+        return Self::default();
+      }
+      Err(err) => {
+        Self::keep_backup(&path, format!("The settings could not be read: {}.", err));
+        return Self::default();
+      }
+    };
+    match Self::parse(&text) {
+      Ok((conf, invalid, version)) => {
+        if version > CONF_VERSION as u64 {
+          let reason = format!(
+            "the settings are from a newer version of JsonEditor (format {}), they are left as is",
+            version
+          );
+          *READ_ONLY.lock().unwrap() = Some(reason);
+          Self::keep_backup(
+            &path,
+            "The settings were saved by a newer version of JsonEditor, \
+             changes made in this version won't be saved."
+              .into(),
+          );
+        } else if !invalid.is_empty() {
+          Self::keep_backup(
+            &path,
+            format!(
+              "These settings were invalid and reset to their defaults: {}.",
+              invalid.join(", ")
+            ),
+          );
         }
+        conf
       }
       Err(err) => {
-        error!("conf_read_error: {}", err);
+        Self::keep_backup(
+          &path,
+          format!(
+            "The settings could not be read: {}. The defaults are used.",
+            err
+          ),
+        );
         Self::default()
       }
     }
   }
 
-  pub fn write(self) -> Self {
-    let path = &Self::file_path();
-    if !exists(path) {
-      create_file(path).unwrap();
-      info!("conf_create");
+  // The config in `text` migrated to `CONF_VERSION`, with the keys that had to be reset
+  // and the version the file was written with
+  fn parse(text: &str) -> Result<(Self, Vec<String>, u64)> {
+    let mut conf = match serde_json::from_str(text)? {
+      Value::Object(conf) => conf,
+      _ => return Err(anyhow!("not a JSON object")),
+    };
+    let version = conf.get("version").and_then(Value::as_u64).unwrap_or(0);
+    for (from, migrate) in MIGRATIONS.iter().enumerate().skip(version as usize) {
+      info!("conf_migrate: {} -> {}", from, from + 1);
+      migrate(&mut conf);
     }
-    if let Ok(v) = serde_json::to_string_pretty(&self) {
-      std::fs::write(path, v).unwrap_or_else(|err| {
-        error!("conf_write: {}", err);
-        Self::default().write();
-      });
-    } else {
-      error!("conf_ser");
-    }
-    self
+    conf.insert("version".into(), CONF_VERSION.into());
+
+    let Value::Object(defaults) = serde_json::to_value(Self::default())? else {
+      return Err(anyhow!("defaults are not an object"));
+    };
+    let (known, unknown): (Map<String, Value>, Map<String, Value>) = conf
+      .into_iter()
+      .partition(|(key, _)| defaults.contains_key(key));
+    let (patch, errors) = AppConfPatch::from_map(known);
+    let invalid = errors.into_iter().filter_map(|e| e.key).collect();
+    let mut conf = Self::default().merge(patch);
+    conf.unknown = unknown;
+    Ok((conf, invalid, version))
   }
 
-  pub fn amend(self, json: Value) -> Self {
-    let val = serde_json::to_value(&self).unwrap();
-    let mut config: BTreeMap<String, Value> = serde_json::from_value(val).unwrap();
-    let new_json: BTreeMap<String, Value> = serde_json::from_value(json).unwrap();
+  fn is_key(key: &str) -> bool {
+    matches!(serde_json::to_value(Self::default()), Ok(Value::Object(defaults)) if defaults.contains_key(key))
+  }
 
-    for (k, v) in new_json {
-      config.insert(k, v);
+  fn keep_backup(path: &Path, warning: String) {
+    if BACKED_UP.swap(true, Ordering::SeqCst) {
+      return;
     }
-
-    match serde_json::to_string_pretty(&config) {
-      Ok(v) => match serde_json::from_str::<AppConf>(&v) {
-        Ok(v) => v,
-        Err(err) => {
-          error!("conf_amend_parse: {}", err);
-          self
-        }
-      },
+    error!("conf_read: {}", warning);
+    let stamp = SystemTime::now()
+      .duration_since(UNIX_EPOCH)
+      .map(|d| d.as_secs())
+      .unwrap_or_default();
+    let backup = path.with_file_name(format!("{}.{}.bak", APP_CONF_PATH, stamp));
+    let warning = match fs::copy(path, &backup) {
+      Ok(_) => {
+        info!("conf_backup: {}", backup.display());
+        format!(
+          "{}\n\nThe previous file was saved as {}",
+          warning,
+          backup.display()
+        )
+      }
       Err(err) => {
-        error!("conf_amend_str: {}", err);
-        self
+        error!("conf_backup: {}", err);
+        READ_ONLY
+          .lock()
+          .unwrap()
+          .get_or_insert("the unreadable config could not be backed up, it is left as is".into());
+        format!(
+          "{}\n\nThe file could not be backed up ({}), so changes won't be saved to {}.",
+          warning,
+          err,
+          path.display()
+        )
       }
+    };
+    WARNINGS.lock().unwrap().push(warning);
+  }
+
+  // Warnings from loading the config, each is returned once
+  pub fn take_warnings() -> Vec<String> {
+    std::mem::take(&mut *WARNINGS.lock().unwrap())
+  }
+
+  // Written next to the file and renamed over it, a crash never leaves half a config
+  pub fn save(&self) -> Result<()> {
+    if let Some(reason) = &*READ_ONLY.lock().unwrap() {
+      return Err(anyhow!("{}", reason));
     }
+    let path = Self::file_path();
+    if let Some(dir) = path.parent() {
+      fs::create_dir_all(dir)?;
+    }
+    files::write_atomic(&path, &self.to_json()?)?;
+    info!("conf_save");
+    Ok(())
+  }
+
+  // The file contents, with the keys of other versions after the known ones
+  fn to_json(&self) -> Result<String> {
+    let mut json = serde_json::to_value(self)?;
+    if let Value::Object(map) = &mut json {
+      map.extend(self.unknown.clone());
+    }
+    Ok(serde_json::to_string_pretty(&json)?)
+  }
+
+  // Apply `patch` to the saved config, nothing changes when any of its settings is invalid
+  pub fn set(patch: AppConfPatch) -> std::result::Result<Self, ConfErrors> {
+    let errors = patch.validate();
+    if !errors.is_empty() {
+      return Err(ConfErrors(errors));
+    }
+    let _saving = SAVING.lock().unwrap();
+    let app_conf = Self::read().merge(patch);
+    app_conf
+      .save()
+      .map_err(|e| ConfErrors::whole(format!("{:#}", e)))?;
+    Ok(app_conf)
+  }
+
+  // `set` for a JSON object of settings, as sent by the frontend
+  pub fn set_json(json: Value) -> std::result::Result<Self, ConfErrors> {
+    let Value::Object(map) = json else {
+      return Err(ConfErrors::whole("expected an object of settings".into()));
+    };
+    let (patch, errors) = AppConfPatch::from_map(map);
+    if !errors.is_empty() {
+      return Err(ConfErrors(errors));
+    }
+    Self::set(patch)
   }
 
   #[cfg(target_os = "macos")]
@@ -147,19 +393,33 @@ impl AppConf {
 
   pub fn theme_mode() -> Theme {
     match Self::get_theme().as_str() {
-      "system" => match dark_light::detect() {
-        // Dark mode
-        dark_light::Mode::Dark => Theme::Dark,
-        // Light mode
-        dark_light::Mode::Light => Theme::Light,
-        // Unspecified
-        dark_light::Mode::Default => Theme::Light,
-      },
+      "system" => Self::system_theme(),
       "dark" => Theme::Dark,
       _ => Theme::Light,
     }
   }
 
+  // theme of new native windows, `None` follows the OS and reports its switches as `ThemeChanged`
+  pub fn window_theme() -> Option<Theme> {
+    match Self::get_theme().as_str() {
+      "system" => None,
+      "dark" => Some(Theme::Dark),
+      _ => Some(Theme::Light),
+    }
+  }
+
+  // light or dark mode of the OS (XDG portal / gsettings on Linux), asked anew on every call
+  pub fn system_theme() -> Theme {
+    match dark_light::detect() {
+      // Dark mode
+      dark_light::Mode::Dark => Theme::Dark,
+      // Light mode
+      dark_light::Mode::Light => Theme::Light,
+      // Unspecified
+      dark_light::Mode::Default => Theme::Light,
+    }
+  }
+
   pub fn get_theme() -> String {
     Self::read().theme.to_lowercase()
   }
@@ -168,12 +428,150 @@ impl AppConf {
     self.auto_update.to_lowercase()
   }
 
+  pub fn client_options(self) -> ClientOptions {
+    ClientOptions {
+      proxy: self.proxy,
+      accept_invalid_certs: self.accept_invalid_certs.unwrap_or(false),
+      ca_cert: self.ca_cert,
+    }
+  }
+
   pub fn theme_check(self, mode: &str) -> bool {
     self.theme.to_lowercase() == mode
   }
+}
 
-  pub fn restart(self, app: tauri::AppHandle) {
-    tauri::api::process::restart(&app.env());
+impl AppConfPatch {
+  // The valid settings of `map` as a patch, with an error for each one left out
+  pub fn from_map(map: Map<String, Value>) -> (Self, Vec<ConfError>) {
+    if let Ok(patch) = serde_json::from_value::<Self>(Value::Object(map.clone())) {
+      let errors = patch.validate();
+      if errors.is_empty() {
+        return (patch, errors);
+      }
+    }
+    // check each key on its own to tell which ones are wrong
+    let mut valid = Map::new();
+    let mut errors = vec![];
+    for (key, value) in map {
+      let reject = |message: String| ConfError {
+        key: Some(key.clone()),
+        message,
+      };
+      if !AppConf::is_key(&key) {
+        errors.push(reject("unknown setting".into()));
+        continue;
+      }
+      let single = Map::from_iter([(key.clone(), value.clone())]);
+      match serde_json::from_value::<Self>(Value::Object(single)) {
+        Ok(patch) => {
+          let invalid = patch.validate();
+          if invalid.is_empty() {
+            valid.insert(key, value);
+          } else {
+            errors.extend(invalid);
+          }
+        }
+        Err(e) => errors.push(reject(e.to_string())),
+      }
+    }
+    let patch = serde_json::from_value(Value::Object(valid)).unwrap_or_default();
+    (patch, errors)
+  }
+
+  pub fn validate(&self) -> Vec<ConfError> {
+    let mut errors = vec![];
+    let mut reject = |key: &str, message: String| {
+      errors.push(ConfError {
+        key: Some(key.into()),
+        message,
+      })
+    };
+    if let Some(version) = self.version {
+      if version != CONF_VERSION {
+        reject(
+          "version",
+          format!("is set by the app, expected {}", CONF_VERSION),
+        );
+      }
+    }
+    let one_of = |value: &Option<String>, allowed: &[&str]| match value {
+      Some(value) if !allowed.contains(&value.to_lowercase().as_str()) => {
+        Some(format!("`{}` is not one of {}", value, allowed.join(", ")))
+      }
+      _ => None,
+    };
+    if let Some(message) = one_of(&self.theme, &["light", "dark", "system"]) {
+      reject("theme", message);
+    }
+    if let Some(message) = one_of(&self.auto_update, &["prompt", "silent", "disable"]) {
+      reject("auto_update", message);
+    }
+    let sizes = [
+      ("main_width", self.main_width),
+      ("main_height", self.main_height),
+      ("tray_width", self.tray_width),
+      ("tray_height", self.tray_height),
+    ];
+    for (key, size) in sizes {
+      if size.is_some_and(|size| !size.is_finite() || size <= 0.0) {
+        reject(key, "must be a positive number".into());
+      }
+    }
+    let shortcuts = [
+      ("global_shortcut", &self.global_shortcut),
+      ("popup_search_shortcut", &self.popup_search_shortcut),
+    ];
+    for (key, accelerator) in shortcuts {
+      if let Some(Some(accelerator)) = accelerator {
+        if let Err(e) = clipboard::validate_accelerator(accelerator) {
+          reject(key, e.to_string());
+        }
+      }
+    }
+    if let Some(Some(shortcuts)) = &self.clipboard_shortcuts {
+      for shortcut in shortcuts {
+        if let Err(e) = clipboard::validate_accelerator(&shortcut.accelerator) {
+          reject("clipboard_shortcuts", e.to_string());
+        }
+      }
+    }
+    if let Some(Some(proxy)) = &self.proxy {
+      if let Err(e) = url::Url::parse(proxy) {
+        reject("proxy", format!("`{}` is not a URL: {}", proxy, e));
+      }
+    }
+    if let Some(hosts) = &self.deep_link_hosts {
+      for host in hosts {
+        if host.is_empty() || host.contains(['/', ':', ' ']) {
+          reject("deep_link_hosts", format!("`{}` is not a host name", host));
+        }
+      }
+    }
+    errors
+  }
+}
+
+// 0 -> 1: files from before `version` existed
+fn migrate_v0(conf: &mut Map<String, Value>) {
+  // the tray popover used to default to the size of the main window
+  let size = |key: &str| conf.get(key).and_then(Value::as_f64);
+  if size("tray_width") == Some(1440.0) && size("tray_height") == Some(900.0) {
+    conf.insert("tray_width".into(), 360.0.into());
+    conf.insert("tray_height".into(), 540.0.into());
+  }
+  // `theme` and `auto_update` were compared case-insensitively
+  for key in ["theme", "auto_update"] {
+    if let Some(Value::String(value)) = conf.get_mut(key) {
+      *value = value.to_lowercase();
+    }
+  }
+}
+
+// 1 -> 2: `deep_link_hosts` was saved as `null`, which allowed every host
+fn migrate_v1(conf: &mut Map<String, Value>) {
+  if conf.get("deep_link_hosts") == Some(&Value::Null) {
+    conf.remove("deep_link_hosts");
   }
 }
 
@@ -182,3 +580,76 @@ impl Default for AppConf {
     Self::new()
   }
 }
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use serde_json::json;
+
+  fn parse(conf: Value) -> (AppConf, Vec<String>, u64) {
+    AppConf::parse(&conf.to_string()).unwrap()
+  }
+
+  #[test]
+  fn has_a_migration_per_version() {
+    assert_eq!(MIGRATIONS.len(), CONF_VERSION as usize);
+  }
+
+  #[test]
+  fn migrates_files_without_a_version() {
+    let (conf, invalid, version) =
+      parse(json!({"tray_width": 1440.0, "tray_height": 900.0, "theme": "Dark"}));
+    assert_eq!(version, 0);
+    assert!(invalid.is_empty(), "{:?}", invalid);
+    assert_eq!((conf.tray_width, conf.tray_height), (360.0, 540.0));
+    assert_eq!(conf.theme, "dark");
+    assert_eq!(conf.version, CONF_VERSION);
+  }
+
+  #[test]
+  fn migrates_unset_deep_link_hosts() {
+    let (conf, invalid, _) = parse(json!({"version": 1, "deep_link_hosts": null}));
+    assert!(invalid.is_empty(), "{:?}", invalid);
+    assert!(conf.deep_link_hosts.is_empty());
+    let (conf, _, _) = parse(json!({"version": 1, "deep_link_hosts": ["example.com"]}));
+    assert_eq!(conf.deep_link_hosts, ["example.com"]);
+  }
+
+  #[test]
+  fn resets_invalid_settings_only() {
+    let (conf, invalid, _) =
+      parse(json!({"version": CONF_VERSION, "theme": "blue", "tray": "x", "stay_on_top": true}));
+    assert_eq!(invalid, ["theme", "tray"]);
+    assert_eq!(conf.theme, AppConf::default().theme);
+    assert!(conf.stay_on_top);
+  }
+
+  #[test]
+  fn keeps_unknown_settings() {
+    let (conf, invalid, _) =
+      parse(json!({"version": CONF_VERSION, "theme": "dark", "future": {"a": [1]}}));
+    assert!(invalid.is_empty(), "{:?}", invalid);
+    let saved: Value = serde_json::from_str(&conf.to_json().unwrap()).unwrap();
+    assert_eq!(saved["future"], json!({"a": [1]}));
+    assert_eq!(saved["theme"], "dark");
+    // the frontend only sees the known settings
+    assert!(serde_json::to_value(&conf).unwrap().get("future").is_none());
+    // and they survive a change
+    let merged = conf.merge(AppConfPatch {
+      stay_on_top: Some(true),
+      ..Default::default()
+    });
+    assert!(merged.to_json().unwrap().contains("\"future\""));
+  }
+
+  #[test]
+  fn reports_files_of_newer_versions() {
+    let newer = CONF_VERSION as u64 + 1;
+    let (conf, invalid, version) =
+      parse(json!({"version": newer, "theme": "dark", "new_setting": true}));
+    assert_eq!(version, newer);
+    assert!(invalid.is_empty(), "{:?}", invalid);
+    assert_eq!(conf.theme, "dark");
+    assert_eq!(conf.unknown.get("new_setting"), Some(&json!(true)));
+  }
+}
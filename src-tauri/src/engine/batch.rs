@@ -0,0 +1,624 @@
+use anyhow::{anyhow, bail, Context, Result};
+use jaq_core::{
+  load::{self, Arena, File, Loader},
+  Ctx, Native, RcIter,
+};
+use jaq_json::Val;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use similar::TextDiff;
+use std::{
+  fs,
+  io::ErrorKind,
+  path::{Path, PathBuf},
+  time::{SystemTime, UNIX_EPOCH},
+};
+
+use super::{
+  files,
+  format::{self, FormatOptions},
+  transform::Transform,
+  workspace::WorkspaceSettings,
+};
+
+const MANIFEST_FILE: &str = "manifest.json";
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Step {
+  // jq program, it must output exactly one value per document
+  Jq { filter: String },
+  // RFC 6902 operations
+  Patch { patch: json_patch::Patch },
+  // layout of the written file, the last format step wins
+  Format { options: FormatOptions },
+  Transform { op: Transform },
+}
+
+// A named list of steps applied to every file below the chosen folder that matches `glob`
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct Pipeline {
+  pub name: String,
+  pub glob: Vec<String>,
+  pub steps: Vec<Step>,
+}
+
+impl Pipeline {
+  pub fn list(dir: &Path) -> Result<Vec<Pipeline>> {
+    if !dir.is_dir() {
+      return Ok(vec![]);
+    }
+    let mut pipelines = vec![];
+    for entry in fs::read_dir(dir)? {
+      let path = entry?.path();
+      if path.extension().is_some_and(|ext| ext == "json") {
+        let text = fs::read_to_string(&path)?;
+        pipelines.push(serde_json::from_str(&text).with_context(|| format!("{}", path.display()))?);
+      }
+    }
+    pipelines.sort_by(|a: &Pipeline, b| a.name.cmp(&b.name));
+    Ok(pipelines)
+  }
+
+  pub fn save(&self, dir: &Path) -> Result<()> {
+    fs::create_dir_all(dir)?;
+    files::write_atomic(
+      &files::named_file(dir, &self.name)?,
+      &serde_json::to_string_pretty(self)?,
+    )
+  }
+
+  pub fn delete(dir: &Path, name: &str) -> Result<()> {
+    Ok(fs::remove_file(files::named_file(dir, name)?)?)
+  }
+}
+
+// A compiled pipeline, jq programs are parsed once for all files
+struct Runner {
+  steps: Vec<Compiled>,
+  format: FormatOptions,
+  // an explicit format step rewrites files even when their content did not change
+  reformat: bool,
+}
+
+enum Compiled {
+  Jq(jaq_core::Filter<Native<Val>>),
+  Patch(json_patch::Patch),
+  SortKeys,
+  Transform(Transform),
+}
+
+impl Runner {
+  fn new(pipeline: &Pipeline, default_format: FormatOptions) -> Result<Self> {
+    let mut runner = Runner {
+      steps: vec![],
+      format: default_format,
+      reformat: false,
+    };
+    for step in &pipeline.steps {
+      match step {
+        Step::Jq { filter } => runner.steps.push(Compiled::Jq(compile_jq(filter)?)),
+        Step::Patch { patch } => runner.steps.push(Compiled::Patch(patch.clone())),
+        Step::Format { options } => {
+          if options.sort_keys {
+            runner.steps.push(Compiled::SortKeys);
+          }
+          runner.format = options.clone();
+          runner.reformat = true;
+        }
+        Step::Transform { op } => runner.steps.push(Compiled::Transform(op.clone())),
+      }
+    }
+    Ok(runner)
+  }
+
+  fn run(&self, mut value: Value) -> Result<Value> {
+    for step in &self.steps {
+      value = match step {
+        Compiled::Jq(filter) => run_jq(filter, value)?,
+        Compiled::Patch(patch) => {
+          json_patch::patch(&mut value, patch)?;
+          value
+        }
+        Compiled::SortKeys => format::sort_keys(value),
+        Compiled::Transform(op) => op.apply(value)?,
+      };
+    }
+    Ok(value)
+  }
+
+  // new text of the file, `None` when it stays as it is
+  fn rewrite(&self, path: &Path, text: &str) -> Result<Option<String>> {
+    if files::is_jsonl(path) {
+      let mut changed = false;
+      let mut out = String::new();
+      for line in text.lines() {
+        if line.trim().is_empty() {
+          out.push_str(line);
+        } else {
+          let before: Value = serde_json::from_str(line)?;
+          let after = self.run(before.clone())?;
+          changed |= after != before;
+          out.push_str(&serde_json::to_string(&after)?);
+        }
+        out.push('\n');
+      }
+      return Ok(changed.then_some(out));
+    }
+    // like `format --write`, files whose comments would be lost are left alone
+    if files::strip_comments(text) != text {
+      return Err(Skipped.into());
+    }
+    let before: Value = serde_json::from_str(text)?;
+    let after = self.run(before.clone())?;
+    if after == before && !self.reformat {
+      return Ok(None);
+    }
+    let out = format::format(&after, &self.format)?;
+    Ok((out != text).then_some(out))
+  }
+}
+
+fn compile_jq(code: &str) -> Result<jaq_core::Filter<Native<Val>>> {
+  let arena = Arena::default();
+  let loader = Loader::new(jaq_std::defs().chain(jaq_json::defs()));
+  let modules = loader
+    .load(&arena, File { code, path: () })
+    .map_err(|errs| {
+      // lexer and parser errors carry the input left at the error
+      let column = |rest: &str| code.len() - rest.len() + 1;
+      let errs: Vec<String> = errs
+        .into_iter()
+        .flat_map(|(_, e)| match e {
+          load::Error::Io(errs) => errs.into_iter().map(|(_, e)| e).collect(),
+          load::Error::Lex(errs) => errs
+            .into_iter()
+            .map(|(expect, rest)| {
+              format!("expected {} at column {}", expect.as_str(), column(rest))
+            })
+            .collect(),
+          load::Error::Parse(errs) => errs
+            .into_iter()
+            .map(|(expect, rest)| {
+              format!("expected {} at column {}", expect.as_str(), column(rest))
+            })
+            .collect::<Vec<_>>(),
+        })
+        .collect();
+      anyhow!("invalid jq filter `{}`: {}", code, errs.join(", "))
+    })?;
+  jaq_core::Compiler::default()
+    .with_funs(jaq_std::funs().chain(jaq_json::funs()))
+    .compile(modules)
+    .map_err(|errs| {
+      let names: Vec<&str> = errs
+        .into_iter()
+        .flat_map(|(_, undefined)| undefined.into_iter().map(|(name, _)| name))
+        .collect();
+      anyhow!(
+        "invalid jq filter `{}`: undefined {}",
+        code,
+        names.join(", ")
+      )
+    })
+}
+
+// Every output of the jq program `code` for `value`
+pub fn jq(code: &str, value: Value) -> Result<Vec<Value>> {
+  let filter = compile_jq(code)?;
+  let inputs = RcIter::new(core::iter::empty());
+  filter
+    .run((Ctx::new([], &inputs), Val::from(value)))
+    .map(|result| result.map(Into::into).map_err(|e| anyhow!("jq: {}", e)))
+    .collect()
+}
+
+fn run_jq(filter: &jaq_core::Filter<Native<Val>>, value: Value) -> Result<Value> {
+  let inputs = RcIter::new(core::iter::empty());
+  let mut out = filter.run((Ctx::new([], &inputs), Val::from(value)));
+  let first = match out.next() {
+    Some(result) => result.map_err(|e| anyhow!("jq: {}", e))?,
+    None => bail!("jq filter produced no output"),
+  };
+  if out.next().is_some() {
+    bail!("jq filter produced more than one output");
+  }
+  Ok(first.into())
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct FileDiff {
+  pub path: String,
+  // unified diff, empty when the file could not be processed
+  pub diff: String,
+  pub error: Option<String>,
+  // the file was left alone on purpose, `error` says why
+  pub skipped: bool,
+}
+
+impl FileDiff {
+  fn failed(path: &Path, e: anyhow::Error) -> Self {
+    FileDiff {
+      path: path.to_string_lossy().into(),
+      diff: String::new(),
+      error: Some(e.to_string()),
+      skipped: e.is::<Skipped>(),
+    }
+  }
+}
+
+#[derive(Debug)]
+struct Skipped;
+
+impl std::fmt::Display for Skipped {
+  fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+    f.write_str("skipped, rewriting would drop its comments")
+  }
+}
+
+impl std::error::Error for Skipped {}
+
+struct Change {
+  path: PathBuf,
+  before: String,
+  after: String,
+}
+
+// Run the pipeline without writing anything, `on_error` gets the files that failed or were skipped
+fn changes(
+  root: &Path,
+  pipeline: &Pipeline,
+  max_size: u64,
+  mut on_error: impl FnMut(&Path, anyhow::Error),
+) -> Result<Vec<Change>> {
+  let settings = WorkspaceSettings::load(root)?;
+  let runner = Runner::new(pipeline, settings.format)?;
+  let globs = files::glob_set(&pipeline.glob)?;
+  let mut changes = vec![];
+  for path in files::walk_json_files(root, max_size) {
+    if !globs.is_match(path.strip_prefix(root).unwrap_or(&path)) {
+      continue;
+    }
+    let result = fs::read_to_string(&path)
+      .map_err(anyhow::Error::from)
+      .and_then(|before| Ok((runner.rewrite(&path, &before)?, before)));
+    match result {
+      Ok((Some(after), before)) => changes.push(Change {
+        path,
+        before,
+        after,
+      }),
+      Ok((None, _)) => (),
+      Err(e) => on_error(&path, e),
+    }
+  }
+  Ok(changes)
+}
+
+// Dry run: the diff of every file the pipeline would change, plus the files it fails on
+pub fn preview(root: &Path, pipeline: &Pipeline, max_size: u64) -> Result<Vec<FileDiff>> {
+  let mut diffs = vec![];
+  let changes = changes(root, pipeline, max_size, |path, e| {
+    diffs.push(FileDiff::failed(path, e))
+  })?;
+  for change in changes {
+    let name = change
+      .path
+      .strip_prefix(root)
+      .unwrap_or(&change.path)
+      .to_string_lossy()
+      .replace('\\', "/");
+    diffs.push(FileDiff {
+      path: change.path.to_string_lossy().into(),
+      diff: TextDiff::from_lines(&change.before, &change.after)
+        .unified_diff()
+        .header(&format!("a/{}", name), &format!("b/{}", name))
+        .to_string(),
+      error: None,
+      skipped: false,
+    });
+  }
+  diffs.sort_by(|a, b| a.path.cmp(&b.path));
+  Ok(diffs)
+}
+
+// Originals of the files changed by one `apply`, stored as `<backups>/<id>/<n>.json`
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Backup {
+  pub id: String,
+  pub pipeline: String,
+  pub root: String,
+  // milliseconds since the Unix epoch
+  pub created: u64,
+  pub files: Vec<String>,
+  // `fingerprint` of what was written to each file, undo leaves files edited since alone
+  #[serde(default)]
+  pub written: Vec<String>,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct Applied {
+  // `None` when no file had to change
+  pub backup: Option<Backup>,
+  pub errors: Vec<FileDiff>,
+}
+
+// Write the pipeline result to every changed file, each file is replaced atomically after its
+// original has been copied to a new backup
+pub fn apply(root: &Path, pipeline: &Pipeline, max_size: u64, backups: &Path) -> Result<Applied> {
+  let mut errors = vec![];
+  let changes = changes(root, pipeline, max_size, |path, e| {
+    errors.push(FileDiff::failed(path, e))
+  })?;
+  if changes.is_empty() {
+    return Ok(Applied {
+      backup: None,
+      errors,
+    });
+  }
+  let created = SystemTime::now().duration_since(UNIX_EPOCH)?.as_millis() as u64;
+  let (id, dir) = backup_dir(backups, created)?;
+  let mut backup = Backup {
+    id,
+    pipeline: pipeline.name.clone(),
+    root: root.to_string_lossy().into(),
+    created,
+    files: vec![],
+    written: vec![],
+  };
+  for (index, change) in changes.iter().enumerate() {
+    fs::write(dir.join(format!("{}.json", index)), &change.before)?;
+    backup.files.push(change.path.to_string_lossy().into());
+    backup.written.push(fingerprint(&change.after));
+  }
+  files::write_atomic(
+    &dir.join(MANIFEST_FILE),
+    &serde_json::to_string_pretty(&backup)?,
+  )?;
+  for change in &changes {
+    if let Err(e) = files::write_atomic(&change.path, &change.after) {
+      errors.push(FileDiff::failed(&change.path, e));
+    }
+  }
+  Ok(Applied {
+    backup: Some(backup),
+    errors,
+  })
+}
+
+// A new directory for a backup made at `created`, `<created>-<n>` when another apply
+// in the same millisecond got there first
+fn backup_dir(backups: &Path, created: u64) -> Result<(String, PathBuf)> {
+  fs::create_dir_all(backups)?;
+  let mut n = 0;
+  loop {
+    let id = match n {
+      0 => created.to_string(),
+      n => format!("{}-{}", created, n),
+    };
+    let dir = backups.join(&id);
+    match fs::create_dir(&dir) {
+      Ok(()) => return Ok((id, dir)),
+      Err(e) if e.kind() == ErrorKind::AlreadyExists => n += 1,
+      Err(e) => return Err(e.into()),
+    }
+  }
+}
+
+// FNV-1a of a written file, stable across builds unlike `DefaultHasher`
+fn fingerprint(text: &str) -> String {
+  let hash = text.bytes().fold(0xcbf2_9ce4_8422_2325_u64, |hash, byte| {
+    (hash ^ byte as u64).wrapping_mul(0x0100_0000_01b3)
+  });
+  format!("{:016x}", hash)
+}
+
+// Backups, newest first
+pub fn backups(backups: &Path) -> Result<Vec<Backup>> {
+  if !backups.is_dir() {
+    return Ok(vec![]);
+  }
+  let mut list = vec![];
+  for entry in fs::read_dir(backups)? {
+    let manifest = entry?.path().join(MANIFEST_FILE);
+    if let Ok(text) = fs::read_to_string(&manifest) {
+      list.push(serde_json::from_str::<Backup>(&text)?);
+    }
+  }
+  list.sort_by_key(|backup| std::cmp::Reverse(backup.created));
+  Ok(list)
+}
+
+// Put the originals of backup `id` back and remove the backup. Nothing is restored when a file
+// was changed since, by hand or by a later pipeline, so those edits are not lost.
+pub fn undo(backups: &Path, id: &str) -> Result<Vec<String>> {
+  if id.is_empty() || !id.chars().all(|c| c.is_ascii_digit() || c == '-') {
+    bail!("invalid backup id `{}`", id);
+  }
+  let dir = backups.join(id);
+  let backup: Backup = serde_json::from_str(&fs::read_to_string(dir.join(MANIFEST_FILE))?)?;
+  let mut originals = vec![];
+  let mut edited = vec![];
+  for (index, path) in backup.files.iter().enumerate() {
+    let original = fs::read_to_string(dir.join(format!("{}.json", index)))?;
+    let current = fs::read_to_string(path).ok();
+    // backups from before `written` was recorded can't be checked
+    let unchanged = match backup.written.get(index) {
+      Some(written) => current
+        .as_deref()
+        .is_some_and(|current| fingerprint(current) == *written || current == original),
+      None => true,
+    };
+    if !unchanged {
+      edited.push(path.as_str());
+    }
+    originals.push(original);
+  }
+  if !edited.is_empty() {
+    bail!(
+      "changed since the batch was applied, nothing was undone: {}",
+      edited.join(", ")
+    );
+  }
+  for (path, original) in backup.files.iter().zip(originals) {
+    files::write_atomic(Path::new(path), &original)?;
+  }
+  fs::remove_dir_all(&dir)?;
+  Ok(backup.files)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::engine::TempDir;
+  use serde_json::json;
+
+  fn pipeline(glob: &str, steps: Value) -> Pipeline {
+    serde_json::from_value(json!({ "name": "test", "glob": [glob], "steps": steps })).unwrap()
+  }
+
+  #[test]
+  fn jq_outputs() {
+    assert_eq!(jq(".a + 1", json!({ "a": 1 })).unwrap(), [json!(2)]);
+    assert_eq!(jq(".[]", json!([1, 2])).unwrap(), [json!(1), json!(2)]);
+    assert!(jq(".a |", json!({})).is_err());
+    assert!(jq("nope", json!({})).is_err());
+  }
+
+  #[test]
+  fn runs_steps_in_order() {
+    let p = pipeline(
+      "*.json",
+      json!([
+        { "kind": "jq", "filter": ".b = .a" },
+        { "kind": "patch", "patch": [{ "op": "remove", "path": "/a" }] },
+        { "kind": "format", "options": { "indent": 4, "sortKeys": true } }
+      ]),
+    );
+    let runner = Runner::new(&p, FormatOptions::default()).unwrap();
+    let out = runner
+      .rewrite(Path::new("a.json"), r#"{"z": 0, "a": 1}"#)
+      .unwrap()
+      .unwrap();
+    assert_eq!(out, "{\n    \"b\": 1,\n    \"z\": 0\n}\n");
+    let jq_many = pipeline("*", json!([{ "kind": "jq", "filter": ".[]" }]));
+    let runner = Runner::new(&jq_many, FormatOptions::default()).unwrap();
+    assert!(runner.rewrite(Path::new("a.json"), "[1, 2]").is_err());
+  }
+
+  #[test]
+  fn jsonl_files_are_rewritten_per_line() {
+    let p = pipeline("*", json!([{ "kind": "jq", "filter": ".n += 1" }]));
+    let runner = Runner::new(&p, FormatOptions::default()).unwrap();
+    let out = runner
+      .rewrite(Path::new("a.jsonl"), "{\"n\": 1}\n\n{\"n\":2}\n")
+      .unwrap();
+    assert_eq!(out.as_deref(), Some("{\"n\":2}\n\n{\"n\":3}\n"));
+  }
+
+  #[test]
+  fn preview_apply_and_undo() {
+    let root = TempDir::new("apply");
+    let backup_dir = root.join(".backups");
+    fs::create_dir_all(root.join("conf")).unwrap();
+    let original = "{\"v\": 1}";
+    fs::write(root.join("conf/a.json"), original).unwrap();
+    fs::write(root.join("conf/same.json"), "{\n  \"v\": 2\n}").unwrap();
+    fs::write(root.join("conf/commented.jsonc"), "{\"v\": 1 /* keep */}").unwrap();
+    fs::write(root.join("conf/broken.json"), "{").unwrap();
+    fs::write(root.join("other.json"), original).unwrap();
+    let p = pipeline("conf/*", json!([{ "kind": "jq", "filter": ".v = 2" }]));
+
+    let diffs = preview(&root, &p, files::DEFAULT_MAX_SIZE).unwrap();
+    let summary: Vec<(String, bool, bool)> = diffs
+      .iter()
+      .map(|d| {
+        let name = Path::new(&d.path)
+          .file_name()
+          .unwrap()
+          .to_string_lossy()
+          .into();
+        (name, d.error.is_some(), d.skipped)
+      })
+      .collect();
+    assert_eq!(
+      summary,
+      [
+        ("a.json".into(), false, false),
+        ("broken.json".into(), true, false),
+        ("commented.jsonc".into(), true, true),
+      ]
+    );
+    assert!(diffs[0].diff.contains("+  \"v\": 2"));
+
+    let applied = apply(&root, &p, files::DEFAULT_MAX_SIZE, &backup_dir).unwrap();
+    let backup = applied.backup.unwrap();
+    assert_eq!(backup.files.len(), 1);
+    assert_eq!(applied.errors.len(), 2);
+    assert_eq!(
+      fs::read_to_string(root.join("conf/a.json")).unwrap(),
+      "{\n  \"v\": 2\n}\n"
+    );
+    assert_eq!(
+      fs::read_to_string(root.join("conf/commented.jsonc")).unwrap(),
+      "{\"v\": 1 /* keep */}"
+    );
+    assert_eq!(
+      fs::read_to_string(root.join("other.json")).unwrap(),
+      original
+    );
+
+    assert_eq!(backups(&backup_dir).unwrap().len(), 1);
+    undo(&backup_dir, &backup.id).unwrap();
+    assert_eq!(
+      fs::read_to_string(root.join("conf/a.json")).unwrap(),
+      original
+    );
+    assert!(backups(&backup_dir).unwrap().is_empty());
+    assert!(undo(&backup_dir, "../x").is_err());
+  }
+
+  #[test]
+  fn undo_keeps_later_edits() {
+    let root = TempDir::new("undo-edited");
+    let backup_dir = root.join(".backups");
+    fs::write(root.join("a.json"), "{\"v\": 1}").unwrap();
+    fs::write(root.join("b.json"), "{\"v\": 1}").unwrap();
+    let first = pipeline("*.json", json!([{ "kind": "jq", "filter": ".v = 2" }]));
+    let second = pipeline("b.json", json!([{ "kind": "jq", "filter": ".v = 3" }]));
+    let older = apply(&root, &first, files::DEFAULT_MAX_SIZE, &backup_dir)
+      .unwrap()
+      .backup
+      .unwrap();
+    let newer = apply(&root, &second, files::DEFAULT_MAX_SIZE, &backup_dir)
+      .unwrap()
+      .backup
+      .unwrap();
+    assert_ne!(older.id, newer.id);
+
+    let err = undo(&backup_dir, &older.id).unwrap_err().to_string();
+    assert!(err.ends_with("b.json"), "{}", err);
+    let b = fs::read_to_string(root.join("b.json")).unwrap();
+    assert_eq!(b, "{\n  \"v\": 3\n}\n");
+    assert_eq!(
+      fs::read_to_string(root.join("a.json")).unwrap(),
+      "{\n  \"v\": 2\n}\n"
+    );
+
+    undo(&backup_dir, &newer.id).unwrap();
+    undo(&backup_dir, &older.id).unwrap();
+    assert_eq!(
+      fs::read_to_string(root.join("b.json")).unwrap(),
+      "{\"v\": 1}"
+    );
+  }
+
+  #[test]
+  fn backups_in_the_same_millisecond_get_their_own_directory() {
+    let root = TempDir::new("backup-ids");
+    let first = backup_dir(&root, 7).unwrap();
+    let second = backup_dir(&root, 7).unwrap();
+    assert_eq!((first.0.as_str(), second.0.as_str()), ("7", "7-1"));
+  }
+}
@@ -0,0 +1,281 @@
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use super::{
+  files,
+  format::{self, FormatOptions},
+};
+
+// A hotkey that rewrites the clipboard without opening a window:
+// `{ "accelerator": "CmdOrCtrl+Alt+P", "action": "prettify" }`
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ClipboardShortcut {
+  pub accelerator: String,
+  pub action: ClipboardAction,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum ClipboardAction {
+  Prettify,
+  Minify,
+  SortKeys,
+  YamlToJson,
+  JsonToYaml,
+  // text -> `"text"` with JSON escapes, and back
+  Escape,
+  Unescape,
+}
+
+impl ClipboardAction {
+  pub fn title(&self) -> &'static str {
+    match self {
+      ClipboardAction::Prettify => "Prettify",
+      ClipboardAction::Minify => "Minify",
+      ClipboardAction::SortKeys => "Sort keys",
+      ClipboardAction::YamlToJson => "YAML to JSON",
+      ClipboardAction::JsonToYaml => "JSON to YAML",
+      ClipboardAction::Escape => "Escape",
+      ClipboardAction::Unescape => "Unescape",
+    }
+  }
+}
+
+// `CmdOrCtrl+Shift+J`: modifiers, then exactly one key
+pub fn validate_accelerator(accelerator: &str) -> Result<()> {
+  let invalid = |reason: &str| anyhow!("invalid shortcut `{}`: {}", accelerator, reason);
+  let mut parts: Vec<String> = accelerator
+    .split('+')
+    .map(|part| part.trim().to_uppercase())
+    .collect();
+  let key = parts.pop().unwrap_or_default();
+  for part in &parts {
+    if !is_modifier(part) {
+      return Err(invalid(&format!("`{}` is not a modifier", part)));
+    }
+  }
+  if key.is_empty() || is_modifier(&key) {
+    return Err(invalid("no key after the modifiers"));
+  }
+  if !is_key(&key) {
+    return Err(invalid(&format!("unknown key `{}`", key)));
+  }
+  // a plain letter would swallow typing in every application
+  if parts.is_empty() && !is_function_key(&key) {
+    return Err(invalid("needs a modifier"));
+  }
+  Ok(())
+}
+
+fn is_modifier(part: &str) -> bool {
+  matches!(
+    part,
+    "SHIFT"
+      | "CONTROL"
+      | "CTRL"
+      | "ALT"
+      | "OPTION"
+      | "SUPER"
+      | "COMMAND"
+      | "CMD"
+      | "COMMANDORCONTROL"
+      | "COMMANDORCTRL"
+      | "CMDORCTRL"
+      | "CMDORCONTROL"
+  )
+}
+
+fn is_function_key(key: &str) -> bool {
+  key
+    .strip_prefix('F')
+    .and_then(|n| n.parse::<u8>().ok())
+    .is_some_and(|n| (1..=24).contains(&n))
+}
+
+fn is_key(key: &str) -> bool {
+  let single = key.len() == 1 && key.chars().all(|c| c.is_ascii_alphanumeric());
+  single
+    || is_function_key(key)
+    || matches!(
+      key,
+      "SPACE"
+        | "TAB"
+        | "ENTER"
+        | "ESCAPE"
+        | "ESC"
+        | "BACKSPACE"
+        | "DELETE"
+        | "INSERT"
+        | "HOME"
+        | "END"
+        | "PAGEUP"
+        | "PAGEDOWN"
+        | "UP"
+        | "DOWN"
+        | "LEFT"
+        | "RIGHT"
+        | "ARROWUP"
+        | "ARROWDOWN"
+        | "ARROWLEFT"
+        | "ARROWRIGHT"
+        | "COMMA"
+        | "PERIOD"
+        | "SLASH"
+        | "BACKSLASH"
+        | "SEMICOLON"
+        | "QUOTE"
+        | "BACKQUOTE"
+        | "MINUS"
+        | "EQUAL"
+        | "BRACKETLEFT"
+        | "BRACKETRIGHT"
+        | ","
+        | "."
+        | "/"
+        | "\\"
+        | ";"
+        | "'"
+        | "`"
+        | "-"
+        | "="
+        | "["
+        | "]"
+    )
+}
+
+// The new clipboard text. JSON input may have comments and trailing commas but is not repaired
+// otherwise, a guess written back over the clipboard would be easy to paste without noticing
+pub fn apply(action: ClipboardAction, text: &str) -> Result<String> {
+  let pretty = FormatOptions {
+    final_newline: false,
+    ..Default::default()
+  };
+  match action {
+    ClipboardAction::Prettify => format::format(&parse(text)?, &pretty),
+    ClipboardAction::Minify => format::format(
+      &parse(text)?,
+      &FormatOptions {
+        indent: 0,
+        ..pretty
+      },
+    ),
+    ClipboardAction::SortKeys => format::format(
+      &parse(text)?,
+      &FormatOptions {
+        sort_keys: true,
+        ..pretty
+      },
+    ),
+    ClipboardAction::YamlToJson => {
+      let value: Value = serde_yaml::from_str(text).map_err(|e| anyhow!("not YAML: {}", e))?;
+      format::format(&value, &pretty)
+    }
+    ClipboardAction::JsonToYaml => Ok(serde_yaml::to_string(&parse(text)?)?),
+    ClipboardAction::Escape => Ok(Value::String(text.into()).to_string()),
+    ClipboardAction::Unescape => {
+      let text = text.trim();
+      // the surrounding quotes are optional
+      serde_json::from_str::<String>(text)
+        .or_else(|_| serde_json::from_str::<String>(&format!("\"{}\"", text)))
+        .map_err(|e| anyhow!("not a JSON string: {}", e))
+    }
+  }
+}
+
+fn parse(text: &str) -> Result<Value> {
+  let text = text.trim().trim_start_matches('\u{feff}');
+  serde_json::from_str(&files::strip_comments(text)).map_err(|e| anyhow!("not JSON: {}", e))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn formats_json() {
+    let text = "{\"b\": [1, 2], \"a\": null}";
+    assert_eq!(
+      apply(ClipboardAction::Prettify, text).unwrap(),
+      "{\n  \"b\": [\n    1,\n    2\n  ],\n  \"a\": null\n}"
+    );
+    assert_eq!(
+      apply(ClipboardAction::Minify, text).unwrap(),
+      "{\"b\":[1,2],\"a\":null}"
+    );
+    assert!(apply(ClipboardAction::SortKeys, text)
+      .unwrap()
+      .starts_with("{\n  \"a\": null"));
+  }
+
+  #[test]
+  fn allows_comments_and_trailing_commas() {
+    let text = "{\n  // id\n  \"a\": 1,\n}";
+    assert_eq!(apply(ClipboardAction::Minify, text).unwrap(), "{\"a\":1}");
+  }
+
+  #[test]
+  fn refuses_text_that_needs_repair() {
+    for text in [
+      "{a: 1}",
+      "{'a': True}",
+      "callback({\"a\": 1})",
+      "[1, 2",
+      "hello",
+    ] {
+      for action in [
+        ClipboardAction::Prettify,
+        ClipboardAction::Minify,
+        ClipboardAction::SortKeys,
+        ClipboardAction::JsonToYaml,
+      ] {
+        let err = apply(action, text).unwrap_err().to_string();
+        assert!(
+          err.starts_with("not JSON"),
+          "{:?} {}: {}",
+          action,
+          text,
+          err
+        );
+      }
+    }
+  }
+
+  #[test]
+  fn converts_yaml() {
+    let json = "{\n  \"a\": [\n    1,\n    \"b\"\n  ]\n}";
+    let yaml = apply(ClipboardAction::JsonToYaml, json).unwrap();
+    assert_eq!(apply(ClipboardAction::YamlToJson, &yaml).unwrap(), json);
+    assert!(apply(ClipboardAction::YamlToJson, "a: [1").is_err());
+  }
+
+  #[test]
+  fn escapes_and_unescapes() {
+    let text = "say \"hi\"\n";
+    let escaped = apply(ClipboardAction::Escape, text).unwrap();
+    assert_eq!(escaped, r#""say \"hi\"\n""#);
+    assert_eq!(apply(ClipboardAction::Unescape, &escaped).unwrap(), text);
+    assert_eq!(apply(ClipboardAction::Unescape, r"a\tb").unwrap(), "a\tb");
+  }
+
+  #[test]
+  fn validates_accelerators() {
+    assert!(validate_accelerator("CmdOrCtrl+Shift+J").is_ok());
+    assert!(validate_accelerator("alt + f5").is_ok());
+    assert!(validate_accelerator("F12").is_ok());
+    let reason = |a: &str| validate_accelerator(a).unwrap_err().to_string();
+    assert_eq!(reason("J"), "invalid shortcut `J`: needs a modifier");
+    assert_eq!(
+      reason("Ctrl+Shift"),
+      "invalid shortcut `Ctrl+Shift`: no key after the modifiers"
+    );
+    assert_eq!(
+      reason("Hyper+J"),
+      "invalid shortcut `Hyper+J`: `HYPER` is not a modifier"
+    );
+    assert_eq!(
+      reason("Ctrl+Enterr"),
+      "invalid shortcut `Ctrl+Enterr`: unknown key `ENTERR`"
+    );
+  }
+}
@@ -0,0 +1,377 @@
+use anyhow::{anyhow, bail, Result};
+use globset::{Glob, GlobSet, GlobSetBuilder};
+use ignore::gitignore::Gitignore;
+use serde_json::Value;
+use std::{
+  fs,
+  path::{Path, PathBuf},
+  sync::atomic::{AtomicU64, Ordering},
+};
+use walkdir::WalkDir;
+
+pub const JSON_EXTENSIONS: [&str; 3] = ["json", "jsonc", "jsonl"];
+// files above this size are skipped by folder operations
+pub const DEFAULT_MAX_SIZE: u64 = 10 * 1024 * 1024;
+// numbers the temporary files of `write_atomic`, two threads writing one path each get their own
+static WRITES: AtomicU64 = AtomicU64::new(0);
+
+pub fn is_json_file(path: &Path) -> bool {
+  path
+    .extension()
+    .and_then(|ext| ext.to_str())
+    .is_some_and(|ext| JSON_EXTENSIONS.contains(&ext.to_lowercase().as_str()))
+}
+
+// Matcher for paths relative to a folder, patterns without a `/` match the file name at any depth
+pub fn glob_set(patterns: &[String]) -> Result<GlobSet> {
+  let mut builder = GlobSetBuilder::new();
+  for pattern in patterns {
+    let pattern = if pattern.contains('/') {
+      pattern.trim_start_matches("./").to_string()
+    } else {
+      format!("**/{}", pattern)
+    };
+    builder.add(Glob::new(&pattern)?);
+  }
+  Ok(builder.build()?)
+}
+
+// Stack of `.gitignore` matchers collected while descending a tree
+#[derive(Default)]
+pub struct Ignores(Vec<Gitignore>);
+
+impl Ignores {
+  // matchers of `dir` and every directory between `root` and `dir`
+  pub fn for_dir(root: &Path, dir: &Path) -> Self {
+    let mut ignores = Ignores::default();
+    let rel = dir.strip_prefix(root).unwrap_or(Path::new(""));
+    let mut cur = root.to_path_buf();
+    ignores.load(&cur);
+    for part in rel.components() {
+      cur.push(part);
+      ignores.load(&cur);
+    }
+    ignores
+  }
+
+  pub fn load(&mut self, dir: &Path) {
+    let gitignore = dir.join(".gitignore");
+    if gitignore.is_file() {
+      self.0.push(Gitignore::new(gitignore).0);
+    }
+  }
+
+  pub fn is_ignored(&self, path: &Path, is_dir: bool) -> bool {
+    if is_dir && path.file_name().is_some_and(|name| name == ".git") {
+      return true;
+    }
+    // the deepest `.gitignore` with a matching rule decides, so `!pattern` can re-include a path
+    for ig in self.0.iter().rev().filter(|ig| path.starts_with(ig.path())) {
+      let matched = ig.matched(path, is_dir);
+      if matched.is_ignore() {
+        return true;
+      }
+      if matched.is_whitelist() {
+        return false;
+      }
+    }
+    false
+  }
+}
+
+// Every JSON file below `root`, skipping `.git` and whatever the `.gitignore` files along the way exclude
+pub fn walk_json_files(root: &Path, max_size: u64) -> impl Iterator<Item = PathBuf> {
+  let mut ignores = Ignores::default();
+  WalkDir::new(root)
+    .follow_links(false)
+    .sort_by_file_name()
+    .into_iter()
+    .filter_entry(move |entry| {
+      let is_dir = entry.file_type().is_dir();
+      if entry.depth() > 0 && ignores.is_ignored(entry.path(), is_dir) {
+        return false;
+      }
+      if is_dir {
+        ignores.load(entry.path());
+      }
+      true
+    })
+    .filter_map(|entry| entry.ok())
+    .filter(move |entry| {
+      entry.file_type().is_file()
+        && is_json_file(entry.path())
+        && entry.metadata().is_ok_and(|m| m.len() <= max_size)
+    })
+    .map(|entry| entry.into_path())
+}
+
+// One JSON text of a file. `.jsonl` files give one source per non-empty line,
+// `prefix` is then the JSON Pointer of that line (`/3`) and `line_offset` the lines before it.
+#[derive(Debug, Clone)]
+pub struct Source {
+  pub prefix: String,
+  pub line_offset: usize,
+  pub text: String,
+}
+
+pub fn is_jsonl(path: &Path) -> bool {
+  path
+    .extension()
+    .is_some_and(|ext| ext.eq_ignore_ascii_case("jsonl"))
+}
+
+pub fn is_jsonc(path: &Path) -> bool {
+  path
+    .extension()
+    .is_some_and(|ext| ext.eq_ignore_ascii_case("jsonc"))
+}
+
+// Comments and trailing commas are accepted in `.jsonc` files, and in any file when `relaxed`
+pub fn sources(path: &Path, relaxed: bool) -> Result<Vec<Source>> {
+  let text = fs::read_to_string(path)?;
+  if is_jsonl(path) {
+    return Ok(
+      text
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .enumerate()
+        .map(|(index, (line_offset, line))| Source {
+          prefix: format!("/{}", index),
+          line_offset,
+          text: line.to_string(),
+        })
+        .collect(),
+    );
+  }
+  // comments are blanked out in place so line/column positions stay valid
+  let text = if relaxed || is_jsonc(path) {
+    strip_comments(&text)
+  } else {
+    text
+  };
+  Ok(vec![Source {
+    prefix: String::new(),
+    line_offset: 0,
+    text,
+  }])
+}
+
+// Parsed file content, `.jsonl` files become an array of their lines
+pub fn read_value(path: &Path) -> Result<Value> {
+  let mut values = sources(path, true)?
+    .into_iter()
+    .map(|source| serde_json::from_str(&source.text))
+    .collect::<Result<Vec<Value>, _>>()?;
+  if is_jsonl(path) {
+    Ok(Value::Array(values))
+  } else {
+    Ok(values.pop().unwrap_or(Value::Null))
+  }
+}
+
+// JSONC -> JSON: `//` and `/* */` comments and trailing commas are replaced with spaces,
+// byte offsets and line breaks are kept as they were
+pub fn strip_comments(text: &str) -> String {
+  let mut bytes = text.as_bytes().to_vec();
+  scan_outside_strings(&mut bytes, |bytes, i| match (bytes[i], bytes.get(i + 1)) {
+    (b'/', Some(b'/')) => {
+      let end = bytes[i..]
+        .iter()
+        .position(|b| *b == b'\n')
+        .map_or(bytes.len(), |p| i + p);
+      blank(&mut bytes[i..end]);
+      end
+    }
+    (b'/', Some(b'*')) => {
+      let end = bytes[i + 2..]
+        .windows(2)
+        .position(|w| w == b"*/")
+        .map_or(bytes.len(), |p| i + 2 + p + 2);
+      blank(&mut bytes[i..end]);
+      end
+    }
+    _ => i + 1,
+  });
+  scan_outside_strings(&mut bytes, |bytes, i| {
+    if bytes[i] == b',' {
+      let next = bytes[i + 1..].iter().find(|b| !b.is_ascii_whitespace());
+      if matches!(next, Some(b'}') | Some(b']')) {
+        bytes[i] = b' ';
+      }
+    }
+    i + 1
+  });
+  // whole comments were blanked, so the text is still valid UTF-8
+  String::from_utf8(bytes).unwrap_or_else(|_| text.to_string())
+}
+
+// Call `f` for each byte outside string literals, `f` returns the index to continue at
+fn scan_outside_strings<F>(bytes: &mut [u8], mut f: F)
+where
+  F: FnMut(&mut [u8], usize) -> usize,
+{
+  let mut i = 0;
+  let mut in_string = false;
+  while i < bytes.len() {
+    if in_string {
+      match bytes[i] {
+        b'\\' => i += 1,
+        b'"' => in_string = false,
+        _ => (),
+      }
+      i += 1;
+    } else if bytes[i] == b'"' {
+      in_string = true;
+      i += 1;
+    } else {
+      i = f(bytes, i);
+    }
+  }
+}
+
+fn blank(bytes: &mut [u8]) {
+  for byte in bytes {
+    if *byte != b'\n' {
+      *byte = b' ';
+    }
+  }
+}
+
+// `<dir>/<name>.json` of a named item (pipeline, request collection...), the name must not escape `dir`
+pub fn named_file(dir: &Path, name: &str) -> Result<PathBuf> {
+  if name.trim().is_empty() || name.contains(['/', '\\']) || name.starts_with('.') {
+    bail!("invalid name `{}`", name);
+  }
+  Ok(dir.join(format!("{}.json", name)))
+}
+
+// Write to a temporary file next to `path`, then rename it over `path`
+pub fn write_atomic(path: &Path, text: &str) -> Result<()> {
+  let dir = path.parent().unwrap_or(Path::new("."));
+  let name = path
+    .file_name()
+    .ok_or_else(|| anyhow!("{} is not a file path", path.display()))?;
+  let tmp = dir.join(format!(
+    ".{}.{}-{}.tmp",
+    name.to_string_lossy(),
+    std::process::id(),
+    WRITES.fetch_add(1, Ordering::Relaxed)
+  ));
+  let result = (|| -> Result<()> {
+    let file = fs::File::create_new(&tmp)?;
+    std::io::Write::write_all(&mut &file, text.as_bytes())?;
+    file.sync_all()?;
+    if let Ok(meta) = fs::metadata(path) {
+      fs::set_permissions(&tmp, meta.permissions())?;
+    }
+    fs::rename(&tmp, path)?;
+    Ok(())
+  })();
+  if result.is_err() {
+    fs::remove_file(&tmp).ok();
+  }
+  result
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::engine::TempDir;
+
+  fn walk(root: &Path) -> Vec<String> {
+    walk_json_files(root, DEFAULT_MAX_SIZE)
+      .map(|p| {
+        p.strip_prefix(root)
+          .unwrap()
+          .to_string_lossy()
+          .replace('\\', "/")
+      })
+      .collect()
+  }
+
+  #[test]
+  fn walks_json_files_honoring_gitignore() {
+    let root = TempDir::new("walk");
+    for file in [
+      "a.json",
+      "b.JSONC",
+      "c.jsonl",
+      "d.txt",
+      "big.json",
+      "sub/e.json",
+      "sub/skip.json",
+      "build/f.json",
+      ".git/g.json",
+    ] {
+      let path = root.join(file);
+      fs::create_dir_all(path.parent().unwrap()).unwrap();
+      fs::write(path, if file == "big.json" { "[1, 2, 3]" } else { "1" }).unwrap();
+    }
+    fs::write(root.join(".gitignore"), "build/\nskip.json\n").unwrap();
+    let files = walk_json_files(&root, 3).count();
+    assert_eq!(
+      walk(&root),
+      ["a.json", "b.JSONC", "big.json", "c.jsonl", "sub/e.json"]
+    );
+    assert_eq!(files, 4);
+  }
+
+  #[test]
+  fn negated_patterns_re_include_files() {
+    let root = TempDir::new("negate");
+    for file in [
+      "keep.json",
+      "drop.json",
+      "sub/drop.json",
+      "sub/again.json",
+      "sub/x.json",
+    ] {
+      let path = root.join(file);
+      fs::create_dir_all(path.parent().unwrap()).unwrap();
+      fs::write(path, "1").unwrap();
+    }
+    fs::write(root.join(".gitignore"), "*.json\n!keep.json\n!sub/\n").unwrap();
+    fs::write(root.join("sub/.gitignore"), "!*.json\ndrop.json\n").unwrap();
+    assert_eq!(walk(&root), ["keep.json", "sub/again.json", "sub/x.json"]);
+
+    let ignores = Ignores::for_dir(&root, &root.join("sub"));
+    assert!(ignores.is_ignored(&root.join("drop.json"), false));
+    assert!(!ignores.is_ignored(&root.join("sub/x.json"), false));
+    assert!(ignores.is_ignored(&root.join("sub/drop.json"), false));
+  }
+
+  #[test]
+  fn concurrent_atomic_writes_keep_one_whole_file() {
+    let root = TempDir::new("atomic");
+    let path = root.join("a.json");
+    let texts: Vec<String> = (0..8).map(|i| format!("[{}]", i).repeat(10_000)).collect();
+    std::thread::scope(|scope| {
+      for text in &texts {
+        let path = &path;
+        scope.spawn(move || write_atomic(path, text).unwrap());
+      }
+    });
+    assert!(texts.contains(&fs::read_to_string(&path).unwrap()));
+    assert_eq!(fs::read_dir(&root).unwrap().count(), 1);
+  }
+
+  #[test]
+  fn extensions_match_in_any_case() {
+    assert!(is_jsonl(Path::new("DATA.JSONL")));
+    assert!(is_jsonc(Path::new("tsconfig.JsonC")));
+    assert!(!is_jsonl(Path::new("data.json")));
+    let root = TempDir::new("case");
+    let path = root.join("DATA.JSONL");
+    fs::write(&path, "{\"a\": 1}\n{\"a\": 2}\n").unwrap();
+    assert_eq!(sources(&path, false).unwrap().len(), 2);
+  }
+
+  #[test]
+  fn globs_match_names_at_any_depth() {
+    let set = glob_set(&["*.json".into(), "./conf/app.json".into()]).unwrap();
+    assert!(set.is_match("a/b/c.json"));
+    assert!(set.is_match("conf/app.json"));
+    assert!(!set.is_match("a.jsonc"));
+  }
+}
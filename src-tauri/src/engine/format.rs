@@ -0,0 +1,69 @@
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use serde_json::{ser::PrettyFormatter, Map, Serializer, Value};
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(default, rename_all = "camelCase")]
+pub struct FormatOptions {
+  // spaces per level, 0 writes the document on a single line
+  pub indent: usize,
+  pub tabs: bool,
+  pub sort_keys: bool,
+  pub final_newline: bool,
+}
+
+impl Default for FormatOptions {
+  fn default() -> Self {
+    Self {
+      indent: 2,
+      tabs: false,
+      sort_keys: false,
+      final_newline: true,
+    }
+  }
+}
+
+pub fn format(value: &Value, options: &FormatOptions) -> Result<String> {
+  let sorted;
+  let value = if options.sort_keys {
+    sorted = sort_keys(value.clone());
+    &sorted
+  } else {
+    value
+  };
+  let mut text = if options.indent == 0 && !options.tabs {
+    serde_json::to_string(value)?
+  } else {
+    let indent = if options.tabs {
+      "\t".to_string()
+    } else {
+      " ".repeat(options.indent)
+    };
+    let mut out = vec![];
+    let mut ser =
+      Serializer::with_formatter(&mut out, PrettyFormatter::with_indent(indent.as_bytes()));
+    value.serialize(&mut ser)?;
+    String::from_utf8(out)?
+  };
+  if options.final_newline {
+    text.push('\n');
+  }
+  Ok(text)
+}
+
+pub fn sort_keys(value: Value) -> Value {
+  match value {
+    Value::Object(map) => {
+      let mut entries: Vec<(String, Value)> = map.into_iter().collect();
+      entries.sort_by(|a, b| a.0.cmp(&b.0));
+      Value::Object(
+        entries
+          .into_iter()
+          .map(|(k, v)| (k, sort_keys(v)))
+          .collect::<Map<_, _>>(),
+      )
+    }
+    Value::Array(arr) => Value::Array(arr.into_iter().map(sort_keys).collect()),
+    v => v,
+  }
+}
@@ -0,0 +1,258 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::cmp::Reverse;
+
+use super::escape_token;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum SourceKind {
+  // open in a window, `id` is the window label
+  Document,
+  // `id` is the file path
+  Recent,
+  Snippet,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Field {
+  Name,
+  Key,
+  Value,
+}
+
+pub struct Source {
+  pub kind: SourceKind,
+  pub id: String,
+  // file or window title, matched as `Field::Name`
+  pub name: String,
+  pub value: Value,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct FuzzyHit {
+  pub kind: SourceKind,
+  pub id: String,
+  pub name: String,
+  pub pointer: String,
+  pub field: Field,
+  pub text: String,
+  pub score: i64,
+  // char indices of `text` that matched, for highlighting
+  pub matches: Vec<usize>,
+}
+
+// large documents are only searched this deep into their nodes
+const MAX_NODES: usize = 200_000;
+// long values are matched on their start
+const MAX_TEXT: usize = 200;
+
+// Best hits of `query` over file names, keys and scalar values of every source
+pub fn search(query: &str, sources: &[Source], limit: usize) -> Vec<FuzzyHit> {
+  let query: Vec<char> = query.trim().to_lowercase().chars().collect();
+  let mut hits = vec![];
+  for source in sources {
+    let mut push = |pointer: &str, field: Field, text: &str| {
+      let text: String = text.chars().take(MAX_TEXT).collect();
+      if let Some((score, matches)) = score(&query, &text) {
+        hits.push(FuzzyHit {
+          kind: source.kind,
+          id: source.id.clone(),
+          name: source.name.clone(),
+          pointer: pointer.into(),
+          field,
+          text,
+          // a matching file name beats the same match deep in a document
+          score: if field == Field::Name {
+            score + 10
+          } else {
+            score
+          },
+          matches,
+        });
+      }
+    };
+    push("", Field::Name, &source.name);
+    if !query.is_empty() {
+      let mut nodes = 0;
+      walk(&source.value, &mut String::new(), &mut nodes, &mut push);
+    }
+  }
+  hits.sort_by_key(|hit| (Reverse(hit.score), hit.text.len()));
+  hits.truncate(limit);
+  hits
+}
+
+fn walk<F>(value: &Value, pointer: &mut String, nodes: &mut usize, push: &mut F)
+where
+  F: FnMut(&str, Field, &str),
+{
+  *nodes += 1;
+  if *nodes > MAX_NODES {
+    return;
+  }
+  let len = pointer.len();
+  match value {
+    Value::Object(map) => {
+      for (key, child) in map {
+        pointer.push('/');
+        pointer.push_str(&escape_token(key));
+        push(pointer, Field::Key, key);
+        walk(child, pointer, nodes, push);
+        pointer.truncate(len);
+      }
+    }
+    Value::Array(items) => {
+      for (i, child) in items.iter().enumerate() {
+        pointer.push_str(&format!("/{}", i));
+        walk(child, pointer, nodes, push);
+        pointer.truncate(len);
+      }
+    }
+    Value::String(s) => push(pointer, Field::Value, s),
+    Value::Null => (),
+    scalar => push(pointer, Field::Value, &scalar.to_string()),
+  }
+}
+
+// Characters of `query` (lowercase) in order within `text`, scored by how tightly they match:
+// runs and word starts count more, gaps count against, a plain substring gets a bonus
+pub fn score(query: &[char], text: &str) -> Option<(i64, Vec<usize>)> {
+  if query.is_empty() {
+    return Some((0, vec![]));
+  }
+  let chars: Vec<char> = text.chars().collect();
+  let lower: Vec<char> = chars
+    .iter()
+    .map(|c| c.to_lowercase().next().unwrap_or(*c))
+    .collect();
+  let mut matches: Vec<usize> = Vec::with_capacity(query.len());
+  let mut score: i64 = 0;
+  let mut next = 0;
+  for q in query {
+    let i = next + lower[next..].iter().position(|c| c == q)?;
+    score += 1;
+    match matches.last() {
+      Some(last) if *last + 1 == i => score += 5,
+      Some(last) => score -= (i - last - 1).min(3) as i64,
+      None => score -= i.min(3) as i64,
+    }
+    let word_start = i == 0
+      || !chars[i - 1].is_alphanumeric()
+      || (chars[i - 1].is_lowercase() && chars[i].is_uppercase());
+    if word_start {
+      score += 8;
+    }
+    matches.push(i);
+    next = i + 1;
+  }
+  let query: String = query.iter().collect();
+  let lower: String = lower.into_iter().collect();
+  if lower.contains(&query) {
+    score += 15;
+    if lower == query {
+      score += 20;
+    }
+  }
+  Some((score, matches))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use serde_json::json;
+
+  fn query(text: &str) -> Vec<char> {
+    text.chars().collect()
+  }
+
+  fn source(kind: SourceKind, name: &str, value: Value) -> Source {
+    Source {
+      kind,
+      id: name.into(),
+      name: name.into(),
+      value,
+    }
+  }
+
+  #[test]
+  fn matches_characters_in_order() {
+    assert_eq!(score(&query("usr"), "userName").unwrap().1, vec![0, 1, 3]);
+    assert!(score(&query("rsu"), "userName").is_none());
+    assert!(score(&query("userx"), "user").is_none());
+    assert_eq!(score(&[], "anything"), Some((0, vec![])));
+  }
+
+  #[test]
+  fn ranks_tight_matches_higher() {
+    let rank = |text: &str| score(&query("name"), text).unwrap().0;
+    assert!(rank("name") > rank("userName"));
+    assert!(rank("userName") > rank("nxaxmxe"));
+    // a camelCase word start beats the same letters mid-word
+    assert!(rank("firstName") > rank("surname_x"));
+  }
+
+  #[test]
+  fn searches_names_keys_and_values() {
+    let sources = [
+      source(
+        SourceKind::Document,
+        "orders.json",
+        json!({"items": [{"sku": "abc-1", "price": 12}], "a/b": null}),
+      ),
+      source(SourceKind::Snippet, "customer", json!({"name": "Ada"})),
+    ];
+    let hits = search("sku", &sources, 10);
+    assert_eq!(hits[0].pointer, "/items/0/sku");
+    assert_eq!(hits[0].field, Field::Key);
+    let hits = search("abc", &sources, 10);
+    assert_eq!(hits[0].field, Field::Value);
+    assert_eq!(hits[0].text, "abc-1");
+    let hits = search("a/b", &sources, 10);
+    assert_eq!(hits[0].pointer, "/a~1b");
+    let hits = search("12", &sources, 10);
+    assert_eq!(
+      (hits[0].pointer.as_str(), hits[0].kind),
+      ("/items/0/price", SourceKind::Document)
+    );
+    let hits = search("ada", &sources, 10);
+    assert_eq!(
+      (hits[0].id.as_str(), hits[0].kind),
+      ("customer", SourceKind::Snippet)
+    );
+  }
+
+  #[test]
+  fn prefers_names_and_keeps_the_limit() {
+    // the same match in a key scores lower than in a file name
+    let sources = [
+      source(SourceKind::Recent, "config.json", json!({"other": 1})),
+      source(SourceKind::Recent, "data.json", json!({"configs": 1})),
+    ];
+    let hits = search("config", &sources, 10);
+    assert_eq!(hits[0].field, Field::Name);
+    assert_eq!(hits[0].id, "config.json");
+    assert_eq!(hits.len(), 2);
+    assert_eq!(search("config", &sources, 1).len(), 1);
+  }
+
+  #[test]
+  fn empty_query_lists_sources_only() {
+    let sources = [source(SourceKind::Recent, "a.json", json!({"k": "v"}))];
+    let hits = search("  ", &sources, 10);
+    assert_eq!(hits.len(), 1);
+    assert_eq!((hits[0].field, hits[0].pointer.as_str()), (Field::Name, ""));
+  }
+
+  #[test]
+  fn matches_long_values_on_their_start() {
+    let long = format!("start{}end", "x".repeat(MAX_TEXT));
+    let sources = [source(SourceKind::Recent, "a.json", json!({"k": long}))];
+    assert_eq!(
+      search("start", &sources, 10)[0].text.chars().count(),
+      MAX_TEXT
+    );
+    assert!(search("xend", &sources, 10).is_empty());
+  }
+}
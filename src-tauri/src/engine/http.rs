@@ -0,0 +1,104 @@
+use anyhow::Result;
+use reqwest::{redirect::Policy, Certificate, Client, Method, Proxy};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::{fs, str::FromStr, time::Instant};
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Header {
+  pub name: String,
+  pub value: String,
+}
+
+fn default_method() -> String {
+  "GET".into()
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct HttpRequest {
+  #[serde(default = "default_method")]
+  pub method: String,
+  pub url: String,
+  #[serde(default)]
+  pub headers: Vec<Header>,
+  #[serde(default)]
+  pub body: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct HttpResponse {
+  // after redirects
+  pub url: String,
+  pub status: u16,
+  pub status_text: String,
+  pub headers: Vec<Header>,
+  // milliseconds until the whole body was read
+  pub elapsed: u64,
+  // decoded body size in bytes
+  pub size: usize,
+  pub body: String,
+  // the body, when it is JSON
+  pub json: Option<Value>,
+}
+
+// Network settings, see the `proxy` / `accept_invalid_certs` / `ca_cert` fields of `AppConf`
+#[derive(Debug, Clone, Default)]
+pub struct ClientOptions {
+  pub proxy: Option<String>,
+  pub accept_invalid_certs: bool,
+  // PEM file with an extra root certificate
+  pub ca_cert: Option<String>,
+}
+
+const MAX_REDIRECTS: usize = 10;
+
+pub fn client(options: &ClientOptions) -> Result<Client> {
+  let mut builder = Client::builder()
+    .user_agent(concat!("JsonEditor/", env!("CARGO_PKG_VERSION")))
+    .redirect(Policy::limited(MAX_REDIRECTS))
+    .gzip(true)
+    .brotli(true)
+    .danger_accept_invalid_certs(options.accept_invalid_certs);
+  if let Some(proxy) = options.proxy.as_deref().filter(|p| !p.is_empty()) {
+    builder = builder.proxy(Proxy::all(proxy)?);
+  }
+  if let Some(path) = options.ca_cert.as_deref().filter(|p| !p.is_empty()) {
+    builder = builder.add_root_certificate(Certificate::from_pem(&fs::read(path)?)?);
+  }
+  Ok(builder.build()?)
+}
+
+pub async fn send(client: &Client, request: &HttpRequest) -> Result<HttpResponse> {
+  let method = Method::from_str(&request.method.to_uppercase())?;
+  let mut builder = client.request(method, request.url.trim());
+  for header in &request.headers {
+    builder = builder.header(header.name.trim(), header.value.trim());
+  }
+  if let Some(body) = &request.body {
+    builder = builder.body(body.clone());
+  }
+  let start = Instant::now();
+  let response = builder.send().await?;
+  let url = response.url().to_string();
+  let status = response.status();
+  let headers = response
+    .headers()
+    .iter()
+    .map(|(name, value)| Header {
+      name: name.to_string(),
+      value: String::from_utf8_lossy(value.as_bytes()).into(),
+    })
+    .collect();
+  let bytes = response.bytes().await?;
+  let elapsed = start.elapsed().as_millis() as u64;
+  Ok(HttpResponse {
+    url,
+    status: status.as_u16(),
+    status_text: status.canonical_reason().unwrap_or_default().into(),
+    headers,
+    elapsed,
+    size: bytes.len(),
+    json: serde_json::from_slice(&bytes).ok(),
+    body: String::from_utf8_lossy(&bytes).into(),
+  })
+}
@@ -0,0 +1,899 @@
+use anyhow::{anyhow, bail, Result};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::{BTreeMap, BTreeSet};
+
+use super::{
+  http::{Header, HttpRequest, HttpResponse},
+  requests::SavedRequest,
+};
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum ImportSource {
+  // a pasted `curl ...` command line
+  Curl {
+    command: String,
+    name: Option<String>,
+  },
+  Har {
+    path: String,
+  },
+  Postman {
+    path: String,
+  },
+}
+
+#[derive(Serialize, Debug, Clone, Default)]
+pub struct Imported {
+  pub requests: Vec<SavedRequest>,
+  // Postman collection variables, usable as an environment
+  pub variables: BTreeMap<String, String>,
+}
+
+pub fn import(source: &ImportSource) -> Result<Imported> {
+  match source {
+    ImportSource::Curl { command, name } => {
+      let request = parse_curl(command)?;
+      Ok(Imported {
+        requests: vec![SavedRequest {
+          name: name.clone().unwrap_or_else(|| default_name(&request)),
+          request,
+        }],
+        variables: BTreeMap::new(),
+      })
+    }
+    ImportSource::Har { path } => Ok(Imported {
+      requests: parse_har(&std::fs::read_to_string(path)?)?
+        .into_iter()
+        .map(|entry| SavedRequest {
+          name: format!("{} {}", entry.index + 1, default_name(&entry.request)),
+          request: entry.request,
+        })
+        .collect(),
+      variables: BTreeMap::new(),
+    }),
+    ImportSource::Postman { path } => parse_postman(&std::fs::read_to_string(path)?),
+  }
+}
+
+// `GET /items` for `GET https://api.example.com/items?page=2`
+fn default_name(request: &HttpRequest) -> String {
+  let path = request
+    .url
+    .split_once("://")
+    .map_or(request.url.as_str(), |(_, rest)| rest);
+  let path = path.find('/').map_or("/", |i| &path[i..]);
+  let path = path.split(['?', '#']).next().unwrap_or(path);
+  format!("{} {}", request.method, path)
+}
+
+// --- cURL
+
+// curl options followed by a value that this importer ignores
+const CURL_IGNORED_VALUES: [&str; 18] = [
+  "-o",
+  "--output",
+  "-w",
+  "--write-out",
+  "-m",
+  "--max-time",
+  "--connect-timeout",
+  "-x",
+  "--proxy",
+  "--retry",
+  "-c",
+  "--cookie-jar",
+  "--cacert",
+  "-E",
+  "--cert",
+  "--key",
+  "--resolve",
+  "--max-redirs",
+];
+
+pub fn parse_curl(command: &str) -> Result<HttpRequest> {
+  let words = shell_words(command)?;
+  let mut args = words.iter().map(String::as_str).peekable();
+  if args.next_if(|word| *word == "curl").is_none() {
+    bail!("not a curl command");
+  }
+  let mut method = None;
+  let mut url = None;
+  let mut headers: Vec<Header> = vec![];
+  let mut data: Vec<String> = vec![];
+  let mut get = false;
+  let mut json = false;
+  while let Some(arg) = args.next() {
+    // `--request=POST`, `-XPOST`
+    let (flag, attached) = match arg.split_once('=') {
+      Some((flag, value)) if arg.starts_with("--") => (flag, Some(value.to_string())),
+      _ if arg.starts_with('-') && !arg.starts_with("--") && arg.len() > 2 => {
+        // `get` keeps a pasted `-é` from being split inside a character
+        match (arg.get(..2), arg.get(2..)) {
+          (Some(flag), Some(value)) if takes_value(flag) => (flag, Some(value.to_string())),
+          _ => (arg, None),
+        }
+      }
+      _ => (arg, None),
+    };
+    let mut value = || {
+      attached
+        .clone()
+        .or_else(|| args.next().map(String::from))
+        .ok_or_else(|| anyhow!("missing value for `{}`", flag))
+    };
+    match flag {
+      "-X" | "--request" => method = Some(value()?.to_uppercase()),
+      "-H" | "--header" => {
+        let header = value()?;
+        if let Some((name, val)) = header.split_once(':') {
+          headers.push(Header {
+            name: name.trim().into(),
+            value: val.trim().into(),
+          });
+        }
+      }
+      "--data-raw" => data.push(value()?),
+      "-d" | "--data" | "--data-binary" | "--data-ascii" => data.push(inline_data(flag, value()?)?),
+      // `content`, `=content`, `name=content`; `@file` and `name@file` read a file
+      "--data-urlencode" => {
+        let field = value()?;
+        data.push(match field.find(['=', '@']) {
+          Some(i) if field[i..].starts_with('@') => {
+            bail!(
+              "`{} {}` reads a file, paste its content instead",
+              flag,
+              field
+            )
+          }
+          Some(0) => url_encode(&field[1..]),
+          Some(i) => format!("{}={}", &field[..i], url_encode(&field[i + 1..])),
+          None => url_encode(&field),
+        });
+      }
+      "--json" => {
+        data.push(inline_data(flag, value()?)?);
+        json = true;
+      }
+      "-u" | "--user" => headers.push(Header {
+        name: "Authorization".into(),
+        value: format!("Basic {}", STANDARD.encode(value()?)),
+      }),
+      "-A" | "--user-agent" => headers.push(Header {
+        name: "User-Agent".into(),
+        value: value()?,
+      }),
+      "-b" | "--cookie" => headers.push(Header {
+        name: "Cookie".into(),
+        value: value()?,
+      }),
+      "-e" | "--referer" => headers.push(Header {
+        name: "Referer".into(),
+        value: value()?,
+      }),
+      "--url" => url = Some(value()?),
+      "-G" | "--get" => get = true,
+      "-I" | "--head" => method = Some("HEAD".into()),
+      "-F" | "--form" => bail!("multipart forms (`{}`) are not supported", flag),
+      flag if CURL_IGNORED_VALUES.contains(&flag) => {
+        value()?;
+      }
+      flag if flag.starts_with('-') => (),
+      _ => url = Some(arg.to_string()),
+    }
+  }
+  let mut url = url.ok_or_else(|| anyhow!("no URL in the curl command"))?;
+  let has_header = |headers: &[Header], name: &str| {
+    headers
+      .iter()
+      .any(|header| header.name.eq_ignore_ascii_case(name))
+  };
+  let mut body = None;
+  if !data.is_empty() {
+    let joined = data.join("&");
+    if get {
+      url.push(if url.contains('?') { '&' } else { '?' });
+      url.push_str(&joined);
+    } else {
+      if json {
+        if !has_header(&headers, "Content-Type") {
+          headers.push(Header {
+            name: "Content-Type".into(),
+            value: "application/json".into(),
+          });
+        }
+        if !has_header(&headers, "Accept") {
+          headers.push(Header {
+            name: "Accept".into(),
+            value: "application/json".into(),
+          });
+        }
+      } else if !has_header(&headers, "Content-Type") {
+        headers.push(Header {
+          name: "Content-Type".into(),
+          value: "application/x-www-form-urlencoded".into(),
+        });
+      }
+      body = Some(joined);
+    }
+  }
+  let method = method.unwrap_or_else(|| {
+    if body.is_some() {
+      "POST".into()
+    } else {
+      "GET".into()
+    }
+  });
+  Ok(HttpRequest {
+    method,
+    url,
+    headers,
+    body,
+  })
+}
+
+// `-d @file` reads the body from a file the pasted command can not reach
+fn inline_data(flag: &str, data: String) -> Result<String> {
+  if data.starts_with('@') {
+    bail!(
+      "`{} {}` reads a file, paste its content instead",
+      flag,
+      data
+    );
+  }
+  Ok(data)
+}
+
+fn takes_value(flag: &str) -> bool {
+  matches!(
+    flag,
+    "-X" | "-H" | "-d" | "-u" | "-A" | "-b" | "-e" | "-F" | "-o" | "-w" | "-m" | "-x" | "-c" | "-E"
+  )
+}
+
+fn url_encode(text: &str) -> String {
+  let mut out = String::new();
+  for byte in text.bytes() {
+    match byte {
+      b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => out.push(byte as char),
+      _ => out.push_str(&format!("%{:02X}", byte)),
+    }
+  }
+  out
+}
+
+// Split a POSIX shell command line: '...', "...", $'...' (as written by browser devtools) and
+// backslash line continuations
+pub fn shell_words(text: &str) -> Result<Vec<String>> {
+  let mut words = vec![];
+  let mut word = String::new();
+  let mut in_word = false;
+  let mut chars = text.chars().peekable();
+  while let Some(c) = chars.next() {
+    match c {
+      '\\' => match chars.next() {
+        Some('\n') => (),
+        Some('\r') => {
+          chars.next_if_eq(&'\n');
+        }
+        Some(c) => {
+          word.push(c);
+          in_word = true;
+        }
+        None => (),
+      },
+      '\'' => {
+        in_word = true;
+        loop {
+          match chars.next() {
+            Some('\'') => break,
+            Some(c) => word.push(c),
+            None => bail!("unterminated quote"),
+          }
+        }
+      }
+      '"' => {
+        in_word = true;
+        loop {
+          match chars.next() {
+            Some('"') => break,
+            Some('\\') => match chars.next() {
+              Some(c @ ('"' | '\\' | '$' | '`')) => word.push(c),
+              Some('\n') => (),
+              Some(c) => {
+                word.push('\\');
+                word.push(c);
+              }
+              None => bail!("unterminated quote"),
+            },
+            Some(c) => word.push(c),
+            None => bail!("unterminated quote"),
+          }
+        }
+      }
+      '$' if chars.peek() == Some(&'\'') => {
+        chars.next();
+        in_word = true;
+        loop {
+          match chars.next() {
+            Some('\'') => break,
+            Some('\\') => match chars.next() {
+              Some('n') => word.push('\n'),
+              Some('t') => word.push('\t'),
+              Some('r') => word.push('\r'),
+              Some(c @ ('x' | 'u')) => {
+                let len = if c == 'x' { 2 } else { 4 };
+                let mut hex = String::new();
+                while hex.len() < len {
+                  match chars.next_if(|c| c.is_ascii_hexdigit()) {
+                    Some(c) => hex.push(c),
+                    None => break,
+                  }
+                }
+                let code =
+                  u32::from_str_radix(&hex, 16).map_err(|_| anyhow!("invalid escape `\\{}`", c))?;
+                word.push(char::from_u32(code).unwrap_or(char::REPLACEMENT_CHARACTER));
+              }
+              Some(c) => word.push(c),
+              None => bail!("unterminated quote"),
+            },
+            Some(c) => word.push(c),
+            None => bail!("unterminated quote"),
+          }
+        }
+      }
+      c if c.is_whitespace() => {
+        if in_word {
+          words.push(std::mem::take(&mut word));
+          in_word = false;
+        }
+      }
+      c => {
+        word.push(c);
+        in_word = true;
+      }
+    }
+  }
+  if in_word {
+    words.push(word);
+  }
+  Ok(words)
+}
+
+// --- HAR
+
+#[derive(Serialize, Debug, Clone)]
+pub struct HarEntry {
+  pub index: usize,
+  pub started: String,
+  pub request: HttpRequest,
+  pub response: HttpResponse,
+  // why the response body was left out: not valid base64, or binary such as an image
+  pub skipped_body: Option<String>,
+}
+
+pub fn parse_har(text: &str) -> Result<Vec<HarEntry>> {
+  let har: Value = serde_json::from_str(text)?;
+  let entries = har
+    .pointer("/log/entries")
+    .and_then(Value::as_array)
+    .ok_or_else(|| anyhow!("not a HAR file: missing log.entries"))?;
+  entries
+    .iter()
+    .enumerate()
+    .map(|(index, entry)| {
+      let request = &entry["request"];
+      let response = &entry["response"];
+      let content = &response["content"];
+      let (body, skipped_body) = match response_body(content) {
+        Ok(body) => (body, None),
+        Err(reason) => (String::new(), Some(reason)),
+      };
+      let size = match skipped_body {
+        Some(_) => content["size"].as_u64().unwrap_or(0) as usize,
+        None => body.len(),
+      };
+      Ok(HarEntry {
+        index,
+        started: str_at(entry, "startedDateTime"),
+        request: HttpRequest {
+          method: str_at(request, "method"),
+          url: str_at(request, "url"),
+          headers: har_headers(&request["headers"]),
+          body: request
+            .pointer("/postData/text")
+            .and_then(Value::as_str)
+            .map(String::from),
+        },
+        response: HttpResponse {
+          url: str_at(request, "url"),
+          status: response["status"].as_u64().unwrap_or(0) as u16,
+          status_text: str_at(response, "statusText"),
+          headers: har_headers(&response["headers"]),
+          elapsed: entry["time"].as_f64().unwrap_or(0.0).max(0.0) as u64,
+          size,
+          json: serde_json::from_str(&body).ok(),
+          body,
+        },
+        skipped_body,
+      })
+    })
+    .collect()
+}
+
+fn str_at(value: &Value, key: &str) -> String {
+  value[key].as_str().unwrap_or_default().to_string()
+}
+
+// HTTP/2 pseudo headers (`:authority`...) are left out
+fn har_headers(headers: &Value) -> Vec<Header> {
+  headers
+    .as_array()
+    .into_iter()
+    .flatten()
+    .filter(|header| !str_at(header, "name").starts_with(':'))
+    .map(|header| Header {
+      name: str_at(header, "name"),
+      value: str_at(header, "value"),
+    })
+    .collect()
+}
+
+// The body as text, or why it is left out, one bad entry does not fail the whole file
+fn response_body(content: &Value) -> std::result::Result<String, String> {
+  let text = str_at(content, "text");
+  if content["encoding"].as_str() != Some("base64") {
+    return Ok(text);
+  }
+  let bytes = STANDARD
+    .decode(text.trim())
+    .map_err(|e| format!("response body is not valid base64: {}", e))?;
+  String::from_utf8(bytes).map_err(|e| {
+    let mime = content["mimeType"].as_str().unwrap_or("binary data");
+    format!("{} bytes of {} left out", e.as_bytes().len(), mime)
+  })
+}
+
+// --- Postman
+
+pub fn parse_postman(text: &str) -> Result<Imported> {
+  let collection: Value = serde_json::from_str(text)?;
+  let schema = collection.pointer("/info/schema").and_then(Value::as_str);
+  if !schema.is_some_and(|schema| schema.contains("v2.")) {
+    bail!("not a Postman v2 collection");
+  }
+  let mut imported = Imported::default();
+  for variable in collection["variable"].as_array().into_iter().flatten() {
+    if let (Some(key), Some(value)) = (variable["key"].as_str(), variable.get("value")) {
+      let value = match value {
+        Value::String(s) => s.clone(),
+        v => v.to_string(),
+      };
+      imported.variables.insert(key.into(), value);
+    }
+  }
+  postman_items(
+    &collection["item"],
+    "",
+    collection.get("auth"),
+    &mut imported.requests,
+  )?;
+  unique_names(&mut imported.requests);
+  Ok(imported)
+}
+
+// Collections merge requests by name, so repeated names become `Name (2)`, `Name (3)`...
+fn unique_names(requests: &mut [SavedRequest]) {
+  let mut seen = BTreeSet::new();
+  for request in requests.iter_mut() {
+    if !seen.insert(request.name.clone()) {
+      let name = (2..)
+        .map(|n| format!("{} ({})", request.name, n))
+        .find(|name| !seen.contains(name))
+        .unwrap_or_default();
+      seen.insert(name.clone());
+      request.name = name;
+    }
+  }
+}
+
+// Folders are flattened, `Folder / Request`; `auth` is inherited from the closest parent
+fn postman_items(
+  items: &Value,
+  prefix: &str,
+  auth: Option<&Value>,
+  out: &mut Vec<SavedRequest>,
+) -> Result<()> {
+  for item in items.as_array().into_iter().flatten() {
+    let name = format!("{}{}", prefix, str_at(item, "name"));
+    let auth = item.get("auth").or(auth);
+    if item.get("item").is_some() {
+      postman_items(&item["item"], &format!("{} / ", name), auth, out)?;
+      continue;
+    }
+    let request = &item["request"];
+    // a request may be a bare URL string
+    if let Some(url) = request.as_str() {
+      out.push(SavedRequest {
+        name,
+        request: HttpRequest {
+          method: "GET".into(),
+          url: url.into(),
+          headers: vec![],
+          body: None,
+        },
+      });
+      continue;
+    }
+    let mut headers: Vec<Header> = request["header"]
+      .as_array()
+      .into_iter()
+      .flatten()
+      .filter(|header| !header["disabled"].as_bool().unwrap_or(false))
+      .map(|header| Header {
+        name: str_at(header, "key"),
+        value: str_at(header, "value"),
+      })
+      .collect();
+    if let Some(header) = postman_auth(request.get("auth").or(auth)) {
+      headers.push(header);
+    }
+    let body = postman_body(&request["body"], &mut headers);
+    let method = request["method"].as_str().unwrap_or("GET").to_uppercase();
+    out.push(SavedRequest {
+      name,
+      request: HttpRequest {
+        method,
+        url: postman_url(&request["url"]),
+        headers,
+        body,
+      },
+    });
+  }
+  Ok(())
+}
+
+fn postman_url(url: &Value) -> String {
+  if let Some(raw) = url.as_str().or_else(|| url["raw"].as_str()) {
+    return raw.to_string();
+  }
+  let join = |value: &Value, sep: &str| {
+    value
+      .as_array()
+      .into_iter()
+      .flatten()
+      .filter_map(Value::as_str)
+      .collect::<Vec<_>>()
+      .join(sep)
+  };
+  let mut out = match url["protocol"].as_str() {
+    Some(protocol) => format!("{}://", protocol),
+    None => String::new(),
+  };
+  out.push_str(&join(&url["host"], "."));
+  if let Some(port) = url["port"].as_str() {
+    out.push_str(&format!(":{}", port));
+  }
+  let path = join(&url["path"], "/");
+  if !path.is_empty() {
+    out.push('/');
+    out.push_str(&path);
+  }
+  out
+}
+
+fn postman_auth(auth: Option<&Value>) -> Option<Header> {
+  let auth = auth?;
+  let param = |kind: &str, key: &str| {
+    auth[kind]
+      .as_array()
+      .into_iter()
+      .flatten()
+      .find(|p| p["key"].as_str() == Some(key))
+      .map(|p| str_at(p, "value"))
+      .unwrap_or_default()
+  };
+  match auth["type"].as_str()? {
+    "bearer" => Some(Header {
+      name: "Authorization".into(),
+      value: format!("Bearer {}", param("bearer", "token")),
+    }),
+    "basic" => Some(Header {
+      name: "Authorization".into(),
+      value: format!(
+        "Basic {}",
+        STANDARD.encode(format!(
+          "{}:{}",
+          param("basic", "username"),
+          param("basic", "password")
+        ))
+      ),
+    }),
+    "apikey" if param("apikey", "in") != "query" => Some(Header {
+      name: param("apikey", "key"),
+      value: param("apikey", "value"),
+    }),
+    _ => None,
+  }
+}
+
+// raw, urlencoded and graphql bodies, other modes (files, form-data) are dropped
+fn postman_body(body: &Value, headers: &mut Vec<Header>) -> Option<String> {
+  let mut content_type = |value: &str| {
+    if !headers
+      .iter()
+      .any(|h| h.name.eq_ignore_ascii_case("Content-Type"))
+    {
+      headers.push(Header {
+        name: "Content-Type".into(),
+        value: value.into(),
+      });
+    }
+  };
+  match body["mode"].as_str()? {
+    "raw" => {
+      if body
+        .pointer("/options/raw/language")
+        .and_then(Value::as_str)
+        == Some("json")
+      {
+        content_type("application/json");
+      }
+      body["raw"].as_str().map(String::from)
+    }
+    "urlencoded" => {
+      content_type("application/x-www-form-urlencoded");
+      Some(
+        body["urlencoded"]
+          .as_array()
+          .into_iter()
+          .flatten()
+          .filter(|field| !field["disabled"].as_bool().unwrap_or(false))
+          .map(|field| {
+            format!(
+              "{}={}",
+              url_encode(&str_at(field, "key")),
+              url_encode(&str_at(field, "value"))
+            )
+          })
+          .collect::<Vec<_>>()
+          .join("&"),
+      )
+    }
+    "graphql" => {
+      content_type("application/json");
+      let variables = body.pointer("/graphql/variables").and_then(Value::as_str);
+      let variables: Value = variables
+        .and_then(|v| serde_json::from_str(v).ok())
+        .unwrap_or(Value::Null);
+      Some(
+        serde_json::json!({
+          "query": body.pointer("/graphql/query").and_then(Value::as_str).unwrap_or_default(),
+          "variables": variables,
+        })
+        .to_string(),
+      )
+    }
+    _ => None,
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use serde_json::json;
+
+  fn header<'a>(request: &'a HttpRequest, name: &str) -> Option<&'a str> {
+    request
+      .headers
+      .iter()
+      .find(|h| h.name.eq_ignore_ascii_case(name))
+      .map(|h| h.value.as_str())
+  }
+
+  #[test]
+  fn splits_shell_words() {
+    let words =
+      shell_words("curl 'a b' \"c \\\"d\\\" \\$e\" $'f\\n\\x41\\u00e9' g\\ h \\\n  i''j").unwrap();
+    assert_eq!(words, ["curl", "a b", "c \"d\" $e", "f\nAé", "g h", "ij"]);
+    assert!(shell_words("curl 'open").is_err());
+    assert!(shell_words("curl \"open").is_err());
+  }
+
+  #[test]
+  fn curl_method_headers_and_body() {
+    let request = parse_curl(
+      "curl -XPUT 'https://api.example.com/items/1?x=1' -H 'X-Id:  7' --user a:b -b 's=1' \
+       --data-raw '{\"a\":1}' -o out.json --compressed",
+    )
+    .unwrap();
+    assert_eq!(request.method, "PUT");
+    assert_eq!(request.url, "https://api.example.com/items/1?x=1");
+    assert_eq!(header(&request, "x-id"), Some("7"));
+    assert_eq!(header(&request, "Authorization"), Some("Basic YTpi"));
+    assert_eq!(header(&request, "Cookie"), Some("s=1"));
+    assert_eq!(
+      header(&request, "Content-Type"),
+      Some("application/x-www-form-urlencoded")
+    );
+    assert_eq!(request.body.as_deref(), Some("{\"a\":1}"));
+    assert_eq!(default_name(&request), "PUT /items/1");
+
+    let request = parse_curl("curl --json '{}' --url=https://example.com").unwrap();
+    assert_eq!(request.method, "POST");
+    assert_eq!(header(&request, "Accept"), Some("application/json"));
+    assert!(parse_curl("wget https://example.com").is_err());
+    assert!(parse_curl("curl -H").is_err());
+    assert!(parse_curl("curl -F a=b https://example.com").is_err());
+  }
+
+  #[test]
+  fn curl_data_from_files_is_refused() {
+    assert!(parse_curl("curl -d @body.json https://example.com").is_err());
+    assert!(parse_curl("curl --json @- https://example.com").is_err());
+    assert!(parse_curl("curl --data-urlencode q@query.txt https://example.com").is_err());
+    let request = parse_curl("curl --data-raw @literal https://example.com").unwrap();
+    assert_eq!(request.body.as_deref(), Some("@literal"));
+  }
+
+  #[test]
+  fn curl_flags_with_multibyte_characters() {
+    let request = parse_curl("curl -é -H'X-Name: é' https://example.com").unwrap();
+    assert_eq!(request.url, "https://example.com");
+    assert_eq!(header(&request, "x-name"), Some("é"));
+  }
+
+  #[test]
+  fn curl_url_encoded_data() {
+    let request = parse_curl(
+      "curl -G https://example.com/s?a=1 --data-urlencode 'q=a b&c' --data-urlencode '=x/y' \
+       --data-urlencode 'é' -d n=2",
+    )
+    .unwrap();
+    assert_eq!(request.method, "GET");
+    assert_eq!(request.body, None);
+    assert_eq!(
+      request.url,
+      "https://example.com/s?a=1&q=a%20b%26c&x%2Fy&%C3%A9&n=2"
+    );
+  }
+
+  #[test]
+  fn postman_folders_auth_and_variables() {
+    let collection = json!({
+      "info": { "schema": "https://schema.getpostman.com/json/collection/v2.1.0/collection.json" },
+      "variable": [{ "key": "base", "value": "https://example.com" }, { "key": "n", "value": 3 }],
+      "auth": { "type": "bearer", "bearer": [{ "key": "token", "value": "{{token}}" }] },
+      "item": [
+        { "name": "Users", "item": [
+          { "name": "List", "request": { "method": "get", "url": "{{base}}/users" } },
+          { "name": "List", "request": {
+            "method": "GET",
+            "url": { "protocol": "https", "host": ["example", "com"], "path": ["users"] },
+            "header": [{ "key": "X-Off", "value": "1", "disabled": true }]
+          } },
+          { "name": "Admin", "auth": { "type": "basic", "basic": [
+              { "key": "username", "value": "a" }, { "key": "password", "value": "b" }
+            ] }, "item": [
+            { "name": "Create", "request": {
+              "method": "POST",
+              "url": "{{base}}/admin",
+              "body": { "mode": "raw", "raw": "{}", "options": { "raw": { "language": "json" } } }
+            } }
+          ] }
+        ] },
+        { "name": "Health", "request": "https://example.com/health" }
+      ]
+    });
+    let imported = parse_postman(&collection.to_string()).unwrap();
+    assert_eq!(imported.variables["base"], "https://example.com");
+    assert_eq!(imported.variables["n"], "3");
+    let names: Vec<&str> = imported.requests.iter().map(|r| r.name.as_str()).collect();
+    assert_eq!(
+      names,
+      [
+        "Users / List",
+        "Users / List (2)",
+        "Users / Admin / Create",
+        "Health"
+      ]
+    );
+    let [list, list2, create, health] = &imported.requests[..] else {
+      panic!("4 requests expected");
+    };
+    assert_eq!(list.request.method, "GET");
+    assert_eq!(
+      header(&list.request, "Authorization"),
+      Some("Bearer {{token}}")
+    );
+    assert_eq!(list2.request.url, "https://example.com/users");
+    assert_eq!(header(&list2.request, "X-Off"), None);
+    assert_eq!(header(&create.request, "Authorization"), Some("Basic YTpi"));
+    assert_eq!(
+      header(&create.request, "Content-Type"),
+      Some("application/json")
+    );
+    assert_eq!(health.request.headers.len(), 0);
+
+    assert!(parse_postman(r#"{"info": {"schema": "v1"}}"#).is_err());
+  }
+
+  #[test]
+  fn unique_names_skip_taken_suffixes() {
+    let request = || HttpRequest {
+      method: "GET".into(),
+      url: "/".into(),
+      headers: vec![],
+      body: None,
+    };
+    let mut requests: Vec<SavedRequest> = ["A", "A (2)", "A", "A"]
+      .into_iter()
+      .map(|name| SavedRequest {
+        name: name.into(),
+        request: request(),
+      })
+      .collect();
+    unique_names(&mut requests);
+    let names: Vec<&str> = requests.iter().map(|r| r.name.as_str()).collect();
+    assert_eq!(names, ["A", "A (2)", "A (3)", "A (4)"]);
+  }
+
+  #[test]
+  fn har_entries() {
+    let har = json!({ "log": { "entries": [{
+      "startedDateTime": "2024-01-01T00:00:00Z",
+      "time": 12.5,
+      "request": {
+        "method": "POST",
+        "url": "https://example.com/a",
+        "headers": [
+          { "name": ":authority", "value": "example.com" },
+          { "name": "Accept", "value": "*/*" }
+        ],
+        "postData": { "text": "x=1" }
+      },
+      "response": {
+        "status": 201,
+        "statusText": "Created",
+        "headers": [],
+        "content": { "text": "eyJvayI6dHJ1ZX0=", "encoding": "base64" }
+      }
+    }] } });
+    let entries = parse_har(&har.to_string()).unwrap();
+    let entry = &entries[0];
+    assert_eq!(entry.request.headers.len(), 1);
+    assert_eq!(entry.request.body.as_deref(), Some("x=1"));
+    assert_eq!(entry.response.status, 201);
+    assert_eq!(entry.response.elapsed, 12);
+    assert_eq!(entry.response.json, Some(json!({ "ok": true })));
+    assert!(parse_har("{}").is_err());
+  }
+
+  #[test]
+  fn har_bodies_that_are_not_text() {
+    let entry = |content: Value| json!({ "request": {}, "response": { "content": content } });
+    let har = json!({ "log": { "entries": [
+      entry(json!({ "text": "not base64!", "encoding": "base64" })),
+      entry(json!({ "text": "iVBORw0KGgo=", "encoding": "base64", "mimeType": "image/png", "size": 8 })),
+      entry(json!({ "text": "[1]", "mimeType": "application/json" })),
+    ] } });
+    let entries = parse_har(&har.to_string()).unwrap();
+    assert!(entries[0]
+      .skipped_body
+      .as_deref()
+      .unwrap()
+      .contains("base64"));
+    assert_eq!(
+      entries[1].skipped_body.as_deref(),
+      Some("8 bytes of image/png left out")
+    );
+    assert_eq!(
+      (entries[1].response.body.as_str(), entries[1].response.size),
+      ("", 8)
+    );
+    assert_eq!(entries[2].skipped_body, None);
+    assert_eq!(entries[2].response.json, Some(json!([1])));
+  }
+}
@@ -0,0 +1,610 @@
+use anyhow::{anyhow, bail, Result};
+use serde_json::Value;
+use std::{cmp::Ordering, iter::Peekable, str::Chars};
+
+use super::{escape_token, transform::compare_values};
+
+// JSONPath subset: $ . .. * [n] [-n] [a:b:c] ['a','b'] [?(@.x op literal && ...)]
+#[derive(Debug, Clone)]
+pub struct JsonPath {
+  steps: Vec<Step>,
+}
+
+#[derive(Debug, Clone)]
+enum Step {
+  Child(Selector),
+  Descendant(Selector),
+}
+
+#[derive(Debug, Clone)]
+enum Selector {
+  Name(String),
+  Wildcard,
+  Index(i64),
+  Slice(Option<i64>, Option<i64>, Option<i64>),
+  Union(Vec<Selector>),
+  Filter(Box<Expr>),
+}
+
+#[derive(Debug, Clone)]
+enum Expr {
+  Or(Box<Expr>, Box<Expr>),
+  And(Box<Expr>, Box<Expr>),
+  Not(Box<Expr>),
+  Exists(Operand),
+  Compare(Operand, CmpOp, Operand),
+}
+
+#[derive(Debug, Clone)]
+enum Operand {
+  // @... relative to the current item, $... from the document root
+  Current(Vec<Step>),
+  Root(Vec<Step>),
+  Literal(Value),
+}
+
+#[derive(Debug, Clone, Copy)]
+enum CmpOp {
+  Eq,
+  Ne,
+  Lt,
+  Le,
+  Gt,
+  Ge,
+}
+
+impl JsonPath {
+  pub fn parse(path: &str) -> Result<Self> {
+    let mut chars = path.trim().chars().peekable();
+    if chars.next() != Some('$') {
+      bail!("JSONPath must start with `$`");
+    }
+    let steps = parse_steps(&mut chars)?;
+    if let Some(c) = chars.next() {
+      bail!("unexpected `{}` in JSONPath", c);
+    }
+    Ok(JsonPath { steps })
+  }
+
+  // Matches as (JSON Pointer, value) in document order
+  pub fn select<'a>(&self, root: &'a Value) -> Vec<(String, &'a Value)> {
+    eval(&self.steps, root, vec![(String::new(), root)])
+  }
+}
+
+// --- Parser
+
+fn parse_steps(chars: &mut Peekable<Chars>) -> Result<Vec<Step>> {
+  let mut steps = vec![];
+  loop {
+    match chars.peek() {
+      Some('.') => {
+        chars.next();
+        let descendant = chars.peek() == Some(&'.');
+        if descendant {
+          chars.next();
+        }
+        let selector = match chars.peek() {
+          Some('[') if descendant => {
+            chars.next();
+            parse_bracket(chars)?
+          }
+          Some('*') => {
+            chars.next();
+            Selector::Wildcard
+          }
+          _ => Selector::Name(parse_name(chars)?),
+        };
+        steps.push(if descendant {
+          Step::Descendant(selector)
+        } else {
+          Step::Child(selector)
+        });
+      }
+      Some('[') => {
+        chars.next();
+        steps.push(Step::Child(parse_bracket(chars)?));
+      }
+      _ => return Ok(steps),
+    }
+  }
+}
+
+fn parse_name(chars: &mut Peekable<Chars>) -> Result<String> {
+  let mut name = String::new();
+  while let Some(&c) = chars.peek() {
+    if c.is_alphanumeric() || c == '_' || c == '-' || c == '$' {
+      name.push(c);
+      chars.next();
+    } else {
+      break;
+    }
+  }
+  if name.is_empty() {
+    bail!("expected a member name in JSONPath");
+  }
+  Ok(name)
+}
+
+fn skip_ws(chars: &mut Peekable<Chars>) {
+  while chars.peek().is_some_and(|c| c.is_whitespace()) {
+    chars.next();
+  }
+}
+
+fn expect(chars: &mut Peekable<Chars>, expected: char) -> Result<()> {
+  skip_ws(chars);
+  match chars.next() {
+    Some(c) if c == expected => Ok(()),
+    Some(c) => bail!("expected `{}` but found `{}` in JSONPath", expected, c),
+    None => bail!("expected `{}` in JSONPath", expected),
+  }
+}
+
+// after `[`
+fn parse_bracket(chars: &mut Peekable<Chars>) -> Result<Selector> {
+  skip_ws(chars);
+  let selector = match chars.peek() {
+    Some('*') => {
+      chars.next();
+      Selector::Wildcard
+    }
+    Some('?') => {
+      chars.next();
+      expect(chars, '(')?;
+      let expr = parse_or(chars)?;
+      expect(chars, ')')?;
+      Selector::Filter(Box::new(expr))
+    }
+    _ => {
+      let mut items = vec![parse_union_item(chars)?];
+      skip_ws(chars);
+      while chars.peek() == Some(&',') {
+        chars.next();
+        items.push(parse_union_item(chars)?);
+        skip_ws(chars);
+      }
+      if items.len() == 1 {
+        items.remove(0)
+      } else {
+        Selector::Union(items)
+      }
+    }
+  };
+  expect(chars, ']')?;
+  Ok(selector)
+}
+
+fn parse_union_item(chars: &mut Peekable<Chars>) -> Result<Selector> {
+  skip_ws(chars);
+  match chars.peek() {
+    Some('\'') | Some('"') => Ok(Selector::Name(parse_string(chars)?)),
+    _ => {
+      let start = parse_int(chars)?;
+      skip_ws(chars);
+      if chars.peek() != Some(&':') {
+        return start
+          .map(Selector::Index)
+          .ok_or_else(|| anyhow!("expected an index in JSONPath"));
+      }
+      chars.next();
+      let end = parse_int(chars)?;
+      skip_ws(chars);
+      let step = if chars.peek() == Some(&':') {
+        chars.next();
+        parse_int(chars)?
+      } else {
+        None
+      };
+      Ok(Selector::Slice(start, end, step))
+    }
+  }
+}
+
+fn parse_int(chars: &mut Peekable<Chars>) -> Result<Option<i64>> {
+  skip_ws(chars);
+  let mut text = String::new();
+  while let Some(&c) = chars.peek() {
+    if c.is_ascii_digit() || (c == '-' && text.is_empty()) {
+      text.push(c);
+      chars.next();
+    } else {
+      break;
+    }
+  }
+  if text.is_empty() {
+    return Ok(None);
+  }
+  Ok(Some(text.parse().map_err(|_| {
+    anyhow!("invalid number `{}` in JSONPath", text)
+  })?))
+}
+
+// 'single' or "double" quoted
+fn parse_string(chars: &mut Peekable<Chars>) -> Result<String> {
+  let quote = chars.next().unwrap_or('"');
+  let mut out = String::new();
+  loop {
+    match chars.next() {
+      Some('\\') => match chars.next() {
+        Some('n') => out.push('\n'),
+        Some('t') => out.push('\t'),
+        Some(c) => out.push(c),
+        None => break,
+      },
+      Some(c) if c == quote => return Ok(out),
+      Some(c) => out.push(c),
+      None => break,
+    }
+  }
+  bail!("unterminated string in JSONPath")
+}
+
+fn parse_or(chars: &mut Peekable<Chars>) -> Result<Expr> {
+  let mut left = parse_and(chars)?;
+  loop {
+    skip_ws(chars);
+    if !eat(chars, "||") {
+      return Ok(left);
+    }
+    left = Expr::Or(Box::new(left), Box::new(parse_and(chars)?));
+  }
+}
+
+fn parse_and(chars: &mut Peekable<Chars>) -> Result<Expr> {
+  let mut left = parse_unary(chars)?;
+  loop {
+    skip_ws(chars);
+    if !eat(chars, "&&") {
+      return Ok(left);
+    }
+    left = Expr::And(Box::new(left), Box::new(parse_unary(chars)?));
+  }
+}
+
+fn parse_unary(chars: &mut Peekable<Chars>) -> Result<Expr> {
+  skip_ws(chars);
+  match chars.peek() {
+    Some('!') => {
+      chars.next();
+      Ok(Expr::Not(Box::new(parse_unary(chars)?)))
+    }
+    Some('(') => {
+      chars.next();
+      let expr = parse_or(chars)?;
+      expect(chars, ')')?;
+      Ok(expr)
+    }
+    _ => {
+      let left = parse_operand(chars)?;
+      skip_ws(chars);
+      let op = if eat(chars, "==") {
+        CmpOp::Eq
+      } else if eat(chars, "!=") {
+        CmpOp::Ne
+      } else if eat(chars, "<=") {
+        CmpOp::Le
+      } else if eat(chars, ">=") {
+        CmpOp::Ge
+      } else if eat(chars, "<") {
+        CmpOp::Lt
+      } else if eat(chars, ">") {
+        CmpOp::Gt
+      } else {
+        return Ok(Expr::Exists(left));
+      };
+      Ok(Expr::Compare(left, op, parse_operand(chars)?))
+    }
+  }
+}
+
+fn parse_operand(chars: &mut Peekable<Chars>) -> Result<Operand> {
+  skip_ws(chars);
+  match chars.peek() {
+    Some('@') => {
+      chars.next();
+      Ok(Operand::Current(parse_steps(chars)?))
+    }
+    Some('$') => {
+      chars.next();
+      Ok(Operand::Root(parse_steps(chars)?))
+    }
+    Some('\'') | Some('"') => Ok(Operand::Literal(Value::String(parse_string(chars)?))),
+    _ => {
+      let mut text = String::new();
+      while let Some(&c) = chars.peek() {
+        if c.is_alphanumeric() || matches!(c, '-' | '+' | '.') {
+          text.push(c);
+          chars.next();
+        } else {
+          break;
+        }
+      }
+      let value = serde_json::from_str(&text)
+        .map_err(|_| anyhow!("invalid literal `{}` in JSONPath filter", text))?;
+      Ok(Operand::Literal(value))
+    }
+  }
+}
+
+fn eat(chars: &mut Peekable<Chars>, token: &str) -> bool {
+  let rest: String = chars.clone().take(token.len()).collect();
+  if rest == token {
+    for _ in 0..token.len() {
+      chars.next();
+    }
+    true
+  } else {
+    false
+  }
+}
+
+// --- Evaluation
+
+type Nodes<'a> = Vec<(String, &'a Value)>;
+
+fn eval<'a>(steps: &[Step], root: &'a Value, mut nodes: Nodes<'a>) -> Nodes<'a> {
+  for step in steps {
+    let mut next = vec![];
+    for (pointer, value) in nodes {
+      match step {
+        Step::Child(selector) => apply(selector, root, &pointer, value, &mut next),
+        Step::Descendant(selector) => {
+          let mut stack = vec![(pointer, value)];
+          while let Some((pointer, value)) = stack.pop() {
+            apply(selector, root, &pointer, value, &mut next);
+            let mut children = children(&pointer, value);
+            children.reverse();
+            stack.extend(children);
+          }
+        }
+      }
+    }
+    nodes = next;
+  }
+  nodes
+}
+
+fn children<'a>(pointer: &str, value: &'a Value) -> Nodes<'a> {
+  match value {
+    Value::Object(map) => map
+      .iter()
+      .map(|(k, v)| (format!("{}/{}", pointer, escape_token(k)), v))
+      .collect(),
+    Value::Array(arr) => arr
+      .iter()
+      .enumerate()
+      .map(|(i, v)| (format!("{}/{}", pointer, i), v))
+      .collect(),
+    _ => vec![],
+  }
+}
+
+fn apply<'a>(
+  selector: &Selector,
+  root: &'a Value,
+  pointer: &str,
+  value: &'a Value,
+  out: &mut Nodes<'a>,
+) {
+  match selector {
+    Selector::Name(name) => {
+      if let Some(v) = value.get(name) {
+        out.push((format!("{}/{}", pointer, escape_token(name)), v));
+      }
+    }
+    Selector::Wildcard => out.extend(children(pointer, value)),
+    Selector::Index(i) => {
+      if let Value::Array(arr) = value {
+        let i = if *i < 0 { arr.len() as i64 + i } else { *i };
+        if let Some(v) = usize::try_from(i).ok().and_then(|i| arr.get(i)) {
+          out.push((format!("{}/{}", pointer, i), v));
+        }
+      }
+    }
+    Selector::Slice(start, end, step) => {
+      if let Value::Array(arr) = value {
+        let len = arr.len() as i64;
+        let norm = |i: i64| if i < 0 { (len + i).max(0) } else { i.min(len) };
+        let step = step.unwrap_or(1);
+        if step > 0 {
+          let mut i = norm(start.unwrap_or(0));
+          let end = norm(end.unwrap_or(len));
+          while i < end {
+            out.push((format!("{}/{}", pointer, i), &arr[i as usize]));
+            // a huge step jumps past the end instead of wrapping around
+            let Some(next) = i.checked_add(step) else {
+              break;
+            };
+            i = next;
+          }
+        } else if step < 0 {
+          // bounds run down to -1 so that index 0 can still be selected
+          let norm = |i: i64| {
+            if i < 0 {
+              (len + i).max(-1)
+            } else {
+              i.min(len - 1)
+            }
+          };
+          let mut i = start.map_or(len - 1, norm);
+          let end = end.map_or(-1, norm);
+          while i > end {
+            out.push((format!("{}/{}", pointer, i), &arr[i as usize]));
+            // a huge step jumps past the end instead of wrapping around
+            let Some(next) = i.checked_add(step) else {
+              break;
+            };
+            i = next;
+          }
+        }
+      }
+    }
+    Selector::Union(items) => {
+      for item in items {
+        apply(item, root, pointer, value, out);
+      }
+    }
+    Selector::Filter(expr) => {
+      for (p, v) in children(pointer, value) {
+        if test(expr, root, v) {
+          out.push((p, v));
+        }
+      }
+    }
+  }
+}
+
+fn resolve<'a>(operand: &'a Operand, root: &'a Value, current: &'a Value) -> Option<&'a Value> {
+  match operand {
+    Operand::Current(steps) => eval(steps, root, vec![(String::new(), current)])
+      .first()
+      .map(|(_, v)| *v),
+    Operand::Root(steps) => eval(steps, root, vec![(String::new(), root)])
+      .first()
+      .map(|(_, v)| *v),
+    Operand::Literal(v) => Some(v),
+  }
+}
+
+fn test(expr: &Expr, root: &Value, current: &Value) -> bool {
+  match expr {
+    Expr::Or(a, b) => test(a, root, current) || test(b, root, current),
+    Expr::And(a, b) => test(a, root, current) && test(b, root, current),
+    Expr::Not(a) => !test(a, root, current),
+    Expr::Exists(operand) => resolve(operand, root, current).is_some(),
+    Expr::Compare(left, op, right) => {
+      let (left, right) = match (resolve(left, root, current), resolve(right, root, current)) {
+        (Some(l), Some(r)) => (l, r),
+        _ => return false,
+      };
+      let comparable = std::mem::discriminant(left) == std::mem::discriminant(right);
+      let ord = compare_values(left, right);
+      // numbers compare by value, `1 == 1.0`
+      let equal = match (left, right) {
+        (Value::Number(_), Value::Number(_)) => ord == Ordering::Equal,
+        _ => left == right,
+      };
+      match op {
+        CmpOp::Eq => equal,
+        CmpOp::Ne => !equal,
+        CmpOp::Lt => comparable && ord == Ordering::Less,
+        CmpOp::Le => comparable && ord != Ordering::Greater,
+        CmpOp::Gt => comparable && ord == Ordering::Greater,
+        CmpOp::Ge => comparable && ord != Ordering::Less,
+      }
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use serde_json::json;
+
+  fn select(path: &str, doc: &Value) -> Vec<String> {
+    let path = JsonPath::parse(path).unwrap();
+    path.select(doc).into_iter().map(|(p, _)| p).collect()
+  }
+
+  #[test]
+  fn parses_and_rejects() {
+    assert!(JsonPath::parse("$.a['b c'][0, -1]..d[*]").is_ok());
+    assert!(JsonPath::parse("a.b").is_err());
+    assert!(JsonPath::parse("$.a[").is_err());
+    assert!(JsonPath::parse("$[?(@.a == )]").is_err());
+    assert!(JsonPath::parse("$.a b").is_err());
+  }
+
+  #[test]
+  fn names_indexes_and_unions() {
+    let doc = json!({ "a/b": [1, 2, 3], "c": { "d": 4 } });
+    assert_eq!(select("$['a/b'][0]", &doc), ["/a~1b/0"]);
+    assert_eq!(select("$['a/b'][-1]", &doc), ["/a~1b/2"]);
+    assert!(select("$['a/b'][3]", &doc).is_empty());
+    assert!(select("$['a/b'][-4]", &doc).is_empty());
+    assert_eq!(select("$['c', 'a/b']", &doc), ["/c", "/a~1b"]);
+    assert_eq!(select("$.*", &doc), ["/a~1b", "/c"]);
+  }
+
+  #[test]
+  fn slices() {
+    let doc = json!([0, 1, 2, 3, 4]);
+    assert_eq!(select("$[1:3]", &doc), ["/1", "/2"]);
+    assert_eq!(select("$[:2]", &doc), ["/0", "/1"]);
+    assert_eq!(select("$[-2:]", &doc), ["/3", "/4"]);
+    assert_eq!(select("$[::2]", &doc), ["/0", "/2", "/4"]);
+    assert_eq!(select("$[1:10]", &doc), ["/1", "/2", "/3", "/4"]);
+    assert!(select("$[3:1]", &doc).is_empty());
+    assert!(select("$[::0]", &doc).is_empty());
+  }
+
+  #[test]
+  fn slices_with_extreme_steps() {
+    let doc = json!([0, 1, 2, 3, 4]);
+    assert_eq!(select("$[1::9223372036854775807]", &doc), ["/1"]);
+    assert_eq!(select("$[4::9223372036854775807]", &doc), ["/4"]);
+    assert_eq!(select("$[::-9223372036854775808]", &doc), ["/4"]);
+    assert_eq!(
+      select("$[-9223372036854775808:9223372036854775807]", &doc),
+      ["/0", "/1", "/2", "/3", "/4"]
+    );
+  }
+
+  #[test]
+  fn negative_step_slices() {
+    let doc = json!([0, 1, 2, 3, 4]);
+    assert_eq!(select("$[::-1]", &doc), ["/4", "/3", "/2", "/1", "/0"]);
+    assert_eq!(select("$[3:1:-1]", &doc), ["/3", "/2"]);
+    assert_eq!(select("$[2:-10:-1]", &doc), ["/2", "/1", "/0"]);
+    assert_eq!(select("$[10::-2]", &doc), ["/4", "/2", "/0"]);
+    assert!(select("$[-10::-1]", &doc).is_empty());
+    assert!(select("$[1:3:-1]", &doc).is_empty());
+  }
+
+  #[test]
+  fn filters() {
+    let doc = json!({ "items": [
+      { "id": 1, "tag": "a", "price": 10.5 },
+      { "id": 2.0, "tag": "b" },
+      { "id": 3, "tag": "a", "price": 3 }
+    ], "max": 5 });
+    assert_eq!(
+      select("$.items[?(@.tag == 'a')]", &doc),
+      ["/items/0", "/items/2"]
+    );
+    assert_eq!(select("$.items[?(@.id == 2)]", &doc), ["/items/1"]);
+    assert_eq!(select("$.items[?(@.id == 1.0)]", &doc), ["/items/0"]);
+    assert_eq!(
+      select("$.items[?(@.id != 1)].id", &doc),
+      ["/items/1/id", "/items/2/id"]
+    );
+    assert_eq!(
+      select("$.items[?(@.price)]", &doc),
+      ["/items/0", "/items/2"]
+    );
+    assert_eq!(select("$.items[?(!@.price)]", &doc), ["/items/1"]);
+    assert_eq!(select("$.items[?(@.price < $.max)]", &doc), ["/items/2"]);
+    assert_eq!(
+      select(
+        "$.items[?(@.tag == 'a' && @.price >= 10 || @.id == 2)]",
+        &doc
+      ),
+      ["/items/0", "/items/1"]
+    );
+    // different types are never ordered
+    assert!(select("$.items[?(@.tag > 1)]", &doc).is_empty());
+  }
+
+  #[test]
+  fn recursive_descent() {
+    let doc = json!({ "id": 1, "a": { "id": 2, "b": [{ "id": 3 }, { "x": { "id": 4 } }] } });
+    assert_eq!(
+      select("$..id", &doc),
+      ["/id", "/a/id", "/a/b/0/id", "/a/b/1/x/id"]
+    );
+    assert_eq!(select("$.a..[0]", &doc), ["/a/b/0"]);
+    assert_eq!(select("$..b[*].x", &doc), ["/a/b/1/x"]);
+    assert_eq!(select("$..[?(@.id > 2)]", &doc), ["/a/b/0", "/a/b/1/x"]);
+  }
+}
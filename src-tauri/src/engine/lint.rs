@@ -0,0 +1,548 @@
+use anyhow::{anyhow, Result};
+use jsonschema::JSONSchema;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::{
+  collections::HashMap,
+  fs,
+  path::{Path, PathBuf},
+};
+
+use super::{
+  files::{self, Source},
+  format,
+  spans::{self, Position},
+  unescape_token,
+  workspace::{SchemaAssociation, WorkspaceSettings},
+};
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Severity {
+  Error,
+  Warning,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum Rule {
+  // the file is not valid JSON
+  Parse,
+  DuplicateKey,
+  // the text differs from what the workspace `format` settings produce
+  Format,
+  Schema,
+}
+
+impl Rule {
+  const ALL: [Rule; 4] = [Rule::Parse, Rule::DuplicateKey, Rule::Format, Rule::Schema];
+
+  pub fn id(&self) -> &'static str {
+    match self {
+      Rule::Parse => "parse",
+      Rule::DuplicateKey => "duplicate-key",
+      Rule::Format => "format",
+      Rule::Schema => "schema",
+    }
+  }
+
+  fn description(&self) -> &'static str {
+    match self {
+      Rule::Parse => "File must be valid JSON",
+      Rule::DuplicateKey => "Object keys must be unique",
+      Rule::Format => "File must match the workspace format settings",
+      Rule::Schema => "Document must match its associated JSON Schema",
+    }
+  }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Problem {
+  pub rule: Rule,
+  pub severity: Severity,
+  pub message: String,
+  pub pointer: Option<String>,
+  // 1-based, in the file (not the `.jsonl` line)
+  pub line: usize,
+  pub column: usize,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct FileReport {
+  pub path: String,
+  pub problems: Vec<Problem>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct LintReport {
+  pub root: String,
+  // number of checked files, `reports` only lists the ones with problems
+  pub files: usize,
+  pub errors: usize,
+  pub warnings: usize,
+  pub reports: Vec<FileReport>,
+}
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ReportFormat {
+  Json,
+  Sarif,
+}
+
+// Check every JSON file below `root` against the `.jsoneditor.json` settings of `root`
+pub fn lint_folder(root: &Path, max_size: u64) -> Result<LintReport> {
+  let settings = WorkspaceSettings::load(root)?;
+  let mut schemas = Schemas::default();
+  let mut report = LintReport {
+    root: root.to_string_lossy().into(),
+    ..Default::default()
+  };
+  for path in files::walk_json_files(root, max_size) {
+    let problems = lint_file(root, &path, &settings, &mut schemas);
+    report.add(&path, problems);
+  }
+  Ok(report)
+}
+
+// Check files and folders given on the command line, each with the settings of its folder.
+// `schema` (a path or URL) replaces the schema associations of those settings.
+pub fn lint_paths(
+  base: &Path,
+  paths: &[PathBuf],
+  schema: Option<&str>,
+  max_size: u64,
+) -> Result<LintReport> {
+  let schema = schema.map(|s| {
+    if s.contains("://") {
+      s.to_string()
+    } else {
+      base.join(s).to_string_lossy().into()
+    }
+  });
+  let mut schemas = Schemas::default();
+  let mut report = LintReport {
+    root: base.to_string_lossy().into(),
+    ..Default::default()
+  };
+  for path in paths {
+    let root = if path.is_dir() {
+      path.as_path()
+    } else {
+      path.parent().unwrap_or(base)
+    };
+    let mut settings = WorkspaceSettings::load(root)?;
+    if let Some(schema) = &schema {
+      settings.schemas = vec![SchemaAssociation {
+        file_match: vec!["*".into()],
+        schema: schema.clone(),
+      }];
+    }
+    let files: Vec<PathBuf> = if path.is_dir() {
+      files::walk_json_files(root, max_size).collect()
+    } else {
+      vec![path.clone()]
+    };
+    for file in files {
+      let problems = lint_file(root, &file, &settings, &mut schemas);
+      report.add(&file, problems);
+    }
+  }
+  Ok(report)
+}
+
+impl LintReport {
+  fn add(&mut self, path: &Path, problems: Vec<Problem>) {
+    self.files += 1;
+    if problems.is_empty() {
+      return;
+    }
+    for problem in &problems {
+      match problem.severity {
+        Severity::Error => self.errors += 1,
+        Severity::Warning => self.warnings += 1,
+      }
+    }
+    self.reports.push(FileReport {
+      path: path.to_string_lossy().into(),
+      problems,
+    });
+  }
+}
+
+pub fn lint_file(
+  root: &Path,
+  path: &Path,
+  settings: &WorkspaceSettings,
+  schemas: &mut Schemas,
+) -> Vec<Problem> {
+  let sources = match files::sources(path, settings.allows_comments(root, path)) {
+    Ok(sources) => sources,
+    Err(e) => return vec![problem(Rule::Parse, e.to_string(), None, 1, 1)],
+  };
+  let schema = settings.schema_for(root, path);
+  let mut problems = vec![];
+  for source in &sources {
+    let value: Value = match serde_json::from_str(&source.text) {
+      Ok(value) => value,
+      Err(e) => {
+        // serde_json reports `line 1 column 12` inside the message as well
+        let message = e.to_string();
+        let message = message
+          .rsplit_once(" at line ")
+          .map_or(message.as_str(), |(m, _)| m)
+          .to_string();
+        problems.push(problem(
+          Rule::Parse,
+          message,
+          None,
+          source.line_offset + e.line(),
+          e.column().max(1),
+        ));
+        continue;
+      }
+    };
+    let nodes = spans::index(&source.text).unwrap_or_default();
+    let locate = |pointer: &str| {
+      nodes
+        .iter()
+        .find(|node| node.pointer == pointer)
+        .map(|node| node.key.unwrap_or(node.value))
+    };
+
+    for node in spans::duplicate_keys(&source.text).unwrap_or_default() {
+      let name = node.pointer.rsplit('/').next().map(unescape_token);
+      problems.push(at(
+        source,
+        Rule::DuplicateKey,
+        format!(
+          "duplicate key `{}`, only its last value is kept",
+          name.unwrap_or_default()
+        ),
+        node.pointer.clone(),
+        node.key.unwrap_or(node.value),
+      ));
+    }
+
+    if let Some(location) = &schema {
+      match schemas.get(location) {
+        Some(Ok(compiled)) => {
+          if let Err(errors) = compiled.validate(&value) {
+            for error in errors {
+              let pointer = error.instance_path.to_string();
+              let position = locate(&pointer).unwrap_or(Position {
+                offset: 0,
+                line: 1,
+                column: 1,
+              });
+              problems.push(at(
+                source,
+                Rule::Schema,
+                error.to_string(),
+                pointer,
+                position,
+              ));
+            }
+          }
+        }
+        Some(Err(message)) => problems.push(problem(Rule::Schema, message, None, 1, 1)),
+        None => (),
+      }
+    }
+  }
+
+  // `.jsonl` lines and commented `.jsonc` files have no canonical layout,
+  // documents with duplicate keys would not survive reformatting
+  let checks_format = !files::is_jsonl(path)
+    && problems
+      .iter()
+      .all(|p| !matches!(p.rule, Rule::Parse | Rule::DuplicateKey));
+  if checks_format {
+    if let Some(problem) = check_format(path, &settings.format) {
+      problems.push(problem);
+    }
+  }
+  problems.sort_by_key(|p| (p.line, p.column));
+  problems
+}
+
+fn check_format(path: &Path, options: &format::FormatOptions) -> Option<Problem> {
+  let text = fs::read_to_string(path).ok()?;
+  if files::strip_comments(&text) != text {
+    return None;
+  }
+  let value: Value = serde_json::from_str(&text).ok()?;
+  let expected = format::format(&value, options).ok()?;
+  if expected == text {
+    return None;
+  }
+  let line = text
+    .lines()
+    .zip(expected.lines())
+    .position(|(a, b)| a != b)
+    .unwrap_or_else(|| text.lines().count().min(expected.lines().count()))
+    + 1;
+  Some(problem(
+    Rule::Format,
+    "file is not formatted with the workspace settings".into(),
+    None,
+    line,
+    1,
+  ))
+}
+
+fn problem(
+  rule: Rule,
+  message: String,
+  pointer: Option<String>,
+  line: usize,
+  column: usize,
+) -> Problem {
+  Problem {
+    rule,
+    severity: match rule {
+      Rule::Format => Severity::Warning,
+      _ => Severity::Error,
+    },
+    message,
+    pointer,
+    line,
+    column,
+  }
+}
+
+// problem at a position of `source`, pointers of `.jsonl` lines get the line prefix
+fn at(
+  source: &Source,
+  rule: Rule,
+  message: String,
+  pointer: String,
+  position: Position,
+) -> Problem {
+  problem(
+    rule,
+    message,
+    Some(format!("{}{}", source.prefix, pointer)),
+    source.line_offset + position.line,
+    position.column,
+  )
+}
+
+// Compiled schemas by location, a schema that fails to load is only reported for the first file
+#[derive(Default)]
+pub struct Schemas(HashMap<String, SchemaState>);
+
+enum SchemaState {
+  Compiled(JSONSchema),
+  Failed(String),
+  Reported,
+}
+
+impl Schemas {
+  // `None` once the load error of `location` has been returned
+  fn get(&mut self, location: &str) -> Option<Result<&JSONSchema, String>> {
+    let state = self
+      .0
+      .entry(location.to_string())
+      .or_insert_with(|| match load_schema(location) {
+        Ok(schema) => SchemaState::Compiled(schema),
+        Err(e) => SchemaState::Failed(format!("schema {}: {}", location, e)),
+      });
+    if let SchemaState::Failed(message) = state {
+      let message = std::mem::take(message);
+      *state = SchemaState::Reported;
+      return Some(Err(message));
+    }
+    match state {
+      SchemaState::Compiled(schema) => Some(Ok(schema)),
+      _ => None,
+    }
+  }
+}
+
+fn load_schema(location: &str) -> Result<JSONSchema> {
+  let text = if location.contains("://") {
+    reqwest::blocking::get(location)?
+      .error_for_status()?
+      .text()?
+  } else {
+    files::strip_comments(&fs::read_to_string(location)?)
+  };
+  let schema: Value = serde_json::from_str(&text)?;
+  JSONSchema::compile(&schema).map_err(|e| anyhow!("{}", e))
+}
+
+pub fn export(report: &LintReport, format: ReportFormat, path: &Path) -> Result<()> {
+  fs::write(
+    path,
+    serde_json::to_string_pretty(&to_value(report, format)?)?,
+  )?;
+  Ok(())
+}
+
+pub fn to_value(report: &LintReport, format: ReportFormat) -> Result<Value> {
+  Ok(match format {
+    ReportFormat::Json => serde_json::to_value(report)?,
+    ReportFormat::Sarif => to_sarif(report),
+  })
+}
+
+// SARIF 2.1.0 log with one run, file locations are relative to the linted folder
+pub fn to_sarif(report: &LintReport) -> Value {
+  let root = Path::new(&report.root);
+  let rules: Vec<Value> = Rule::ALL
+    .iter()
+    .map(|rule| json!({ "id": rule.id(), "shortDescription": { "text": rule.description() } }))
+    .collect();
+  let results: Vec<Value> = report
+    .reports
+    .iter()
+    .flat_map(|file| {
+      let uri = Path::new(&file.path)
+        .strip_prefix(root)
+        .unwrap_or(Path::new(&file.path))
+        .to_string_lossy()
+        .replace('\\', "/");
+      file.problems.iter().map(move |problem| {
+        json!({
+          "ruleId": problem.rule.id(),
+          "level": match problem.severity {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+          },
+          "message": { "text": problem.message },
+          "locations": [{
+            "physicalLocation": {
+              "artifactLocation": { "uri": uri, "uriBaseId": "SRCROOT" },
+              "region": { "startLine": problem.line, "startColumn": problem.column },
+            },
+            "logicalLocations": problem.pointer.iter().map(|p| json!({ "fullyQualifiedName": p })).collect::<Vec<_>>(),
+          }],
+        })
+      })
+    })
+    .collect();
+  json!({
+    "version": "2.1.0",
+    "$schema": "https://json.schemastore.org/sarif-2.1.0.json",
+    "runs": [{
+      "tool": {
+        "driver": {
+          "name": "JsonEditor",
+          "informationUri": "https://github.com/jxo-me/JsonEditor",
+          "rules": rules,
+        }
+      },
+      "originalUriBaseIds": {
+        "SRCROOT": { "uri": format!("file://{}/", report.root.replace('\\', "/").trim_end_matches('/')) }
+      },
+      "results": results,
+    }]
+  })
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::engine::workspace;
+  use crate::engine::TempDir;
+
+  fn lint(dir: &Path, settings: &WorkspaceSettings, name: &str, text: &str) -> Vec<Problem> {
+    let path = dir.join(name);
+    fs::write(&path, text).unwrap();
+    lint_file(dir, &path, settings, &mut Schemas::default())
+  }
+
+  fn rules(problems: &[Problem]) -> Vec<(Rule, usize, usize)> {
+    problems
+      .iter()
+      .map(|p| (p.rule, p.line, p.column))
+      .collect()
+  }
+
+  #[test]
+  fn json_files_are_parsed_strictly() {
+    let dir = TempDir::new("strict");
+    let settings = WorkspaceSettings::default();
+    let problems = lint(&dir, &settings, "a.json", "{\n  \"a\": 1,\n}");
+    assert_eq!(problems.len(), 1);
+    assert_eq!((problems[0].rule, problems[0].line), (Rule::Parse, 3));
+    let problems = lint(&dir, &settings, "b.json", "// note\n{}");
+    assert_eq!(rules(&problems)[0].0, Rule::Parse);
+  }
+
+  #[test]
+  fn comments_are_allowed_in_jsonc_and_configured_files() {
+    let dir = TempDir::new("jsonc");
+    let mut settings = WorkspaceSettings::default();
+    let text = "{\n  // note\n  \"a\": [1, 2,],\n}";
+    assert!(lint(&dir, &settings, "a.jsonc", text).is_empty());
+    assert_eq!(
+      rules(&lint(&dir, &settings, "tsconfig.json", text))[0].0,
+      Rule::Parse
+    );
+    settings.allow_comments = vec!["tsconfig.json".into()];
+    assert!(lint(&dir, &settings, "tsconfig.json", text).is_empty());
+  }
+
+  #[test]
+  fn duplicate_keys_and_format() {
+    let dir = TempDir::new("rules");
+    let settings = WorkspaceSettings::default();
+    let problems = lint(&dir, &settings, "dup.json", "{\"a\": 1,\n \"a\": 2}");
+    assert_eq!(rules(&problems), [(Rule::DuplicateKey, 2, 2)]);
+    assert_eq!(problems[0].pointer.as_deref(), Some("/a"));
+
+    let formatted = format::format(&json!({ "a": [1, 2] }), &settings.format).unwrap();
+    assert!(lint(&dir, &settings, "ok.json", &formatted).is_empty());
+    let problems = lint(&dir, &settings, "compact.json", "{\"a\":[1,2]}");
+    assert_eq!(rules(&problems), [(Rule::Format, 1, 1)]);
+    assert_eq!(problems[0].severity, Severity::Warning);
+  }
+
+  #[test]
+  fn jsonl_lines_are_linted_separately() {
+    let dir = TempDir::new("jsonl");
+    let problems = lint(
+      &dir,
+      &WorkspaceSettings::default(),
+      "a.jsonl",
+      "{\"a\":1}\n\n{\"b\":1,\"b\":2}\n{",
+    );
+    assert_eq!(
+      rules(&problems),
+      [(Rule::DuplicateKey, 3, 8), (Rule::Parse, 4, 1)]
+    );
+    assert_eq!(problems[0].pointer.as_deref(), Some("/1/b"));
+  }
+
+  #[test]
+  fn schema_problems_point_at_the_value() {
+    let dir = TempDir::new("schema");
+    fs::write(
+      dir.join("schema.json"),
+      r#"{ "properties": { "port": { "type": "integer" } } }"#,
+    )
+    .unwrap();
+    fs::write(
+      dir.join(workspace::SETTINGS_FILE),
+      r#"{ "schemas": [{ "fileMatch": ["conf.json"], "schema": "./schema.json" }] }"#,
+    )
+    .unwrap();
+    fs::write(dir.join("conf.json"), "{\n  \"port\": \"80\"\n}\n").unwrap();
+    let report = lint_folder(&dir, files::DEFAULT_MAX_SIZE).unwrap();
+    assert_eq!(report.files, 3);
+    assert_eq!(report.errors, 1);
+    let conf = report
+      .reports
+      .iter()
+      .find(|r| r.path.ends_with("conf.json"))
+      .unwrap();
+    assert_eq!(rules(&conf.problems), [(Rule::Schema, 2, 3)]);
+    assert_eq!(conf.problems[0].pointer.as_deref(), Some("/port"));
+
+    let sarif = to_sarif(&report);
+    let results = sarif["runs"][0]["results"].as_array().unwrap();
+    assert!(results.iter().any(|r| r["ruleId"] == "schema"));
+  }
+}
@@ -0,0 +1,478 @@
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::{
+  collections::HashMap,
+  path::{Path, PathBuf},
+  sync::{Arc, RwLock},
+  thread::{self, JoinHandle},
+  time::Duration,
+};
+use tiny_http::{Header, Method, Request, Response, Server};
+
+use super::files;
+
+pub const DEFAULT_PORT: u16 = 3210;
+// threads answering requests, a delayed response only holds up its own thread
+const WORKERS: usize = 4;
+
+fn default_port() -> u16 {
+  DEFAULT_PORT
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct MockConfig {
+  #[serde(default = "default_port")]
+  pub port: u16,
+  // delay before every response, in milliseconds
+  #[serde(default)]
+  pub latency: u64,
+  // status of every response instead of 200 / 404
+  #[serde(default)]
+  pub status: Option<u16>,
+  // serve the JSON files of this folder instead of the document
+  #[serde(default)]
+  pub folder: Option<String>,
+}
+
+// The document is served as:
+// - a route table when every top-level key is a route: `{ "GET /users/:id": {...}, "/health": "ok" }`
+// - otherwise a REST tree: `/users/2` is `users` item with `"id": 2` (or at index 2)
+// Folders map files to routes: `users.json` -> `/users`, `users/[id].json` -> `/users/:id`,
+// `index.json` -> its folder. Path params replace `{{name}}` in string values.
+// `?_status=500&_delay=2000` override the status and latency of a single request.
+pub struct MockServer {
+  server: Arc<Server>,
+  state: Arc<RwLock<MockState>>,
+  threads: Vec<JoinHandle<()>>,
+  pub port: u16,
+}
+
+struct MockState {
+  config: MockConfig,
+  document: Value,
+}
+
+impl MockServer {
+  pub fn start(config: MockConfig, document: Value) -> Result<Self> {
+    let server = Arc::new(Server::http(("127.0.0.1", config.port)).map_err(|e| anyhow!("{}", e))?);
+    let port = server
+      .server_addr()
+      .to_ip()
+      .map_or(config.port, |addr| addr.port());
+    let config = MockConfig { port, ..config };
+    let state = Arc::new(RwLock::new(MockState { config, document }));
+    let threads = (0..WORKERS)
+      .map(|_| {
+        let server = server.clone();
+        let state = state.clone();
+        thread::spawn(move || {
+          for request in server.incoming_requests() {
+            handle(request, &state);
+          }
+        })
+      })
+      .collect();
+    Ok(MockServer {
+      server,
+      state,
+      threads,
+      port,
+    })
+  }
+
+  // live update, the next request gets the new document
+  pub fn set_document(&self, document: Value) {
+    self.state.write().unwrap().document = document;
+  }
+
+  // latency, status and folder take effect immediately, a new port needs a restart
+  pub fn set_config(&self, config: MockConfig) {
+    self.state.write().unwrap().config = MockConfig {
+      port: self.port,
+      ..config
+    };
+  }
+
+  pub fn config(&self) -> MockConfig {
+    self.state.read().unwrap().config.clone()
+  }
+}
+
+impl Drop for MockServer {
+  fn drop(&mut self) {
+    // every unblock stops one worker
+    for _ in &self.threads {
+      self.server.unblock();
+    }
+    for thread in self.threads.drain(..) {
+      thread.join().ok();
+    }
+  }
+}
+
+fn handle(mut request: Request, state: &RwLock<MockState>) {
+  let (path, query) = match request.url().split_once('?') {
+    Some((path, query)) => (path.to_string(), parse_query(query)),
+    None => (request.url().to_string(), HashMap::new()),
+  };
+  let method = request.method().clone();
+  let mut body = String::new();
+  request.as_reader().read_to_string(&mut body).ok();
+
+  let (config, found) = {
+    let state = state.read().unwrap();
+    let segments = segments(&path);
+    let found = match &state.config.folder {
+      Some(folder) => resolve_folder(Path::new(folder), &segments),
+      None => resolve_document(&state.document, &method, &segments),
+    };
+    (state.config.clone(), found)
+  };
+
+  let delay = query
+    .get("_delay")
+    .and_then(|d| d.parse().ok())
+    .unwrap_or(config.latency);
+  if delay > 0 {
+    thread::sleep(Duration::from_millis(delay));
+  }
+
+  let (status, value) = match (method, found) {
+    (Method::Options, _) => (204, None),
+    (_, None) => (
+      404,
+      Some(serde_json::json!({ "error": "not found", "path": path })),
+    ),
+    (Method::Get | Method::Head, Some(value)) => (200, Some(value)),
+    // writes are not stored, they answer with the sent body
+    (Method::Post, Some(_)) => (201, serde_json::from_str(&body).ok()),
+    (Method::Delete, Some(_)) => (204, None),
+    (_, Some(value)) => (200, serde_json::from_str(&body).ok().or(Some(value))),
+  };
+  let status = query
+    .get("_status")
+    .and_then(|s| s.parse().ok())
+    .or(config.status)
+    .unwrap_or(status);
+
+  let text = value
+    .map(|v| serde_json::to_string_pretty(&v).unwrap_or_default())
+    .unwrap_or_default();
+  let mut response = Response::from_string(text).with_status_code(status);
+  for (name, value) in [
+    ("Content-Type", "application/json; charset=utf-8"),
+    ("Access-Control-Allow-Origin", "*"),
+    ("Access-Control-Allow-Headers", "*"),
+    (
+      "Access-Control-Allow-Methods",
+      "GET, POST, PUT, PATCH, DELETE, OPTIONS",
+    ),
+  ] {
+    if let Ok(header) = Header::from_bytes(name.as_bytes(), value.as_bytes()) {
+      response = response.with_header(header);
+    }
+  }
+  request.respond(response).ok();
+}
+
+fn parse_query(query: &str) -> HashMap<String, String> {
+  query
+    .split('&')
+    .filter_map(|pair| pair.split_once('='))
+    .map(|(k, v)| (k.to_string(), percent_decode(v)))
+    .collect()
+}
+
+fn segments(path: &str) -> Vec<String> {
+  path
+    .split('/')
+    .filter(|s| !s.is_empty())
+    .map(percent_decode)
+    .collect()
+}
+
+fn percent_decode(text: &str) -> String {
+  let bytes = text.as_bytes();
+  let mut out = Vec::with_capacity(bytes.len());
+  let mut i = 0;
+  while i < bytes.len() {
+    let hex = (bytes[i] == b'%')
+      .then(|| text.get(i + 1..i + 3))
+      .flatten()
+      .and_then(|h| u8::from_str_radix(h, 16).ok());
+    match hex {
+      Some(byte) => {
+        out.push(byte);
+        i += 3;
+      }
+      None => {
+        out.push(if bytes[i] == b'+' { b' ' } else { bytes[i] });
+        i += 1;
+      }
+    }
+  }
+  String::from_utf8_lossy(&out).into()
+}
+
+// path params by name, in route order
+type Params = Vec<(String, String)>;
+
+// `GET /users/:id` -> (Some("GET"), ["users", ":id"])
+fn parse_route(key: &str) -> Option<(Option<&str>, Vec<&str>)> {
+  let (method, path) = match key.split_once(' ') {
+    Some((method, path)) if method.chars().all(|c| c.is_ascii_uppercase()) => (Some(method), path),
+    _ => (None, key),
+  };
+  let path = path.trim();
+  if !path.starts_with('/') {
+    return None;
+  }
+  Some((method, path.split('/').filter(|s| !s.is_empty()).collect()))
+}
+
+fn param_name(segment: &str) -> Option<&str> {
+  segment
+    .strip_prefix(':')
+    .or_else(|| segment.strip_prefix('[').and_then(|s| s.strip_suffix(']')))
+}
+
+// params of `pattern` if it matches the start of `segments`
+fn match_prefix(pattern: &[&str], segments: &[String]) -> Option<Params> {
+  if pattern.len() > segments.len() {
+    return None;
+  }
+  let mut params = vec![];
+  for (part, segment) in pattern.iter().zip(segments) {
+    match param_name(part) {
+      Some(name) => params.push((name.to_string(), segment.clone())),
+      None if part == segment => (),
+      None => return None,
+    }
+  }
+  Some(params)
+}
+
+fn method_matches(route: Option<&str>, method: &Method) -> bool {
+  match route {
+    None => true,
+    Some("GET") if *method == Method::Head => true,
+    Some(route) => route.eq_ignore_ascii_case(method.as_str()),
+  }
+}
+
+fn resolve_document(document: &Value, method: &Method, segments: &[String]) -> Option<Value> {
+  if let Some(routes) = document
+    .as_object()
+    .filter(|map| !map.is_empty() && map.keys().all(|key| parse_route(key).is_some()))
+  {
+    // `/users/me` wins over `/users/:id` wherever it is in the table, ties go to the first
+    let (value, params) = routes
+      .iter()
+      .filter_map(|(key, value)| {
+        let (route_method, pattern) = parse_route(key)?;
+        if pattern.len() != segments.len() || !method_matches(route_method, method) {
+          return None;
+        }
+        Some((value, match_prefix(&pattern, segments)?))
+      })
+      .min_by_key(|(_, params)| params.len())?;
+    return Some(substitute(value.clone(), &params));
+  }
+  navigate(document, segments).cloned()
+}
+
+// `users/2`: object members by key, array items by their `id` or index
+fn navigate<'a>(value: &'a Value, segments: &[String]) -> Option<&'a Value> {
+  segments
+    .iter()
+    .try_fold(value, |value, segment| match value {
+      Value::Object(map) => map.get(segment),
+      Value::Array(items) => items
+        .iter()
+        .find(|item| match &item["id"] {
+          Value::String(id) => id == segment,
+          Value::Number(id) => id.to_string() == *segment,
+          _ => false,
+        })
+        .or_else(|| segment.parse::<usize>().ok().and_then(|i| items.get(i))),
+      _ => None,
+    })
+}
+
+fn substitute(value: Value, params: &Params) -> Value {
+  match value {
+    Value::String(mut s) => {
+      for (name, param) in params {
+        s = s.replace(&format!("{{{{{}}}}}", name), param);
+      }
+      Value::String(s)
+    }
+    Value::Array(items) => Value::Array(items.into_iter().map(|v| substitute(v, params)).collect()),
+    Value::Object(map) => Value::Object(
+      map
+        .into_iter()
+        .map(|(k, v)| (k, substitute(v, params)))
+        .collect(),
+    ),
+    v => v,
+  }
+}
+
+// Files are read on every request, so edits on disk show up right away
+fn resolve_folder(folder: &Path, segments: &[String]) -> Option<Value> {
+  let mut best: Option<(usize, usize, PathBuf, Params)> = None;
+  for path in files::walk_json_files(folder, files::DEFAULT_MAX_SIZE) {
+    let Ok(rel) = path.strip_prefix(folder) else {
+      continue;
+    };
+    let rel = rel.with_extension("");
+    let mut pattern: Vec<String> = rel
+      .components()
+      .map(|c| c.as_os_str().to_string_lossy().into_owned())
+      .collect();
+    if pattern.last().is_some_and(|last| last == "index") {
+      pattern.pop();
+    }
+    let pattern: Vec<&str> = pattern.iter().map(String::as_str).collect();
+    let Some(params) = match_prefix(&pattern, segments) else {
+      continue;
+    };
+    // the longest route wins, then the one with fewer params
+    let rank = (pattern.len(), usize::MAX - params.len());
+    if !matches!(&best, Some((len, fixed, ..)) if rank <= (*len, *fixed)) {
+      best = Some((rank.0, rank.1, path, params));
+    }
+  }
+  let (len, _, path, params) = best?;
+  let value = files::read_value(&path).ok()?;
+  navigate(&value, &segments[len..]).map(|v| substitute(v.clone(), &params))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::engine::TempDir;
+  use serde_json::json;
+  use std::fs;
+
+  fn get(document: &Value, path: &str) -> Option<Value> {
+    resolve_document(document, &Method::Get, &segments(path))
+  }
+
+  #[test]
+  fn route_tables() {
+    let routes = json!({
+      "GET /users/:id": { "id": "{{id}}", "name": "User {{id}}" },
+      "POST /users": { "created": true },
+      "/health": "ok"
+    });
+    assert_eq!(
+      get(&routes, "/users/7"),
+      Some(json!({ "id": "7", "name": "User 7" }))
+    );
+    assert_eq!(
+      resolve_document(&routes, &Method::Head, &segments("/users/7")).map(|v| v["id"].clone()),
+      Some(json!("7"))
+    );
+    assert_eq!(get(&routes, "/users"), None);
+    assert_eq!(
+      resolve_document(&routes, &Method::Post, &segments("/users")),
+      Some(json!({ "created": true }))
+    );
+    assert_eq!(
+      resolve_document(&routes, &Method::Delete, &segments("/health")),
+      Some(json!("ok"))
+    );
+    assert_eq!(get(&routes, "/users/7/extra"), None);
+  }
+
+  #[test]
+  fn literal_routes_win() {
+    let routes = json!({
+      "GET /users/:id": "by id",
+      "GET /users/me": "me",
+      "/:a/:b": "any"
+    });
+    assert_eq!(get(&routes, "/users/me"), Some(json!("me")));
+    assert_eq!(get(&routes, "/users/7"), Some(json!("by id")));
+    assert_eq!(get(&routes, "/teams/7"), Some(json!("any")));
+  }
+
+  #[test]
+  fn rest_trees() {
+    let tree = json!({ "users": [{ "id": 5, "name": "a" }, { "id": "x y", "name": "b" }], "n": 1 });
+    assert_eq!(get(&tree, "/users/5/name"), Some(json!("a")));
+    assert_eq!(
+      get(&tree, "/users/x%20y"),
+      Some(json!({ "id": "x y", "name": "b" }))
+    );
+    assert_eq!(get(&tree, "/users/1/name"), Some(json!("b")));
+    assert_eq!(get(&tree, "/"), Some(tree.clone()));
+    assert_eq!(get(&tree, "/n/1"), None);
+  }
+
+  #[test]
+  fn decodes_paths_and_queries() {
+    assert_eq!(percent_decode("a%2Fb+c%zz%C3%A9"), "a/b c%zzé");
+    let query = parse_query("_status=500&_delay=10&flag");
+    assert_eq!(query["_status"], "500");
+    assert!(!query.contains_key("flag"));
+  }
+
+  #[test]
+  fn folder_routes() {
+    let dir = TempDir::new("folder-routes");
+    fs::create_dir_all(dir.join("users")).unwrap();
+    fs::write(dir.join("index.json"), r#"{ "root": true }"#).unwrap();
+    fs::write(dir.join("users.json"), r#"[{ "id": 1 }]"#).unwrap();
+    fs::write(
+      dir.join("users/[id].json"),
+      r#"{ "id": "{{id}}", "tags": ["a"] }"#,
+    )
+    .unwrap();
+    let at = |path: &str| resolve_folder(&dir, &segments(path));
+    assert_eq!(at("/"), Some(json!({ "root": true })));
+    assert_eq!(at("/users"), Some(json!([{ "id": 1 }])));
+    assert_eq!(at("/users/3"), Some(json!({ "id": "3", "tags": ["a"] })));
+    assert_eq!(at("/users/3/tags/0"), Some(json!("a")));
+    assert_eq!(at("/users/3/missing"), None);
+  }
+
+  #[test]
+  fn serves_and_updates_documents() {
+    let config = MockConfig {
+      port: 0,
+      latency: 0,
+      status: None,
+      folder: None,
+    };
+    let server = MockServer::start(config.clone(), json!({ "a": 1 })).unwrap();
+    assert_ne!(server.port, 0);
+    let url = |path: &str| format!("http://127.0.0.1:{}{}", server.port, path);
+    let response = reqwest::blocking::get(url("/a")).unwrap();
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(response.json::<Value>().unwrap(), json!(1));
+    assert_eq!(
+      reqwest::blocking::get(url("/b")).unwrap().status().as_u16(),
+      404
+    );
+    assert_eq!(
+      reqwest::blocking::get(url("/a?_status=503"))
+        .unwrap()
+        .status()
+        .as_u16(),
+      503
+    );
+
+    server.set_document(json!({ "b": 2 }));
+    server.set_config(MockConfig {
+      status: Some(418),
+      ..config
+    });
+    assert_eq!(server.config().port, server.port);
+    let response = reqwest::blocking::get(url("/b")).unwrap();
+    assert_eq!(response.status().as_u16(), 418);
+    assert_eq!(response.json::<Value>().unwrap(), json!(2));
+  }
+}
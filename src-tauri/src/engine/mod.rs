@@ -0,0 +1,88 @@
+pub mod batch;
+pub mod clipboard;
+pub mod files;
+pub mod format;
+pub mod fuzzy;
+pub mod http;
+pub mod importers;
+pub mod jsonpath;
+pub mod lint;
+pub mod mock;
+pub mod repair;
+pub mod requests;
+pub mod search;
+pub mod snippets;
+pub mod spans;
+pub mod tabular;
+pub mod transform;
+pub mod workspace;
+
+use anyhow::{anyhow, Result};
+use serde_json::Value;
+
+// Run `f` on the value at `pointer` (the whole document when empty) and put the result back.
+pub fn apply_at<F>(mut doc: Value, pointer: Option<&str>, f: F) -> Result<Value>
+where
+  F: FnOnce(Value) -> Result<Value>,
+{
+  let pointer = pointer.unwrap_or_default();
+  if pointer.is_empty() {
+    return f(doc);
+  }
+  let target = doc
+    .pointer_mut(pointer)
+    .ok_or_else(|| anyhow!("pointer not found: {}", pointer))?;
+  *target = f(target.take())?;
+  Ok(doc)
+}
+
+// JSON Pointer reference token escaping (RFC 6901)
+pub fn escape_token(key: &str) -> String {
+  key.replace('~', "~0").replace('/', "~1")
+}
+
+pub fn unescape_token(token: &str) -> String {
+  token.replace("~1", "/").replace("~0", "~")
+}
+
+// Fresh directory for one test, removed again when the test is done (or panics).
+#[cfg(test)]
+pub(crate) struct TempDir(std::path::PathBuf);
+
+#[cfg(test)]
+impl TempDir {
+  pub fn new(name: &str) -> Self {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    static DIRS: AtomicUsize = AtomicUsize::new(0);
+    let n = DIRS.fetch_add(1, Ordering::Relaxed);
+    let dir =
+      std::env::temp_dir().join(format!("jsoneditor-{}-{}-{}", std::process::id(), n, name));
+    std::fs::remove_dir_all(&dir).ok();
+    std::fs::create_dir_all(&dir).unwrap();
+    // the temp dir is a symlink on macOS; tests compare against walked paths
+    Self(dir.canonicalize().unwrap())
+  }
+}
+
+#[cfg(test)]
+impl std::ops::Deref for TempDir {
+  type Target = std::path::Path;
+
+  fn deref(&self) -> &std::path::Path {
+    &self.0
+  }
+}
+
+#[cfg(test)]
+impl AsRef<std::path::Path> for TempDir {
+  fn as_ref(&self) -> &std::path::Path {
+    &self.0
+  }
+}
+
+#[cfg(test)]
+impl Drop for TempDir {
+  fn drop(&mut self) {
+    std::fs::remove_dir_all(&self.0).ok();
+  }
+}
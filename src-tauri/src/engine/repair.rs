@@ -0,0 +1,234 @@
+use anyhow::{anyhow, Result};
+use serde_json::Value;
+
+use super::files;
+
+// Parse JSON pasted from elsewhere, fixing what usually breaks it:
+// JS object literals (single quotes, unquoted keys, `undefined`), Python reprs (`True`, `None`),
+// smart quotes, comments and trailing commas, surrounding text or a JSONP callback,
+// unclosed brackets, and a whole document that was encoded as a JSON string.
+// The error of the original text is returned when the repair does not parse either.
+pub fn repair(text: &str) -> Result<Value> {
+  let text = text.trim().trim_start_matches('\u{feff}');
+  let error = match serde_json::from_str(&files::strip_comments(text)) {
+    Ok(value) => return Ok(unwrap_string(value)),
+    Err(e) => e,
+  };
+  // plain text is not quoted into a string, only objects and arrays are repaired
+  extract(text)
+    .and_then(|body| serde_json::from_str(&files::strip_comments(&rewrite(body))).ok())
+    .map(unwrap_string)
+    .ok_or_else(|| anyhow!("not JSON: {}", error))
+}
+
+// `"{\"a\":1}"` -> `{"a":1}`
+fn unwrap_string(value: Value) -> Value {
+  match &value {
+    Value::String(s) if s.trim_start().starts_with(['{', '[']) => {
+      serde_json::from_str(s).unwrap_or(value)
+    }
+    _ => value,
+  }
+}
+
+// the part from the first `{` or `[` to the last `}` or `]`, for log lines and `callback({...});`
+fn extract(text: &str) -> Option<&str> {
+  let start = text.find(['{', '['])?;
+  let end = text.rfind(['}', ']']).filter(|end| *end > start);
+  Some(match end {
+    Some(end) => &text[start..=end],
+    None => &text[start..],
+  })
+}
+
+fn rewrite(text: &str) -> String {
+  let chars: Vec<char> = text.chars().collect();
+  let mut out = String::with_capacity(text.len());
+  let mut closers = vec![];
+  let mut i = 0;
+  while i < chars.len() {
+    let c = chars[i];
+    match c {
+      '"' | '\'' | '“' | '”' | '‘' | '’' => {
+        let quote = match c {
+          '“' | '”' => '”',
+          '‘' | '’' => '’',
+          c => c,
+        };
+        let mut value = String::new();
+        i += 1;
+        while i < chars.len() && chars[i] != quote && !(quote == '”' && chars[i] == '"') {
+          if chars[i] == '\\' && i + 1 < chars.len() {
+            // `\'` is not a JSON escape
+            if chars[i + 1] != '\'' {
+              value.push('\\');
+            }
+            i += 1;
+          } else if chars[i] == '"' {
+            value.push('\\');
+          }
+          push_char(&mut value, chars[i]);
+          i += 1;
+        }
+        out.push('"');
+        out.push_str(&value);
+        out.push('"');
+      }
+      '{' => {
+        closers.push('}');
+        out.push(c);
+      }
+      '[' => {
+        closers.push(']');
+        out.push(c);
+      }
+      '}' | ']' => {
+        closers.pop();
+        out.push(c);
+      }
+      '/' if matches!(chars.get(i + 1), Some('/' | '*')) => {
+        // comments are left for `strip_comments`, they may contain quotes
+        let end = if chars[i + 1] == '/' {
+          chars[i..].iter().position(|c| *c == '\n').map(|p| i + p)
+        } else {
+          chars[i + 2..]
+            .windows(2)
+            .position(|w| w == ['*', '/'])
+            .map(|p| i + 2 + p + 1)
+        };
+        let end = end.unwrap_or(chars.len() - 1);
+        out.extend(&chars[i..=end]);
+        i = end;
+      }
+      // numbers are copied as they are, `1e5` is not a word
+      c if c.is_ascii_digit() || c == '-' => {
+        while i + 1 < chars.len()
+          && (chars[i + 1].is_ascii_alphanumeric() || matches!(chars[i + 1], '.' | '+' | '-'))
+        {
+          out.push(chars[i]);
+          i += 1;
+        }
+        out.push(chars[i]);
+      }
+      c if c.is_alphabetic() || c == '_' || c == '$' => {
+        let start = i;
+        while i + 1 < chars.len()
+          && (chars[i + 1].is_alphanumeric() || matches!(chars[i + 1], '_' | '$'))
+        {
+          i += 1;
+        }
+        let word: String = chars[start..=i].iter().collect();
+        let is_key = chars[i + 1..]
+          .iter()
+          .find(|c| !c.is_whitespace())
+          .is_some_and(|c| *c == ':');
+        match word.as_str() {
+          _ if is_key => out.push_str(&format!("\"{}\"", word)),
+          "true" | "True" => out.push_str("true"),
+          "false" | "False" => out.push_str("false"),
+          "null" | "None" | "undefined" | "NaN" | "Infinity" => out.push_str("null"),
+          _ => out.push_str(&Value::String(word).to_string()),
+        }
+      }
+      c => out.push(c),
+    }
+    i += 1;
+  }
+  out.extend(closers.into_iter().rev());
+  out
+}
+
+fn push_char(out: &mut String, c: char) {
+  match c {
+    '\n' => out.push_str("\\n"),
+    '\r' => out.push_str("\\r"),
+    '\t' => out.push_str("\\t"),
+    c => out.push(c),
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use serde_json::json;
+
+  fn repaired(text: &str) -> Value {
+    repair(text).unwrap_or_else(|e| panic!("{}: {}", text, e))
+  }
+
+  #[test]
+  fn keeps_valid_json() {
+    assert_eq!(
+      repaired("\u{feff} {\"a\": [1, 2.5e3]} "),
+      json!({"a": [1, 2.5e3]})
+    );
+    assert_eq!(repaired("42"), json!(42));
+  }
+
+  #[test]
+  fn repairs_js_object_literals() {
+    let text = "{name: 'it\\'s', $id: 1, ok: true, gone: undefined}";
+    assert_eq!(
+      repaired(text),
+      json!({"name": "it's", "$id": 1, "ok": true, "gone": null})
+    );
+  }
+
+  #[test]
+  fn repairs_python_reprs() {
+    let text = "{'a': True, 'b': False, 'c': None, 'd': [1, -2]}";
+    assert_eq!(
+      repaired(text),
+      json!({"a": true, "b": false, "c": null, "d": [1, -2]})
+    );
+  }
+
+  #[test]
+  fn repairs_smart_quotes() {
+    assert_eq!(repaired("{“a”: ‘b’}"), json!({"a": "b"}));
+  }
+
+  #[test]
+  fn drops_comments_and_trailing_commas() {
+    let text = "{\n  // note\n  \"a\": 1, /* \"b\": 2 */\n  \"c\": [1, 2,],\n}";
+    assert_eq!(repaired(text), json!({"a": 1, "c": [1, 2]}));
+    assert_eq!(
+      repaired("{a: 'x', // it's\n b: 2,}"),
+      json!({"a": "x", "b": 2})
+    );
+  }
+
+  #[test]
+  fn extracts_json_from_surrounding_text() {
+    assert_eq!(repaired("callback({\"a\": 1});"), json!({"a": 1}));
+    assert_eq!(repaired("INFO 12:00 payload=[1, 2] done"), json!([1, 2]));
+  }
+
+  #[test]
+  fn closes_unclosed_brackets() {
+    assert_eq!(
+      repaired("{\"a\": [1, {\"b\": 2"),
+      json!({"a": [1, {"b": 2}]})
+    );
+  }
+
+  #[test]
+  fn unwraps_encoded_documents() {
+    assert_eq!(repaired(r#""{\"a\":1}""#), json!({"a": 1}));
+    assert_eq!(repaired(r#""plain""#), json!("plain"));
+  }
+
+  #[test]
+  fn escapes_quotes_and_control_characters_in_strings() {
+    assert_eq!(
+      repaired("{a: 'say \"hi\"\tnow'}"),
+      json!({"a": "say \"hi\"\tnow"})
+    );
+  }
+
+  #[test]
+  fn reports_the_original_error() {
+    let err = repair("hello world").unwrap_err().to_string();
+    assert!(err.starts_with("not JSON: expected value"), "{}", err);
+  }
+}
@@ -0,0 +1,515 @@
+use anyhow::{anyhow, bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use similar::TextDiff;
+use std::{
+  collections::BTreeMap,
+  fs,
+  path::{Path, PathBuf},
+  time::{SystemTime, UNIX_EPOCH},
+};
+
+use super::{
+  files,
+  http::{Header, HttpRequest, HttpResponse},
+};
+
+const ENVIRONMENTS_FILE: &str = "environments.json";
+const HISTORY_DIR: &str = "history";
+// older responses are dropped from the history
+pub const HISTORY_LIMIT: usize = 100;
+// larger response bodies are stored cut, without their parsed JSON
+pub const HISTORY_BODY_LIMIT: usize = 1024 * 1024;
+// header values that are never written to the history
+const SECRET_HEADERS: [&str; 5] = [
+  "authorization",
+  "proxy-authorization",
+  "cookie",
+  "set-cookie",
+  "x-api-key",
+];
+const REDACTED: &str = "<redacted>";
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct SavedRequest {
+  pub name: String,
+  #[serde(flatten)]
+  pub request: HttpRequest,
+}
+
+// Named group of requests, stored as `<dir>/<name>.json`
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Collection {
+  pub name: String,
+  #[serde(default)]
+  pub requests: Vec<SavedRequest>,
+}
+
+impl Collection {
+  pub fn list(dir: &Path) -> Result<Vec<Collection>> {
+    if !dir.is_dir() {
+      return Ok(vec![]);
+    }
+    let mut collections = vec![];
+    for entry in fs::read_dir(dir)? {
+      let path = entry?.path();
+      let is_collection = path.extension().is_some_and(|ext| ext == "json")
+        && path
+          .file_name()
+          .is_some_and(|name| name != ENVIRONMENTS_FILE);
+      if is_collection {
+        let text = fs::read_to_string(&path)?;
+        collections
+          .push(serde_json::from_str(&text).with_context(|| format!("{}", path.display()))?);
+      }
+    }
+    collections.sort_by(|a: &Collection, b| a.name.cmp(&b.name));
+    Ok(collections)
+  }
+
+  pub fn save(&self, dir: &Path) -> Result<()> {
+    if self.name == ENVIRONMENTS_FILE.trim_end_matches(".json") {
+      bail!("`{}` is a reserved name", self.name);
+    }
+    fs::create_dir_all(dir)?;
+    files::write_atomic(
+      &files::named_file(dir, &self.name)?,
+      &serde_json::to_string_pretty(self)?,
+    )
+  }
+
+  pub fn delete(dir: &Path, name: &str) -> Result<()> {
+    Ok(fs::remove_file(files::named_file(dir, name)?)?)
+  }
+
+  pub fn load(dir: &Path, name: &str) -> Result<Option<Collection>> {
+    let path = files::named_file(dir, name)?;
+    if !path.is_file() {
+      return Ok(None);
+    }
+    Ok(Some(serde_json::from_str(&fs::read_to_string(path)?)?))
+  }
+
+  // add `requests`, replacing those with the same name
+  pub fn merge(&mut self, requests: Vec<SavedRequest>) {
+    for request in requests {
+      match self.requests.iter_mut().find(|r| r.name == request.name) {
+        Some(existing) => *existing = request,
+        None => self.requests.push(request),
+      }
+    }
+  }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct Environment {
+  pub name: String,
+  #[serde(default)]
+  pub variables: BTreeMap<String, String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct Environments {
+  // used when a request is sent without naming an environment
+  pub active: Option<String>,
+  #[serde(default)]
+  pub environments: Vec<Environment>,
+}
+
+impl Environments {
+  pub fn load(dir: &Path) -> Result<Self> {
+    let path = dir.join(ENVIRONMENTS_FILE);
+    if !path.is_file() {
+      return Ok(Self::default());
+    }
+    Ok(serde_json::from_str(&fs::read_to_string(path)?)?)
+  }
+
+  pub fn save(&self, dir: &Path) -> Result<()> {
+    fs::create_dir_all(dir)?;
+    files::write_atomic(
+      &dir.join(ENVIRONMENTS_FILE),
+      &serde_json::to_string_pretty(self)?,
+    )
+  }
+
+  // add `environment`, replacing the one with the same name
+  pub fn upsert(&mut self, environment: Environment) {
+    match self
+      .environments
+      .iter_mut()
+      .find(|env| env.name == environment.name)
+    {
+      Some(existing) => *existing = environment,
+      None => self.environments.push(environment),
+    }
+  }
+
+  // variables of `name`, or of the active environment
+  pub fn variables(&self, name: Option<&str>) -> Result<BTreeMap<String, String>> {
+    let Some(name) = name.or(self.active.as_deref()) else {
+      return Ok(BTreeMap::new());
+    };
+    self
+      .environments
+      .iter()
+      .find(|env| env.name == name)
+      .map(|env| env.variables.clone())
+      .ok_or_else(|| anyhow!("unknown environment `{}`", name))
+  }
+}
+
+// Replace `{{name}}` placeholders in the URL, headers and body
+pub fn resolve(request: &HttpRequest, variables: &BTreeMap<String, String>) -> Result<HttpRequest> {
+  Ok(HttpRequest {
+    method: request.method.clone(),
+    url: substitute(&request.url, variables)?,
+    headers: request
+      .headers
+      .iter()
+      .map(|header| {
+        Ok(Header {
+          name: substitute(&header.name, variables)?,
+          value: substitute(&header.value, variables)?,
+        })
+      })
+      .collect::<Result<_>>()?,
+    body: request
+      .body
+      .as_deref()
+      .map(|body| substitute(body, variables))
+      .transpose()?,
+  })
+}
+
+pub fn substitute(text: &str, variables: &BTreeMap<String, String>) -> Result<String> {
+  let mut out = String::with_capacity(text.len());
+  let mut rest = text;
+  while let Some(start) = rest.find("{{") {
+    let Some(len) = rest[start + 2..].find("}}") else {
+      break;
+    };
+    let name = rest[start + 2..start + 2 + len].trim();
+    let value = variables
+      .get(name)
+      .ok_or_else(|| anyhow!("undefined variable `{}`", name))?;
+    out.push_str(&rest[..start]);
+    out.push_str(value);
+    rest = &rest[start + 2 + len + 2..];
+  }
+  out.push_str(rest);
+  Ok(out)
+}
+
+// A sent request as it was written (placeholders not resolved) and its response
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct HistoryEntry {
+  pub id: String,
+  // milliseconds since the Unix epoch
+  pub created: u64,
+  pub environment: Option<String>,
+  pub request: HttpRequest,
+  pub response: HttpResponse,
+  // the stored body was cut at `HISTORY_BODY_LIMIT`
+  #[serde(default)]
+  pub truncated: bool,
+}
+
+impl HistoryEntry {
+  // what goes to disk: secret headers redacted, large bodies cut
+  fn stored(&self) -> HistoryEntry {
+    let mut entry = self.clone();
+    for header in entry
+      .request
+      .headers
+      .iter_mut()
+      .chain(entry.response.headers.iter_mut())
+    {
+      if SECRET_HEADERS.contains(&header.name.to_lowercase().as_str()) {
+        header.value = REDACTED.into();
+      }
+    }
+    let body = &mut entry.response.body;
+    if body.len() > HISTORY_BODY_LIMIT {
+      let mut end = HISTORY_BODY_LIMIT;
+      while !body.is_char_boundary(end) {
+        end -= 1;
+      }
+      body.truncate(end);
+      entry.response.json = None;
+      entry.truncated = true;
+    }
+    entry
+  }
+}
+
+// Store `template`, the request before `resolve`, so variable values are not written to disk.
+// The returned entry keeps the whole response.
+pub fn record(
+  dir: &Path,
+  environment: Option<String>,
+  template: HttpRequest,
+  response: HttpResponse,
+) -> Result<HistoryEntry> {
+  let history_dir = dir.join(HISTORY_DIR);
+  fs::create_dir_all(&history_dir)?;
+  let created = SystemTime::now().duration_since(UNIX_EPOCH)?.as_millis() as u64;
+  // two requests may finish within the same millisecond
+  let mut id = created.to_string();
+  let mut n = 1;
+  while history_dir.join(format!("{}.json", id)).exists() {
+    id = format!("{}-{}", created, n);
+    n += 1;
+  }
+  let entry = HistoryEntry {
+    id,
+    created,
+    environment,
+    request: template,
+    response,
+    truncated: false,
+  };
+  files::write_atomic(
+    &files::named_file(&history_dir, &entry.id)?,
+    &serde_json::to_string(&entry.stored())?,
+  )?;
+  prune(&history_dir)?;
+  Ok(entry)
+}
+
+// Keep the newest `HISTORY_LIMIT` entries, their file names are enough to order them
+fn prune(history_dir: &Path) -> Result<()> {
+  let mut entries: Vec<((u64, u64), PathBuf)> = fs::read_dir(history_dir)?
+    .filter_map(|entry| entry.ok())
+    .filter_map(|entry| {
+      let path = entry.path();
+      let key = history_key(path.file_stem()?.to_str()?)?;
+      Some((key, path))
+    })
+    .collect();
+  entries.sort_by_key(|(key, _)| std::cmp::Reverse(*key));
+  for (_, path) in entries.iter().skip(HISTORY_LIMIT) {
+    fs::remove_file(path).ok();
+  }
+  Ok(())
+}
+
+// `<created>` or `<created>-<n>` as (created, n)
+fn history_key(id: &str) -> Option<(u64, u64)> {
+  let (created, n) = id.split_once('-').unwrap_or((id, "0"));
+  Some((created.parse().ok()?, n.parse().ok()?))
+}
+
+// Newest first
+pub fn history(dir: &Path) -> Result<Vec<HistoryEntry>> {
+  let dir = dir.join(HISTORY_DIR);
+  if !dir.is_dir() {
+    return Ok(vec![]);
+  }
+  let mut entries = vec![];
+  for entry in fs::read_dir(&dir)? {
+    let path = entry?.path();
+    if let Ok(entry) = serde_json::from_str::<HistoryEntry>(&fs::read_to_string(&path)?) {
+      entries.push(entry);
+    }
+  }
+  entries.sort_by(|a, b| b.created.cmp(&a.created).then_with(|| b.id.cmp(&a.id)));
+  Ok(entries)
+}
+
+pub fn clear_history(dir: &Path) -> Result<()> {
+  let dir = dir.join(HISTORY_DIR);
+  if dir.is_dir() {
+    fs::remove_dir_all(dir)?;
+  }
+  Ok(())
+}
+
+pub fn history_entry(dir: &Path, id: &str) -> Result<HistoryEntry> {
+  let path = files::named_file(&dir.join(HISTORY_DIR), id)?;
+  let text = fs::read_to_string(&path).with_context(|| format!("no history entry `{}`", id))?;
+  Ok(serde_json::from_str(&text)?)
+}
+
+// Unified diff of two responses: status line, headers, then the body (pretty printed when JSON)
+pub fn diff(a: &HistoryEntry, b: &HistoryEntry) -> Result<String> {
+  let (left, right) = (response_text(&a.response)?, response_text(&b.response)?);
+  Ok(
+    TextDiff::from_lines(&left, &right)
+      .unified_diff()
+      .header(&a.id, &b.id)
+      .to_string(),
+  )
+}
+
+fn response_text(response: &HttpResponse) -> Result<String> {
+  let mut text = format!("{} {}\n", response.status, response.status_text);
+  let mut headers: Vec<&Header> = response.headers.iter().collect();
+  headers.sort_by(|a, b| a.name.cmp(&b.name));
+  for header in headers {
+    text.push_str(&format!("{}: {}\n", header.name, header.value));
+  }
+  text.push('\n');
+  match &response.json {
+    Some(json) => text.push_str(&serde_json::to_string_pretty(json)?),
+    None => text.push_str(&response.body),
+  }
+  text.push('\n');
+  Ok(text)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::engine::TempDir;
+
+  fn header(name: &str, value: &str) -> Header {
+    Header {
+      name: name.into(),
+      value: value.into(),
+    }
+  }
+
+  fn request() -> HttpRequest {
+    HttpRequest {
+      method: "POST".into(),
+      url: "{{base}}/users?q={{ query }}".into(),
+      headers: vec![
+        header("Authorization", "Bearer {{token}}"),
+        header("Accept", "application/json"),
+      ],
+      body: Some(r#"{"name": "{{name}}"}"#.into()),
+    }
+  }
+
+  fn response(body: &str) -> HttpResponse {
+    HttpResponse {
+      url: "https://example.com/users".into(),
+      status: 200,
+      status_text: "OK".into(),
+      headers: vec![header("Set-Cookie", "session=abc")],
+      elapsed: 1,
+      size: body.len(),
+      body: body.into(),
+      json: serde_json::from_str(body).ok(),
+    }
+  }
+
+  fn variables() -> BTreeMap<String, String> {
+    [
+      ("base", "https://example.com"),
+      ("query", "a b"),
+      ("token", "secret"),
+      ("name", "Ann"),
+    ]
+    .into_iter()
+    .map(|(k, v)| (k.to_string(), v.to_string()))
+    .collect()
+  }
+
+  #[test]
+  fn resolves_placeholders() {
+    let resolved = resolve(&request(), &variables()).unwrap();
+    assert_eq!(resolved.url, "https://example.com/users?q=a b");
+    assert_eq!(resolved.headers[0].value, "Bearer secret");
+    assert_eq!(resolved.body.as_deref(), Some(r#"{"name": "Ann"}"#));
+    assert!(substitute("{{missing}}", &variables()).is_err());
+    assert_eq!(substitute("{{open", &variables()).unwrap(), "{{open");
+  }
+
+  #[test]
+  fn environments() {
+    let mut envs = Environments::default();
+    assert!(envs.variables(None).unwrap().is_empty());
+    envs.upsert(Environment {
+      name: "dev".into(),
+      variables: variables(),
+    });
+    envs.upsert(Environment {
+      name: "dev".into(),
+      variables: BTreeMap::new(),
+    });
+    assert_eq!(envs.environments.len(), 1);
+    envs.active = Some("dev".into());
+    assert!(envs.variables(None).unwrap().is_empty());
+    assert!(envs.variables(Some("prod")).is_err());
+  }
+
+  #[test]
+  fn history_stores_templates_without_secrets() {
+    let dir = TempDir::new("secrets");
+    let entry = record(&dir, Some("dev".into()), request(), response("[1]")).unwrap();
+    assert_eq!(entry.response.headers[0].value, "session=abc");
+    let stored = history_entry(&dir, &entry.id).unwrap();
+    assert_eq!(stored.request.url, "{{base}}/users?q={{ query }}");
+    assert_eq!(stored.request.headers[0].value, REDACTED);
+    assert_eq!(stored.request.headers[1].value, "application/json");
+    assert_eq!(stored.response.headers[0].value, REDACTED);
+    assert_eq!(stored.response.json, Some(serde_json::json!([1])));
+    assert!(!stored.truncated);
+    let text = fs::read_to_string(files::named_file(&dir.join(HISTORY_DIR), &entry.id).unwrap());
+    assert!(!text.unwrap().contains("secret"));
+  }
+
+  #[test]
+  fn history_cuts_large_bodies() {
+    let dir = TempDir::new("large");
+    let body = format!("[\"{}\"]", "é".repeat(HISTORY_BODY_LIMIT));
+    let entry = record(&dir, None, request(), response(&body)).unwrap();
+    assert_eq!(entry.response.body, body);
+    let stored = history_entry(&dir, &entry.id).unwrap();
+    assert!(stored.truncated);
+    assert_eq!(stored.response.json, None);
+    assert!(stored.response.body.len() <= HISTORY_BODY_LIMIT);
+    assert_eq!(stored.response.size, body.len());
+  }
+
+  #[test]
+  fn history_keeps_the_newest_entries() {
+    let dir = TempDir::new("prune");
+    let history_dir = dir.join(HISTORY_DIR);
+    fs::create_dir_all(&history_dir).unwrap();
+    // older than anything `record` writes, `1-10` sorts after `1-9` by number
+    for id in (0..HISTORY_LIMIT).map(|n| format!("1-{}", n)) {
+      fs::write(history_dir.join(format!("{}.json", id)), "{}").unwrap();
+    }
+    let entry = record(&dir, None, request(), response("1")).unwrap();
+    let mut ids: Vec<String> = fs::read_dir(&history_dir)
+      .unwrap()
+      .map(|e| {
+        e.unwrap()
+          .path()
+          .file_stem()
+          .unwrap()
+          .to_string_lossy()
+          .into()
+      })
+      .collect();
+    ids.sort();
+    assert_eq!(ids.len(), HISTORY_LIMIT);
+    assert!(ids.contains(&entry.id));
+    assert!(!ids.contains(&"1-0".to_string()));
+    assert!(ids.contains(&"1-10".to_string()));
+    assert_eq!(history(&dir).unwrap()[0].id, entry.id);
+    clear_history(&dir).unwrap();
+    assert!(history(&dir).unwrap().is_empty());
+  }
+
+  #[test]
+  fn diffs_responses() {
+    let dir = TempDir::new("diff");
+    let a = record(&dir, None, request(), response(r#"{"a": 1}"#)).unwrap();
+    let b = record(&dir, None, request(), response(r#"{"a": 2}"#)).unwrap();
+    let diff = diff(&a, &b).unwrap();
+    assert!(diff.contains("-  \"a\": 1\n+  \"a\": 2"));
+  }
+
+  #[test]
+  fn reserved_collection_name() {
+    let dir = TempDir::new("collections");
+    let collection = Collection {
+      name: "environments".into(),
+      requests: vec![],
+    };
+    assert!(collection.save(&dir).is_err());
+  }
+}
@@ -0,0 +1,503 @@
+use anyhow::{anyhow, bail, Result};
+use regex::{NoExpand, Regex, RegexBuilder};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Number, Value};
+use std::{collections::HashMap, path::Path};
+
+use super::{escape_token, files, jsonpath::JsonPath, spans, unescape_token};
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Target {
+  Key,
+  String,
+  Number,
+  // any value, only reported for JSONPath matches
+  Value,
+}
+
+fn all_targets() -> Vec<Target> {
+  vec![Target::Key, Target::String, Target::Number]
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct SearchQuery {
+  pub pattern: String,
+  // treat `pattern` as a regular expression, the replacement may then use `$1` / `${name}`
+  #[serde(default)]
+  pub regex: bool,
+  #[serde(default)]
+  pub case_sensitive: bool,
+  #[serde(default = "all_targets")]
+  pub targets: Vec<Target>,
+  // only search below this JSON Pointer
+  pub pointer: Option<String>,
+}
+
+impl SearchQuery {
+  pub fn compile(&self) -> Result<Regex> {
+    if self.pattern.is_empty() {
+      bail!("empty search pattern");
+    }
+    let pattern = if self.regex {
+      self.pattern.clone()
+    } else {
+      regex::escape(&self.pattern)
+    };
+    Ok(
+      RegexBuilder::new(&pattern)
+        .case_insensitive(!self.case_sensitive)
+        .build()?,
+    )
+  }
+
+  fn has(&self, target: Target) -> bool {
+    self.targets.contains(&target)
+  }
+
+  // The value at `pointer` and everything below it. The key the scope node itself is stored
+  // under is outside, `replace` starts at that value and can't rename it.
+  fn in_scope(&self, pointer: &str, target: Target) -> bool {
+    match self.pointer.as_deref() {
+      None | Some("") => true,
+      Some(root) if pointer == root => target != Target::Key,
+      Some(root) => pointer.starts_with(root) && pointer[root.len()..].starts_with('/'),
+    }
+  }
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct Hit {
+  pub pointer: String,
+  pub target: Target,
+  // the searched key or value and the matched byte range within it
+  pub text: String,
+  pub start: usize,
+  pub end: usize,
+  // where the key or value starts in the source text
+  pub line: usize,
+  pub column: usize,
+}
+
+pub fn search_text(text: &str, query: &SearchQuery) -> Result<Vec<Hit>> {
+  find(text, query, &query.compile()?)
+}
+
+fn find(text: &str, query: &SearchQuery, re: &Regex) -> Result<Vec<Hit>> {
+  let doc: Value = serde_json::from_str(text)?;
+  let mut hits = vec![];
+  for node in spans::index(text)? {
+    let mut push = |target: Target, text: String, at: spans::Position| {
+      if !query.in_scope(&node.pointer, target) {
+        return;
+      }
+      for m in re.find_iter(&text) {
+        hits.push(Hit {
+          pointer: node.pointer.clone(),
+          target,
+          text: text.clone(),
+          start: m.start(),
+          end: m.end(),
+          line: at.line,
+          column: at.column,
+        });
+      }
+    };
+    if let (Some(at), true) = (node.key, query.has(Target::Key)) {
+      let key = node.pointer.rsplit('/').next().unwrap_or_default();
+      push(Target::Key, unescape_token(key), at);
+    }
+    match doc.pointer(&node.pointer) {
+      Some(Value::String(s)) if query.has(Target::String) => {
+        push(Target::String, s.clone(), node.value)
+      }
+      // as written, `1E5` would read `100000.0` when printed back
+      Some(Value::Number(_)) if query.has(Target::Number) => push(
+        Target::Number,
+        number_text(text, node.value.offset),
+        node.value,
+      ),
+      _ => (),
+    }
+  }
+  Ok(hits)
+}
+
+fn number_text(text: &str, offset: usize) -> String {
+  text[offset..]
+    .chars()
+    .take_while(|c| c.is_ascii_digit() || matches!(c, '-' | '+' | '.' | 'e' | 'E'))
+    .collect()
+}
+
+// JSONPath matches of a JSON text, `text` of each hit is the (shortened) matched value
+pub fn search_path_text(text: &str, path: &JsonPath) -> Result<Vec<Hit>> {
+  let doc: Value = serde_json::from_str(text)?;
+  let positions: HashMap<String, spans::Position> = spans::index(text)?
+    .into_iter()
+    .map(|node| (node.pointer, node.value))
+    .collect();
+  Ok(
+    path
+      .select(&doc)
+      .into_iter()
+      .filter_map(|(pointer, value)| {
+        let at = positions.get(&pointer)?;
+        let text = preview(value);
+        Some(Hit {
+          target: Target::Value,
+          start: 0,
+          end: text.len(),
+          text,
+          line: at.line,
+          column: at.column,
+          pointer,
+        })
+      })
+      .collect(),
+  )
+}
+
+fn preview(value: &Value) -> String {
+  let text = match value {
+    Value::String(s) => s.clone(),
+    v => v.to_string(),
+  };
+  match text.char_indices().nth(200) {
+    Some((end, _)) => format!("{}...", &text[..end]),
+    None => text,
+  }
+}
+
+// --- Folder search
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(tag = "mode", rename_all = "snake_case")]
+pub enum FolderQuery {
+  // key / value / regex search, see `SearchQuery::targets`
+  Text(SearchQuery),
+  JsonPath { path: String },
+}
+
+impl FolderQuery {
+  // surface an invalid pattern before starting a search
+  pub fn validate(&self) -> Result<()> {
+    match self {
+      FolderQuery::Text(q) => q.compile().map(|_| ()),
+      FolderQuery::JsonPath { path } => JsonPath::parse(path).map(|_| ()),
+    }
+  }
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct FileHits {
+  pub path: String,
+  pub hits: Vec<Hit>,
+}
+
+#[derive(Serialize, Debug, Clone, Default)]
+pub struct FolderStats {
+  pub files: usize,
+  pub matched_files: usize,
+  pub hits: usize,
+  // files that could not be read or parsed
+  pub errors: usize,
+  pub cancelled: bool,
+}
+
+enum Matcher<'a> {
+  Text(&'a SearchQuery, Regex),
+  Path(JsonPath),
+}
+
+// Search every JSON file below `root`, `on_file` receives the hits file by file as they are found.
+// `cancelled` is polled between files.
+pub fn search_folder<C, F>(
+  root: &Path,
+  query: &FolderQuery,
+  max_size: u64,
+  cancelled: C,
+  mut on_file: F,
+) -> Result<FolderStats>
+where
+  C: Fn() -> bool,
+  F: FnMut(FileHits),
+{
+  let matcher = match query {
+    FolderQuery::Text(q) => Matcher::Text(q, q.compile()?),
+    FolderQuery::JsonPath { path } => Matcher::Path(JsonPath::parse(path)?),
+  };
+  let mut stats = FolderStats::default();
+  for path in files::walk_json_files(root, max_size) {
+    if cancelled() {
+      stats.cancelled = true;
+      break;
+    }
+    stats.files += 1;
+    match search_file(&path, &matcher) {
+      Ok(hits) if hits.is_empty() => (),
+      Ok(hits) => {
+        stats.matched_files += 1;
+        stats.hits += hits.len();
+        on_file(FileHits {
+          path: path.to_string_lossy().into(),
+          hits,
+        });
+      }
+      Err(_) => stats.errors += 1,
+    }
+  }
+  Ok(stats)
+}
+
+fn search_file(path: &Path, matcher: &Matcher) -> Result<Vec<Hit>> {
+  let mut hits = vec![];
+  for source in files::sources(path, true)? {
+    let found = match matcher {
+      Matcher::Text(query, re) => find(&source.text, query, re)?,
+      Matcher::Path(path) => search_path_text(&source.text, path)?,
+    };
+    hits.extend(found.into_iter().map(|mut hit| {
+      hit.pointer = format!("{}{}", source.prefix, hit.pointer);
+      hit.line += source.line_offset;
+      hit
+    }));
+  }
+  Ok(hits)
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct Replaced {
+  pub json: Value,
+  pub count: usize,
+}
+
+// Replace every match, keys keep unique and numbers must stay valid numbers
+pub fn replace(doc: Value, query: &SearchQuery, replacement: &str) -> Result<Replaced> {
+  let mut replacer = Replacer {
+    re: query.compile()?,
+    query,
+    replacement,
+    count: 0,
+  };
+  let root = query.pointer.clone().unwrap_or_default();
+  let doc = super::apply_at(doc, Some(&root), |v| replacer.walk(v, &root))?;
+  Ok(Replaced {
+    json: doc,
+    count: replacer.count,
+  })
+}
+
+struct Replacer<'a> {
+  re: Regex,
+  query: &'a SearchQuery,
+  replacement: &'a str,
+  count: usize,
+}
+
+impl<'a> Replacer<'a> {
+  fn replace_str(&mut self, text: &str) -> Option<String> {
+    let n = self.re.find_iter(text).count();
+    if n == 0 {
+      return None;
+    }
+    self.count += n;
+    let out = if self.query.regex {
+      self.re.replace_all(text, self.replacement)
+    } else {
+      self.re.replace_all(text, NoExpand(self.replacement))
+    };
+    Some(out.into_owned())
+  }
+
+  fn walk(&mut self, value: Value, pointer: &str) -> Result<Value> {
+    Ok(match value {
+      Value::Object(map) => {
+        let mut out = Map::new();
+        for (k, v) in map {
+          let child = format!("{}/{}", pointer, escape_token(&k));
+          let v = self.walk(v, &child)?;
+          let k = match self.query.has(Target::Key) {
+            true => self.replace_str(&k).unwrap_or(k),
+            false => k,
+          };
+          if out.contains_key(&k) {
+            bail!("{}: key `{}` would be duplicated", pointer, k);
+          }
+          out.insert(k, v);
+        }
+        Value::Object(out)
+      }
+      Value::Array(arr) => Value::Array(
+        arr
+          .into_iter()
+          .enumerate()
+          .map(|(i, v)| self.walk(v, &format!("{}/{}", pointer, i)))
+          .collect::<Result<_>>()?,
+      ),
+      Value::String(s) if self.query.has(Target::String) => {
+        Value::String(self.replace_str(&s).unwrap_or(s))
+      }
+      Value::Number(n) if self.query.has(Target::Number) => {
+        match self.replace_str(&n.to_string()) {
+          // numbers must stay numbers, otherwise the replacement is rejected
+          Some(text) => Value::Number(
+            serde_json::from_str::<Number>(&text)
+              .map_err(|_| anyhow!("{}: `{}` is not a valid number", pointer, text))?,
+          ),
+          None => Value::Number(n),
+        }
+      }
+      v => v,
+    })
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use serde_json::json;
+
+  fn query(pattern: &str) -> SearchQuery {
+    SearchQuery {
+      pattern: pattern.into(),
+      regex: false,
+      case_sensitive: false,
+      targets: all_targets(),
+      pointer: None,
+    }
+  }
+
+  const TEXT: &str =
+    "{\n  \"name\": \"Alice\",\n  \"alias\": [\"ali\", 42],\n  \"n\": {\"ALI\": 421}\n}";
+
+  #[test]
+  fn searches_keys_strings_and_numbers() {
+    let hits = search_text(TEXT, &query("ali")).unwrap();
+    let found: Vec<(&str, Target)> = hits
+      .iter()
+      .map(|h| (h.pointer.as_str(), h.target))
+      .collect();
+    assert_eq!(
+      found,
+      [
+        ("/name", Target::String),
+        ("/alias", Target::Key),
+        ("/alias/0", Target::String),
+        ("/n/ALI", Target::Key),
+      ]
+    );
+    assert_eq!((hits[0].line, hits[0].column, hits[0].start), (2, 11, 0));
+
+    let numbers = search_text(TEXT, &query("42")).unwrap();
+    assert_eq!(numbers.len(), 2);
+    assert!(numbers.iter().all(|h| h.target == Target::Number));
+  }
+
+  #[test]
+  fn case_scope_and_targets() {
+    let mut q = query("ali");
+    q.case_sensitive = true;
+    assert_eq!(search_text(TEXT, &q).unwrap().len(), 2);
+    q.case_sensitive = false;
+    q.pointer = Some("/n".into());
+    assert_eq!(search_text(TEXT, &q).unwrap()[0].pointer, "/n/ALI");
+    // `/na` is not below `/n`
+    q.pointer = Some("/na".into());
+    assert!(search_text(TEXT, &q).unwrap().is_empty());
+    q.pointer = None;
+    q.targets = vec![Target::Key];
+    assert_eq!(search_text(TEXT, &q).unwrap().len(), 2);
+  }
+
+  #[test]
+  fn invalid_patterns() {
+    assert!(query("").compile().is_err());
+    let mut q = query("(");
+    assert!(q.compile().is_ok());
+    q.regex = true;
+    assert!(q.compile().is_err());
+  }
+
+  #[test]
+  fn json_path_hits() {
+    let path = JsonPath::parse("$.alias[*]").unwrap();
+    let hits = search_path_text(TEXT, &path).unwrap();
+    let found: Vec<(&str, &str)> = hits
+      .iter()
+      .map(|h| (h.pointer.as_str(), h.text.as_str()))
+      .collect();
+    assert_eq!(found, [("/alias/0", "ali"), ("/alias/1", "42")]);
+  }
+
+  #[test]
+  fn replace_literal_and_regex() {
+    let doc = json!({ "first_name": "a-b", "list": ["a"] });
+    let replaced = replace(doc.clone(), &query("a"), "$1").unwrap();
+    assert_eq!(replaced.count, 3);
+    assert_eq!(
+      replaced.json,
+      json!({ "first_n$1me": "$1-b", "list": ["$1"] })
+    );
+
+    let mut q = query(r"(\w)-(\w)");
+    q.regex = true;
+    let replaced = replace(doc, &q, "$2-$1").unwrap();
+    assert_eq!(replaced.json["first_name"], json!("b-a"));
+    assert_eq!(replaced.count, 1);
+  }
+
+  #[test]
+  fn replace_keeps_documents_valid() {
+    let doc = json!({ "ab": 1, "ac": 2 });
+    let mut q = query("[bc]");
+    q.regex = true;
+    assert!(replace(doc, &q, "x").is_err());
+
+    let mut q = query("1");
+    q.targets = vec![Target::Number];
+    assert_eq!(
+      replace(json!([1, 21]), &q, "3").unwrap().json,
+      json!([3, 23])
+    );
+    assert!(replace(json!([1]), &q, "x").is_err());
+  }
+
+  #[test]
+  fn replace_below_pointer() {
+    let mut q = query("a");
+    q.pointer = Some("/b".into());
+    let replaced = replace(json!({ "a": "a", "b": ["a"] }), &q, "z").unwrap();
+    assert_eq!(replaced.json, json!({ "a": "a", "b": ["z"] }));
+  }
+
+  #[test]
+  fn search_and_replace_share_the_scope() {
+    let text = r#"{"alias": {"alias": "ali", "x": "y"}}"#;
+    let mut q = query("ali");
+    q.pointer = Some("/alias".into());
+    let hits = search_text(text, &q).unwrap();
+    let found: Vec<(&str, Target)> = hits
+      .iter()
+      .map(|h| (h.pointer.as_str(), h.target))
+      .collect();
+    assert_eq!(
+      found,
+      [
+        ("/alias/alias", Target::Key),
+        ("/alias/alias", Target::String)
+      ]
+    );
+    let replaced = replace(serde_json::from_str(text).unwrap(), &q, "z").unwrap();
+    assert_eq!(replaced.count, hits.len());
+    assert_eq!(replaced.json, json!({ "alias": { "zas": "z", "x": "y" } }));
+  }
+
+  #[test]
+  fn numbers_match_as_written() {
+    let hits = search_text("[1E5, -2.50e-3, 100000]", &query("e")).unwrap();
+    let texts: Vec<&str> = hits.iter().map(|h| h.text.as_str()).collect();
+    assert_eq!(texts, ["1E5", "-2.50e-3"]);
+    assert!(search_text("[1E5]", &query("100000.0")).unwrap().is_empty());
+  }
+}
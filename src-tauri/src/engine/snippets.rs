@@ -0,0 +1,48 @@
+use anyhow::Result;
+use serde::Serialize;
+use serde_json::Value;
+use std::{fs, path::Path};
+
+use super::files;
+
+// A snippet is a plain JSON file `<dir>/<name>.json`, so it opens like any document
+#[derive(Serialize, Debug, Clone)]
+pub struct Snippet {
+  pub name: String,
+  pub path: String,
+}
+
+pub fn list(dir: &Path) -> Result<Vec<Snippet>> {
+  if !dir.is_dir() {
+    return Ok(vec![]);
+  }
+  let mut snippets = vec![];
+  for entry in fs::read_dir(dir)? {
+    let path = entry?.path();
+    if path.extension().is_some_and(|ext| ext == "json") {
+      snippets.push(Snippet {
+        name: path
+          .file_stem()
+          .map(|name| name.to_string_lossy().into())
+          .unwrap_or_default(),
+        path: path.to_string_lossy().into(),
+      });
+    }
+  }
+  snippets.sort_by(|a, b| a.name.cmp(&b.name));
+  Ok(snippets)
+}
+
+pub fn save(dir: &Path, name: &str, json: &Value) -> Result<Snippet> {
+  fs::create_dir_all(dir)?;
+  let path = files::named_file(dir, name)?;
+  files::write_atomic(&path, &serde_json::to_string_pretty(json)?)?;
+  Ok(Snippet {
+    name: name.into(),
+    path: path.to_string_lossy().into(),
+  })
+}
+
+pub fn delete(dir: &Path, name: &str) -> Result<()> {
+  Ok(fs::remove_file(files::named_file(dir, name)?)?)
+}
@@ -0,0 +1,293 @@
+use anyhow::{bail, Result};
+use serde::Serialize;
+use std::collections::HashSet;
+
+use super::escape_token;
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Position {
+  // byte offset in the source text
+  pub offset: usize,
+  // 1-based
+  pub line: usize,
+  // 1-based, counted in characters
+  pub column: usize,
+}
+
+// A value in the source text, `key` is set for object members
+#[derive(Serialize, Debug, Clone)]
+pub struct Node {
+  pub pointer: String,
+  pub key: Option<Position>,
+  pub value: Position,
+  // byte offset just past the value
+  pub end: usize,
+}
+
+// Positions of every key and value of a JSON text, in document order.
+// The text is expected to be valid JSON (parse it with serde_json first for good error messages).
+pub fn index(text: &str) -> Result<Vec<Node>> {
+  Ok(scan(text)?.nodes)
+}
+
+// Pointer of the innermost value whose text (with its key) covers `from..to`, `None` when that
+// is the whole document. Offsets count UTF-16 units like the text editor of the web view does.
+pub fn pointer_at(text: &str, from: usize, to: usize) -> Result<Option<String>> {
+  let byte_offset = |units: usize| {
+    let mut counted = 0;
+    text
+      .char_indices()
+      .find(|(_, c)| {
+        counted += c.len_utf16();
+        counted > units
+      })
+      .map_or(text.len(), |(i, _)| i)
+  };
+  let (from, to) = (byte_offset(from), byte_offset(to));
+  // nested values come after their parent, the last match is the innermost
+  let node = index(text)?.into_iter().rev().find(|node| {
+    let start = node.key.unwrap_or(node.value).offset;
+    start <= from && to <= node.end
+  });
+  Ok(
+    node
+      .map(|node| node.pointer)
+      .filter(|pointer| !pointer.is_empty()),
+  )
+}
+
+// Members whose key already appeared earlier in the same object, serde_json silently keeps the last one
+pub fn duplicate_keys(text: &str) -> Result<Vec<Node>> {
+  Ok(scan(text)?.duplicates)
+}
+
+fn scan(text: &str) -> Result<Scanner<'_>> {
+  let mut scanner = Scanner {
+    text,
+    bytes: text.as_bytes(),
+    pos: 0,
+    line: 1,
+    line_start: 0,
+    nodes: vec![],
+    duplicates: vec![],
+  };
+  scanner.value(String::new(), None)?;
+  Ok(scanner)
+}
+
+struct Scanner<'a> {
+  text: &'a str,
+  bytes: &'a [u8],
+  pos: usize,
+  line: usize,
+  line_start: usize,
+  nodes: Vec<Node>,
+  duplicates: Vec<Node>,
+}
+
+impl<'a> Scanner<'a> {
+  fn position(&self) -> Position {
+    Position {
+      offset: self.pos,
+      line: self.line,
+      column: self.text[self.line_start..self.pos].chars().count() + 1,
+    }
+  }
+
+  fn peek(&self) -> Option<u8> {
+    self.bytes.get(self.pos).copied()
+  }
+
+  fn skip_ws(&mut self) {
+    while let Some(b) = self.peek() {
+      match b {
+        b'\n' => {
+          self.pos += 1;
+          self.line += 1;
+          self.line_start = self.pos;
+        }
+        b' ' | b'\t' | b'\r' => self.pos += 1,
+        _ => break,
+      }
+    }
+  }
+
+  fn expect(&mut self, byte: u8) -> Result<()> {
+    self.skip_ws();
+    if self.peek() != Some(byte) {
+      let at = self.position();
+      bail!(
+        "expected `{}` at line {} column {}",
+        byte as char,
+        at.line,
+        at.column
+      );
+    }
+    self.pos += 1;
+    Ok(())
+  }
+
+  fn value(&mut self, pointer: String, key: Option<Position>) -> Result<()> {
+    self.skip_ws();
+    let value = self.position();
+    let node = self.nodes.len();
+    self.nodes.push(Node {
+      pointer: pointer.clone(),
+      key,
+      value,
+      end: value.offset,
+    });
+    self.contents(pointer, value)?;
+    self.nodes[node].end = self.pos;
+    Ok(())
+  }
+
+  fn contents(&mut self, pointer: String, value: Position) -> Result<()> {
+    match self.peek() {
+      Some(b'{') => {
+        self.pos += 1;
+        self.skip_ws();
+        if self.peek() == Some(b'}') {
+          self.pos += 1;
+          return Ok(());
+        }
+        let mut seen = HashSet::new();
+        loop {
+          self.skip_ws();
+          let key = self.position();
+          let name = self.string()?;
+          self.expect(b':')?;
+          let member = format!("{}/{}", pointer, escape_token(&name));
+          let duplicate = !seen.insert(name);
+          let node = self.nodes.len();
+          self.value(member, Some(key))?;
+          if duplicate {
+            self.duplicates.push(self.nodes[node].clone());
+          }
+          self.skip_ws();
+          match self.peek() {
+            Some(b',') => self.pos += 1,
+            _ => return self.expect(b'}'),
+          }
+        }
+      }
+      Some(b'[') => {
+        self.pos += 1;
+        self.skip_ws();
+        if self.peek() == Some(b']') {
+          self.pos += 1;
+          return Ok(());
+        }
+        let mut index = 0;
+        loop {
+          self.value(format!("{}/{}", pointer, index), None)?;
+          index += 1;
+          self.skip_ws();
+          match self.peek() {
+            Some(b',') => self.pos += 1,
+            _ => return self.expect(b']'),
+          }
+        }
+      }
+      Some(b'"') => self.string().map(|_| ()),
+      Some(_) => {
+        // number, true, false, null
+        let start = self.pos;
+        while let Some(b) = self.peek() {
+          if b.is_ascii_alphanumeric() || matches!(b, b'-' | b'+' | b'.') {
+            self.pos += 1;
+          } else {
+            break;
+          }
+        }
+        if start == self.pos {
+          bail!(
+            "unexpected character at line {} column {}",
+            value.line,
+            value.column
+          );
+        }
+        Ok(())
+      }
+      None => bail!("unexpected end of input"),
+    }
+  }
+
+  fn string(&mut self) -> Result<String> {
+    let start = self.pos;
+    self.expect(b'"')?;
+    while let Some(b) = self.peek() {
+      self.pos += 1;
+      match b {
+        b'\\' => self.pos += 1,
+        b'"' => return Ok(serde_json::from_str(&self.text[start..self.pos])?),
+        _ => (),
+      }
+    }
+    bail!("unterminated string")
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn positions_of_keys_and_values() {
+    let text = "{\n  \"a\": [1, \"é\"],\n  \"b/c\": {\"d\": true}\n}";
+    let nodes = index(text).unwrap();
+    let pointers: Vec<&str> = nodes.iter().map(|n| n.pointer.as_str()).collect();
+    assert_eq!(pointers, ["", "/a", "/a/0", "/a/1", "/b~1c", "/b~1c/d"]);
+    let a = &nodes[1];
+    assert_eq!(a.key.map(|p| (p.line, p.column)), Some((2, 3)));
+    assert_eq!((a.value.line, a.value.column), (2, 8));
+    assert_eq!(nodes[3].key, None);
+    let d = &nodes[5];
+    assert_eq!((d.value.line, d.value.column), (3, 16));
+    assert_eq!(&text[d.value.offset..d.value.offset + 4], "true");
+  }
+
+  #[test]
+  fn columns_count_characters() {
+    let nodes = index(r#"{"é": "ü", "x": 1}"#).unwrap();
+    assert_eq!(nodes[2].key.unwrap().column, 12);
+  }
+
+  #[test]
+  fn escaped_keys_are_decoded() {
+    let nodes = index(r#"{"a\"b": 1, "A~": 2}"#).unwrap();
+    assert_eq!(nodes[1].pointer, "/a\"b");
+    assert_eq!(nodes[2].pointer, "/A~0");
+  }
+
+  #[test]
+  fn finds_duplicate_keys() {
+    let dups = duplicate_keys(r#"{"a": 1, "b": {"a": 2, "a": 3}, "a": 4}"#).unwrap();
+    let pointers: Vec<&str> = dups.iter().map(|n| n.pointer.as_str()).collect();
+    assert_eq!(pointers, ["/b/a", "/a"]);
+    assert!(duplicate_keys("[1, 2]").unwrap().is_empty());
+  }
+
+  #[test]
+  fn pointers_around_selections() {
+    let text = "{\n  \"é\": [1, 22],\n  \"b\": {\"c\": true}\n}";
+    let at = |from, to| pointer_at(text, from, to).unwrap();
+    let offset = |part: &str| {
+      text.encode_utf16().count() - text[text.find(part).unwrap()..].encode_utf16().count()
+    };
+    let two = offset("22");
+    assert_eq!(at(two, two), Some("/é/1".into()));
+    assert_eq!(at(two + 2, two + 2), Some("/é/1".into()));
+    assert_eq!(at(two - 1, two), Some("/é".into()));
+    assert_eq!(at(offset("\"c\""), offset("true") + 4), Some("/b/c".into()));
+    assert_eq!(at(offset("1,"), offset("true")), None);
+    assert_eq!(at(0, 0), None);
+    assert!(pointer_at("[1,", 0, 0).is_err());
+  }
+
+  #[test]
+  fn rejects_broken_text() {
+    assert!(index("{\"a\": }").is_err());
+    assert!(index("[1, 2").is_err());
+  }
+}
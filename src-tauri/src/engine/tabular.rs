@@ -0,0 +1,421 @@
+use anyhow::{anyhow, bail, Result};
+use calamine::{open_workbook_auto, Data, Reader};
+use rust_xlsxwriter::{Format, Workbook};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::{collections::HashSet, path::Path};
+
+use super::transform::{self, PathStyle};
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum TableFormat {
+  Csv,
+  Tsv,
+  Xlsx,
+}
+
+impl TableFormat {
+  pub fn from_path(path: &Path) -> Option<Self> {
+    let ext = path.extension()?.to_str()?.to_lowercase();
+    match ext.as_str() {
+      "csv" => Some(TableFormat::Csv),
+      "tsv" | "tab" => Some(TableFormat::Tsv),
+      "xlsx" | "xlsm" | "xls" | "ods" => Some(TableFormat::Xlsx),
+      _ => None,
+    }
+  }
+}
+
+// One exported column: `key` is the dotted column, `header` the title written to the file
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Column {
+  pub key: String,
+  pub header: Option<String>,
+}
+
+impl Column {
+  pub fn title(&self) -> &str {
+    self.header.as_deref().unwrap_or(&self.key)
+  }
+}
+
+pub fn rows_at<'a>(doc: &'a Value, pointer: Option<&str>) -> Result<&'a Vec<Value>> {
+  let pointer = pointer.unwrap_or_default();
+  doc
+    .pointer(pointer)
+    .ok_or_else(|| anyhow!("pointer not found: {}", pointer))?
+    .as_array()
+    .ok_or_else(|| anyhow!("value at `{}` is not an array", pointer))
+}
+
+// Nested objects become dotted columns, arrays stay as a single JSON cell
+pub fn flatten_row(item: &Value) -> Map<String, Value> {
+  fn walk(value: &Value, path: String, out: &mut Map<String, Value>) {
+    match value {
+      Value::Object(map) if !map.is_empty() => {
+        for (k, v) in map {
+          let key = if path.is_empty() {
+            k.clone()
+          } else {
+            format!("{}.{}", path, k)
+          };
+          walk(v, key, out);
+        }
+      }
+      _ => {
+        out.insert(path, value.clone());
+      }
+    }
+  }
+  let mut out = Map::new();
+  match item {
+    Value::Object(_) => walk(item, String::new(), &mut out),
+    _ => {
+      out.insert("value".into(), item.clone());
+    }
+  }
+  out
+}
+
+// Union of all columns in first-seen order
+pub fn columns(rows: &[Value]) -> Vec<String> {
+  let mut columns: Vec<String> = vec![];
+  for row in rows {
+    for key in flatten_row(row).keys() {
+      if !columns.contains(key) {
+        columns.push(key.clone());
+      }
+    }
+  }
+  columns
+}
+
+pub fn export(
+  doc: &Value,
+  pointer: Option<&str>,
+  columns: Option<Vec<Column>>,
+  format: TableFormat,
+  path: &Path,
+) -> Result<usize> {
+  let rows = rows_at(doc, pointer)?;
+  let columns = columns.unwrap_or_else(|| {
+    self::columns(rows)
+      .into_iter()
+      .map(|key| Column { key, header: None })
+      .collect()
+  });
+  let flat: Vec<Map<String, Value>> = rows.iter().map(flatten_row).collect();
+  match format {
+    TableFormat::Csv => write_delimited(&flat, &columns, b',', path)?,
+    TableFormat::Tsv => write_delimited(&flat, &columns, b'\t', path)?,
+    TableFormat::Xlsx => write_xlsx(&flat, &columns, path)?,
+  }
+  Ok(flat.len())
+}
+
+fn cell_text(value: Option<&Value>) -> String {
+  match value {
+    None | Some(Value::Null) => String::new(),
+    Some(Value::String(s)) => s.clone(),
+    Some(v) => v.to_string(),
+  }
+}
+
+fn write_delimited(
+  rows: &[Map<String, Value>],
+  columns: &[Column],
+  delimiter: u8,
+  path: &Path,
+) -> Result<()> {
+  let mut writer = csv::WriterBuilder::new()
+    .delimiter(delimiter)
+    .from_path(path)?;
+  writer.write_record(columns.iter().map(Column::title))?;
+  for row in rows {
+    writer.write_record(columns.iter().map(|c| cell_text(row.get(&c.key))))?;
+  }
+  writer.flush()?;
+  Ok(())
+}
+
+fn write_xlsx(rows: &[Map<String, Value>], columns: &[Column], path: &Path) -> Result<()> {
+  let mut workbook = Workbook::new();
+  let sheet = workbook.add_worksheet();
+  let bold = Format::new().set_bold();
+  for (c, column) in columns.iter().enumerate() {
+    sheet.write_string_with_format(0, c as u16, column.title(), &bold)?;
+  }
+  for (r, row) in rows.iter().enumerate() {
+    let r = r as u32 + 1;
+    for (c, column) in columns.iter().enumerate() {
+      let c = c as u16;
+      match row.get(&column.key) {
+        None | Some(Value::Null) => {}
+        Some(Value::Bool(b)) => {
+          sheet.write_boolean(r, c, *b)?;
+        }
+        Some(Value::Number(n)) => {
+          sheet.write_number(r, c, n.as_f64().unwrap_or_default())?;
+        }
+        v => {
+          sheet.write_string(r, c, cell_text(v))?;
+        }
+      }
+    }
+  }
+  sheet.set_freeze_panes(1, 0)?;
+  workbook.save(path)?;
+  Ok(())
+}
+
+// --- Import
+
+pub fn import(path: &Path, sheet: Option<&str>) -> Result<Value> {
+  let format = TableFormat::from_path(path)
+    .ok_or_else(|| anyhow!("unsupported file type: {}", path.display()))?;
+  let table = match format {
+    TableFormat::Csv => read_delimited(path, b',')?,
+    TableFormat::Tsv => read_delimited(path, b'\t')?,
+    TableFormat::Xlsx => read_workbook(path, sheet)?,
+  };
+  let mut table = table.into_iter();
+  let headers: Vec<String> = match table.next() {
+    Some(row) => headers(&row)?,
+    None => return Ok(Value::Array(vec![])),
+  };
+  let rows = table
+    .map(|cells| {
+      let map: Map<String, Value> = headers
+        .iter()
+        .cloned()
+        .zip(cells)
+        .filter(|(_, v)| !v.is_null())
+        .collect();
+      transform::unflatten(Value::Object(map), PathStyle::Dotted)
+    })
+    .collect::<Result<Vec<_>>>()?;
+  Ok(Value::Array(rows))
+}
+
+// Keys of the header row: blank cells are named after their column (`column_3`),
+// a name used twice would leave only one of its columns
+fn headers(row: &[Value]) -> Result<Vec<String>> {
+  let mut seen = HashSet::new();
+  row
+    .iter()
+    .enumerate()
+    .map(|(i, cell)| {
+      let mut name = cell_text(Some(cell));
+      if name.trim().is_empty() {
+        name = format!("column_{}", i + 1);
+      }
+      if !seen.insert(name.clone()) {
+        bail!("column `{}` appears twice in the header row", name);
+      }
+      Ok(name)
+    })
+    .collect()
+}
+
+fn read_delimited(path: &Path, delimiter: u8) -> Result<Vec<Vec<Value>>> {
+  let mut reader = csv::ReaderBuilder::new()
+    .delimiter(delimiter)
+    .has_headers(false)
+    .flexible(true)
+    .from_path(path)?;
+  let mut table = vec![];
+  for record in reader.records() {
+    table.push(record?.iter().map(infer).collect());
+  }
+  Ok(table)
+}
+
+fn read_workbook(path: &Path, sheet: Option<&str>) -> Result<Vec<Vec<Value>>> {
+  let mut workbook = open_workbook_auto(path)?;
+  let name = match sheet {
+    Some(name) => name.to_string(),
+    None => workbook
+      .sheet_names()
+      .first()
+      .cloned()
+      .ok_or_else(|| anyhow!("workbook has no sheets"))?,
+  };
+  let range = workbook.worksheet_range(&name)?;
+  Ok(
+    range
+      .rows()
+      .map(|row| row.iter().map(cell_value).collect())
+      .collect(),
+  )
+}
+
+fn cell_value(cell: &Data) -> Value {
+  match cell {
+    Data::Empty | Data::Error(_) => Value::Null,
+    Data::Bool(b) => Value::Bool(*b),
+    Data::Int(i) => Value::from(*i),
+    // spreadsheets store every number as a float, give whole numbers back as integers
+    Data::Float(f) if f.fract() == 0.0 && f.abs() < 9_007_199_254_740_992.0 => {
+      Value::from(*f as i64)
+    }
+    Data::Float(f) => Value::from(*f),
+    Data::String(s) => infer(s),
+    v => Value::String(v.to_string()),
+  }
+}
+
+// Cell text -> JSON value: empty cells are dropped, numbers/booleans and JSON arrays/objects are parsed
+fn infer(text: &str) -> Value {
+  if text.is_empty() {
+    return Value::Null;
+  }
+  if text.starts_with('[') || text.starts_with('{') {
+    if let Ok(v) = serde_json::from_str(text) {
+      return v;
+    }
+  }
+  if text == "null" {
+    return Value::Null;
+  }
+  transform::coerce_str(text).unwrap_or_else(|| Value::String(text.into()))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::engine::TempDir;
+  use serde_json::json;
+
+  fn rows() -> Value {
+    json!({ "data": [
+      { "id": 1, "user": { "name": "Ada", "langs": ["en", "fr"] }, "active": true },
+      { "id": 2, "user": { "name": "Linus, Jr." }, "note": null },
+      "plain"
+    ]})
+  }
+
+  #[test]
+  fn flattens_nested_objects_only() {
+    let row = flatten_row(&rows()["data"][0]);
+    assert_eq!(row["user.name"], json!("Ada"));
+    assert_eq!(row["user.langs"], json!(["en", "fr"]));
+    assert_eq!(
+      flatten_row(&json!(3)),
+      Map::from_iter([("value".into(), json!(3))])
+    );
+  }
+
+  #[test]
+  fn columns_in_first_seen_order() {
+    let doc = rows();
+    let rows = rows_at(&doc, Some("/data")).unwrap();
+    assert_eq!(
+      columns(rows),
+      ["id", "user.name", "user.langs", "active", "note", "value"]
+    );
+    assert!(rows_at(&doc, Some("/missing")).is_err());
+    assert!(rows_at(&doc, Some("/data/0")).is_err());
+  }
+
+  #[test]
+  fn csv_round_trip() {
+    let dir = TempDir::new("round-csv");
+    let path = dir.join("round.csv");
+    let doc = json!([
+      { "id": 1, "user": { "name": "Ada", "langs": ["en"] }, "zip": "007" },
+      { "id": 2, "user": { "name": "Linus, Jr." }, "ok": false }
+    ]);
+    assert_eq!(
+      export(&doc, None, None, TableFormat::Csv, &path).unwrap(),
+      2
+    );
+    let text = std::fs::read_to_string(&path).unwrap();
+    assert!(text.starts_with("id,user.name,user.langs,zip,ok\n"));
+    assert!(text.contains("\"Linus, Jr.\""));
+    let imported = import(&path, None).unwrap();
+    assert_eq!(imported, doc);
+  }
+
+  #[test]
+  fn names_blank_header_cells() {
+    let dir = TempDir::new("blank-csv");
+    let path = dir.join("blank.csv");
+    std::fs::write(&path, "id,,name\n1,x,Ada\n").unwrap();
+    let imported = import(&path, None).unwrap();
+    assert_eq!(
+      imported,
+      json!([{ "id": 1, "column_2": "x", "name": "Ada" }])
+    );
+  }
+
+  #[test]
+  fn refuses_repeated_header_names() {
+    let dir = TempDir::new("repeated-csv");
+    let path = dir.join("repeated.csv");
+    std::fs::write(&path, "id,name,id\n1,Ada,2\n").unwrap();
+    let err = import(&path, None).unwrap_err();
+    assert_eq!(
+      err.to_string(),
+      "column `id` appears twice in the header row"
+    );
+  }
+
+  #[test]
+  fn tsv_with_chosen_columns() {
+    let dir = TempDir::new("columns-tsv");
+    let path = dir.join("columns.tsv");
+    let doc = json!([{ "a": 1, "b": { "c": "x" } }]);
+    let columns = vec![
+      Column {
+        key: "b.c".into(),
+        header: Some("C".into()),
+      },
+      Column {
+        key: "missing".into(),
+        header: None,
+      },
+    ];
+    export(&doc, None, Some(columns), TableFormat::Tsv, &path).unwrap();
+    let text = std::fs::read_to_string(&path).unwrap();
+    assert_eq!(text, "C\tmissing\nx\t\n");
+  }
+
+  #[test]
+  fn xlsx_round_trip() {
+    let dir = TempDir::new("round-xlsx");
+    let path = dir.join("round.xlsx");
+    let doc = json!([{ "n": 1, "f": 1.5, "b": true, "s": "text", "o": { "k": "v" } }]);
+    export(&doc, None, None, TableFormat::Xlsx, &path).unwrap();
+    let imported = import(&path, None).unwrap();
+    assert_eq!(imported, doc);
+  }
+
+  #[test]
+  fn infers_cell_types() {
+    assert_eq!(infer(""), Value::Null);
+    assert_eq!(infer("null"), Value::Null);
+    assert_eq!(infer("12"), json!(12));
+    assert_eq!(infer("true"), json!(true));
+    assert_eq!(infer("[1,2]"), json!([1, 2]));
+    assert_eq!(infer("[not json"), json!("[not json"));
+    assert_eq!(infer("0012"), json!("0012"));
+  }
+
+  #[test]
+  fn format_from_extension() {
+    assert_eq!(
+      TableFormat::from_path(Path::new("a.CSV")),
+      Some(TableFormat::Csv)
+    );
+    assert_eq!(
+      TableFormat::from_path(Path::new("a.tab")),
+      Some(TableFormat::Tsv)
+    );
+    assert_eq!(
+      TableFormat::from_path(Path::new("a.ods")),
+      Some(TableFormat::Xlsx)
+    );
+    assert_eq!(TableFormat::from_path(Path::new("a.json")), None);
+  }
+}
@@ -0,0 +1,597 @@
+use anyhow::{anyhow, bail, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Number, Value};
+use std::{cmp::Ordering, collections::HashSet};
+
+use super::escape_token;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum PathStyle {
+  // a.b.0.c
+  Dotted,
+  // a.b[0].c
+  Bracket,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum KeyCase {
+  Camel,
+  Snake,
+  Kebab,
+  Pascal,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Transform {
+  Flatten {
+    style: PathStyle,
+  },
+  Unflatten {
+    style: PathStyle,
+  },
+  RenameKeys {
+    case: KeyCase,
+  },
+  StripEmpty {
+    // only drop `null`, keep "", [] and {}
+    #[serde(default)]
+    nulls_only: bool,
+  },
+  SortArray {
+    // key name or JSON Pointer ("/a/b") inside each item, the item itself when omitted
+    by: Option<String>,
+    #[serde(default)]
+    descending: bool,
+  },
+  DedupeArray {
+    by: Option<String>,
+  },
+  CoerceScalars,
+}
+
+impl Transform {
+  pub fn apply(&self, value: Value) -> Result<Value> {
+    Ok(match self {
+      Transform::Flatten { style } => flatten(&value, *style),
+      Transform::Unflatten { style } => unflatten(value, *style)?,
+      Transform::RenameKeys { case } => rename_keys(value, *case)?,
+      Transform::StripEmpty { nulls_only } => strip_empty(value, *nulls_only),
+      Transform::SortArray { by, descending } => sort_array(value, by.as_deref(), *descending)?,
+      Transform::DedupeArray { by } => dedupe_array(value, by.as_deref())?,
+      Transform::CoerceScalars => coerce_scalars(value),
+    })
+  }
+}
+
+// --- Flatten / Unflatten
+
+#[derive(Debug, Clone, PartialEq)]
+enum Segment {
+  Key(String),
+  Index(usize),
+}
+
+pub fn flatten(value: &Value, style: PathStyle) -> Value {
+  let mut out = Map::new();
+  flatten_into(value, String::new(), style, &mut out);
+  Value::Object(out)
+}
+
+fn flatten_into(value: &Value, path: String, style: PathStyle, out: &mut Map<String, Value>) {
+  match value {
+    Value::Object(map) if !map.is_empty() => {
+      for (k, v) in map {
+        flatten_into(v, join_key(&path, k, style), style, out);
+      }
+    }
+    Value::Array(arr) if !arr.is_empty() => {
+      for (i, v) in arr.iter().enumerate() {
+        flatten_into(v, join_index(&path, i, style), style, out);
+      }
+    }
+    _ => {
+      out.insert(path, value.clone());
+    }
+  }
+}
+
+fn join_key(path: &str, key: &str, style: PathStyle) -> String {
+  if style == PathStyle::Bracket && key.contains(['.', '[', ']', '"']) {
+    return format!("{}[{}]", path, Value::String(key.into()));
+  }
+  if path.is_empty() {
+    key.into()
+  } else {
+    format!("{}.{}", path, key)
+  }
+}
+
+fn join_index(path: &str, index: usize, style: PathStyle) -> String {
+  match style {
+    PathStyle::Bracket => format!("{}[{}]", path, index),
+    PathStyle::Dotted if path.is_empty() => index.to_string(),
+    PathStyle::Dotted => format!("{}.{}", path, index),
+  }
+}
+
+pub fn unflatten(value: Value, style: PathStyle) -> Result<Value> {
+  let map = match value {
+    Value::Object(map) => map,
+    _ => bail!("unflatten expects an object of paths"),
+  };
+  let mut root = Value::Null;
+  for (path, v) in map {
+    let segments = parse_path(&path, style)?;
+    insert_path(&mut root, &segments, v).map_err(|e| anyhow!("{}: {}", path, e))?;
+  }
+  if root.is_null() {
+    root = Value::Object(Map::new());
+  }
+  Ok(root)
+}
+
+fn parse_path(path: &str, style: PathStyle) -> Result<Vec<Segment>> {
+  if path.is_empty() {
+    return Ok(vec![]);
+  }
+  match style {
+    PathStyle::Dotted => Ok(path.split('.').map(segment_of).collect()),
+    PathStyle::Bracket => parse_bracket_path(path),
+  }
+}
+
+fn segment_of(part: &str) -> Segment {
+  match part.parse::<usize>() {
+    Ok(i) => Segment::Index(i),
+    Err(_) => Segment::Key(part.into()),
+  }
+}
+
+fn parse_bracket_path(path: &str) -> Result<Vec<Segment>> {
+  let mut segments = vec![];
+  let mut key = String::new();
+  let mut rest = path;
+  while let Some(c) = rest.chars().next() {
+    rest = &rest[c.len_utf8()..];
+    if c != '.' && c != '[' {
+      key.push(c);
+      continue;
+    }
+    if !key.is_empty() {
+      segments.push(Segment::Key(std::mem::take(&mut key)));
+    }
+    if c == '.' {
+      continue;
+    }
+    let (segment, len) = if rest.starts_with('"') {
+      // quoted key: ["a.b"]
+      let mut de = serde_json::Deserializer::from_str(rest).into_iter::<String>();
+      let quoted = de
+        .next()
+        .ok_or_else(|| anyhow!("unterminated key in `{}`", path))??;
+      (Segment::Key(quoted), de.byte_offset())
+    } else {
+      let end = rest.find(']').unwrap_or(rest.len());
+      let index = rest[..end]
+        .parse::<usize>()
+        .map_err(|_| anyhow!("invalid index `{}` in `{}`", &rest[..end], path))?;
+      (Segment::Index(index), end)
+    };
+    rest = rest[len..]
+      .strip_prefix(']')
+      .ok_or_else(|| anyhow!("missing `]` in `{}`", path))?;
+    segments.push(segment);
+  }
+  if !key.is_empty() {
+    segments.push(Segment::Key(key));
+  }
+  Ok(segments)
+}
+
+fn insert_path(root: &mut Value, segments: &[Segment], value: Value) -> Result<()> {
+  let mut cur = root;
+  for segment in segments {
+    // only a path starting at index 0 makes an array, `sales.20240101` is a key
+    if cur.is_null() {
+      *cur = match segment {
+        Segment::Index(0) => Value::Array(vec![]),
+        _ => Value::Object(Map::new()),
+      };
+    }
+    cur = match (cur, segment) {
+      (Value::Object(map), Segment::Key(k)) => map.entry(k.clone()).or_insert(Value::Null),
+      (Value::Object(map), Segment::Index(i)) => map.entry(i.to_string()).or_insert(Value::Null),
+      (Value::Array(arr), Segment::Index(i)) => {
+        let len = arr.len();
+        if *i == len {
+          arr.push(Value::Null);
+        }
+        arr
+          .get_mut(*i)
+          .ok_or_else(|| anyhow!("index {} skips items of an array of {}", i, len))?
+      }
+      _ => bail!("conflicting path"),
+    };
+  }
+  if !cur.is_null() {
+    bail!("duplicate path");
+  }
+  *cur = value;
+  Ok(())
+}
+
+// --- Key case
+
+// Keys that end up with the same name (`userId` and `user_id`) fail instead of losing one value
+pub fn rename_keys(value: Value, case: KeyCase) -> Result<Value> {
+  rename_keys_at(value, case, "")
+}
+
+fn rename_keys_at(value: Value, case: KeyCase, pointer: &str) -> Result<Value> {
+  Ok(match value {
+    Value::Object(map) => {
+      let mut out = Map::new();
+      for (k, v) in map {
+        let v = rename_keys_at(v, case, &format!("{}/{}", pointer, escape_token(&k)))?;
+        let k = convert_case(&k, case);
+        if out.contains_key(&k) {
+          bail!("{}: key `{}` would be duplicated", pointer, k);
+        }
+        out.insert(k, v);
+      }
+      Value::Object(out)
+    }
+    Value::Array(arr) => Value::Array(
+      arr
+        .into_iter()
+        .enumerate()
+        .map(|(i, v)| rename_keys_at(v, case, &format!("{}/{}", pointer, i)))
+        .collect::<Result<_>>()?,
+    ),
+    v => v,
+  })
+}
+
+pub fn convert_case(key: &str, case: KeyCase) -> String {
+  // keep leading markers such as `_id`, `$ref` or `@type`
+  let body = key.trim_start_matches(['_', '$', '@']);
+  let prefix = &key[..key.len() - body.len()];
+  let words = split_words(body);
+  if words.is_empty() {
+    return key.into();
+  }
+  let joined = match case {
+    KeyCase::Snake => lower_words(&words).join("_"),
+    KeyCase::Kebab => lower_words(&words).join("-"),
+    KeyCase::Pascal => words.iter().map(|w| capitalize(w)).collect(),
+    KeyCase::Camel => words
+      .iter()
+      .enumerate()
+      .map(|(i, w)| {
+        if i == 0 {
+          w.to_lowercase()
+        } else {
+          capitalize(w)
+        }
+      })
+      .collect(),
+  };
+  format!("{}{}", prefix, joined)
+}
+
+fn lower_words(words: &[String]) -> Vec<String> {
+  words.iter().map(|w| w.to_lowercase()).collect()
+}
+
+fn capitalize(word: &str) -> String {
+  let mut chars = word.chars();
+  match chars.next() {
+    Some(first) => first
+      .to_uppercase()
+      .chain(chars.flat_map(char::to_lowercase))
+      .collect(),
+    None => String::new(),
+  }
+}
+
+// userID -> [user, ID], HTTPServer -> [HTTP, Server], snake_case / kebab-case -> [snake, case]
+fn split_words(key: &str) -> Vec<String> {
+  let chars: Vec<char> = key.chars().collect();
+  let mut words = vec![];
+  let mut word = String::new();
+  for (i, &c) in chars.iter().enumerate() {
+    if !c.is_alphanumeric() {
+      if !word.is_empty() {
+        words.push(std::mem::take(&mut word));
+      }
+      continue;
+    }
+    if c.is_uppercase() && !word.is_empty() {
+      let prev = chars[i - 1];
+      let next_lower = chars.get(i + 1).is_some_and(|n| n.is_lowercase());
+      if !prev.is_uppercase() || next_lower {
+        words.push(std::mem::take(&mut word));
+      }
+    }
+    word.push(c);
+  }
+  if !word.is_empty() {
+    words.push(word);
+  }
+  words
+}
+
+// --- Cleanup
+
+pub fn strip_empty(value: Value, nulls_only: bool) -> Value {
+  let keep = |v: &Value| match v {
+    Value::Null => false,
+    _ if nulls_only => true,
+    Value::String(s) => !s.is_empty(),
+    Value::Array(a) => !a.is_empty(),
+    Value::Object(o) => !o.is_empty(),
+    _ => true,
+  };
+  match value {
+    Value::Object(map) => Value::Object(
+      map
+        .into_iter()
+        .map(|(k, v)| (k, strip_empty(v, nulls_only)))
+        .filter(|(_, v)| keep(v))
+        .collect(),
+    ),
+    Value::Array(arr) => Value::Array(
+      arr
+        .into_iter()
+        .map(|v| strip_empty(v, nulls_only))
+        .filter(keep)
+        .collect(),
+    ),
+    v => v,
+  }
+}
+
+pub fn sort_array(value: Value, by: Option<&str>, descending: bool) -> Result<Value> {
+  let mut arr = match value {
+    Value::Array(arr) => arr,
+    _ => bail!("sort expects an array"),
+  };
+  arr.sort_by(|a, b| {
+    let ord = compare_values(field(a, by), field(b, by));
+    if descending {
+      ord.reverse()
+    } else {
+      ord
+    }
+  });
+  Ok(Value::Array(arr))
+}
+
+pub fn dedupe_array(value: Value, by: Option<&str>) -> Result<Value> {
+  let arr = match value {
+    Value::Array(arr) => arr,
+    _ => bail!("dedupe expects an array"),
+  };
+  let mut seen = HashSet::new();
+  let arr = arr
+    .into_iter()
+    .filter(|v| seen.insert(field(v, by).to_string()))
+    .collect();
+  Ok(Value::Array(arr))
+}
+
+fn field<'a>(item: &'a Value, by: Option<&str>) -> &'a Value {
+  match by {
+    None | Some("") => item,
+    Some(p) if p.starts_with('/') => item.pointer(p).unwrap_or(&Value::Null),
+    Some(k) => item.get(k).unwrap_or(&Value::Null),
+  }
+}
+
+// null < bool < number < string < array < object
+pub fn compare_values(a: &Value, b: &Value) -> Ordering {
+  fn rank(v: &Value) -> u8 {
+    match v {
+      Value::Null => 0,
+      Value::Bool(_) => 1,
+      Value::Number(_) => 2,
+      Value::String(_) => 3,
+      Value::Array(_) => 4,
+      Value::Object(_) => 5,
+    }
+  }
+  match (a, b) {
+    (Value::Bool(x), Value::Bool(y)) => x.cmp(y),
+    (Value::Number(x), Value::Number(y)) => {
+      let (x, y) = (
+        x.as_f64().unwrap_or_default(),
+        y.as_f64().unwrap_or_default(),
+      );
+      x.partial_cmp(&y).unwrap_or(Ordering::Equal)
+    }
+    (Value::String(x), Value::String(y)) => x.cmp(y),
+    (Value::Array(x), Value::Array(y)) => x.len().cmp(&y.len()),
+    _ => rank(a).cmp(&rank(b)),
+  }
+}
+
+// "42" -> 42, "true" -> true; only lossless conversions are applied so "007" and "1e3" stay strings
+pub fn coerce_scalars(value: Value) -> Value {
+  match value {
+    Value::Object(map) => Value::Object(
+      map
+        .into_iter()
+        .map(|(k, v)| (k, coerce_scalars(v)))
+        .collect(),
+    ),
+    Value::Array(arr) => Value::Array(arr.into_iter().map(coerce_scalars).collect()),
+    Value::String(s) => coerce_str(&s).unwrap_or(Value::String(s)),
+    v => v,
+  }
+}
+
+pub fn coerce_str(s: &str) -> Option<Value> {
+  match s {
+    "true" => Some(Value::Bool(true)),
+    "false" => Some(Value::Bool(false)),
+    _ => match serde_json::from_str::<Number>(s) {
+      Ok(n) if n.to_string() == s => Some(Value::Number(n)),
+      _ => None,
+    },
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::engine::{apply_at, unescape_token};
+  use serde_json::json;
+
+  fn sample() -> Value {
+    json!({
+      "user": { "name": "Ada", "tags": ["a", "b"], "address": { "city": "London" } },
+      "items": [{ "id": 1 }, { "id": 2, "extra": null }],
+      "empty": {},
+      "none": []
+    })
+  }
+
+  #[test]
+  fn flatten_dotted() {
+    let flat = flatten(&sample(), PathStyle::Dotted);
+    assert_eq!(flat["user.name"], json!("Ada"));
+    assert_eq!(flat["user.tags.1"], json!("b"));
+    assert_eq!(flat["items.1.extra"], json!(null));
+    assert_eq!(flat["empty"], json!({}));
+    assert_eq!(flat["none"], json!([]));
+  }
+
+  #[test]
+  fn flatten_bracket() {
+    let flat = flatten(&sample(), PathStyle::Bracket);
+    assert_eq!(flat["user.tags[0]"], json!("a"));
+    assert_eq!(flat["items[1].id"], json!(2));
+  }
+
+  #[test]
+  fn flatten_unflatten_round_trip() {
+    for style in [PathStyle::Dotted, PathStyle::Bracket] {
+      let flat = flatten(&sample(), style);
+      assert_eq!(unflatten(flat, style).unwrap(), sample(), "{:?}", style);
+    }
+  }
+
+  #[test]
+  fn bracket_style_quotes_keys_with_separators() {
+    let value = json!({ "a.b": { "c[0]": 1, "say \"hi\"": [true] } });
+    let flat = flatten(&value, PathStyle::Bracket);
+    assert_eq!(flat[r#"["a.b"]["c[0]"]"#], json!(1));
+    assert_eq!(unflatten(flat, PathStyle::Bracket).unwrap(), value);
+  }
+
+  #[test]
+  fn slash_and_tilde_keys_round_trip() {
+    let value = json!({ "a/b": { "~c": 1, "d~/e": [2] } });
+    for style in [PathStyle::Dotted, PathStyle::Bracket] {
+      let flat = flatten(&value, style);
+      assert_eq!(unflatten(flat, style).unwrap(), value, "{:?}", style);
+    }
+  }
+
+  #[test]
+  fn pointer_tokens_escape_slash_and_tilde() {
+    assert_eq!(escape_token("a/b~c"), "a~1b~0c");
+    assert_eq!(unescape_token("a~1b~0c"), "a/b~c");
+    // `~01` is an escaped `~` followed by `1`, not a slash
+    assert_eq!(unescape_token("~01"), "~1");
+    let doc = json!({ "a/b": { "~c": [1, 2] } });
+    let pointer = format!("/{}/{}", escape_token("a/b"), escape_token("~c"));
+    let doc = apply_at(doc, Some(&pointer), |v| sort_array(v, None, true)).unwrap();
+    assert_eq!(doc, json!({ "a/b": { "~c": [2, 1] } }));
+  }
+
+  #[test]
+  fn unflatten_rejects_conflicts() {
+    assert!(unflatten(json!({ "a": 1, "a.b": 2 }), PathStyle::Dotted).is_err());
+    assert!(unflatten(json!({ "a[x]": 1 }), PathStyle::Bracket).is_err());
+    assert!(unflatten(json!([1]), PathStyle::Dotted).is_err());
+  }
+
+  #[test]
+  fn unflatten_keeps_large_numbers_as_keys() {
+    assert_eq!(
+      unflatten(json!({ "sales.20240101": 1 }), PathStyle::Dotted).unwrap(),
+      json!({ "sales": { "20240101": 1 } })
+    );
+    assert_eq!(
+      unflatten(json!({ "a.18446744073709551615": 1 }), PathStyle::Dotted).unwrap(),
+      json!({ "a": { "18446744073709551615": 1 } })
+    );
+    let err = unflatten(json!({ "a.0": 1, "a.20240101": 2 }), PathStyle::Dotted).unwrap_err();
+    assert!(
+      err
+        .to_string()
+        .starts_with("a.20240101: index 20240101 skips"),
+      "{}",
+      err
+    );
+  }
+
+  #[test]
+  fn key_cases() {
+    assert_eq!(convert_case("userID", KeyCase::Snake), "user_id");
+    assert_eq!(convert_case("HTTPServer", KeyCase::Kebab), "http-server");
+    assert_eq!(convert_case("first_name", KeyCase::Camel), "firstName");
+    assert_eq!(convert_case("first-name", KeyCase::Pascal), "FirstName");
+    assert_eq!(convert_case("_id", KeyCase::Camel), "_id");
+    assert_eq!(convert_case("$refTarget", KeyCase::Snake), "$ref_target");
+  }
+
+  #[test]
+  fn rename_keys_refuses_collisions() {
+    let value = json!({ "items": [{ "userId": 1, "first-name": "Ada" }] });
+    assert_eq!(
+      rename_keys(value, KeyCase::Snake).unwrap(),
+      json!({ "items": [{ "user_id": 1, "first_name": "Ada" }] })
+    );
+    let value = json!({ "items": [{ "userId": 1, "user_id": 2 }] });
+    let err = rename_keys(value, KeyCase::Snake).unwrap_err().to_string();
+    assert_eq!(err, "/items/0: key `user_id` would be duplicated");
+  }
+
+  #[test]
+  fn strip_empty_values() {
+    let value = json!({ "a": null, "b": "", "c": [null, {}], "d": 0 });
+    assert_eq!(strip_empty(value.clone(), false), json!({ "d": 0 }));
+    assert_eq!(
+      strip_empty(value, true),
+      json!({ "b": "", "c": [{}], "d": 0 })
+    );
+  }
+
+  #[test]
+  fn sort_and_dedupe_by_field() {
+    let value = json!([{ "n": 2 }, { "n": 1 }, { "n": 2 }, {}]);
+    assert_eq!(
+      sort_array(value.clone(), Some("n"), false).unwrap(),
+      json!([{}, { "n": 1 }, { "n": 2 }, { "n": 2 }])
+    );
+    assert_eq!(
+      dedupe_array(value, Some("/n")).unwrap(),
+      json!([{ "n": 2 }, { "n": 1 }, {}])
+    );
+    assert!(sort_array(json!({}), None, false).is_err());
+  }
+
+  #[test]
+  fn coerce_only_lossless() {
+    let value = json!(["42", "-1.5", "true", "007", "1e3", "", "x"]);
+    assert_eq!(
+      coerce_scalars(value),
+      json!([42, -1.5, true, "007", "1e3", "", "x"])
+    );
+  }
+}
@@ -0,0 +1,199 @@
+use anyhow::{bail, Result};
+use serde::{Deserialize, Serialize};
+use std::{
+  fs,
+  path::{Path, PathBuf},
+};
+use walkdir::WalkDir;
+
+use super::{
+  files::{self, Ignores},
+  format::FormatOptions,
+};
+
+// per-workspace settings, read from the folder root
+pub const SETTINGS_FILE: &str = ".jsoneditor.json";
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(default, rename_all = "camelCase")]
+pub struct WorkspaceSettings {
+  pub schemas: Vec<SchemaAssociation>,
+  pub format: FormatOptions,
+  // `.json` files linted like `.jsonc`, comments and trailing commas allowed: `["tsconfig.json"]`
+  pub allow_comments: Vec<String>,
+}
+
+// `{ "fileMatch": ["config/*.json"], "schema": "./schemas/config.schema.json" }`
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct SchemaAssociation {
+  pub file_match: Vec<String>,
+  // path relative to the workspace root, or a URL
+  pub schema: String,
+}
+
+impl WorkspaceSettings {
+  pub fn load(root: &Path) -> Result<Self> {
+    let path = root.join(SETTINGS_FILE);
+    if !path.is_file() {
+      return Ok(Self::default());
+    }
+    let text = files::strip_comments(&fs::read_to_string(&path)?);
+    Ok(serde_json::from_str(&text)?)
+  }
+
+  pub fn allows_comments(&self, root: &Path, file: &Path) -> bool {
+    let rel = file.strip_prefix(root).unwrap_or(file);
+    files::is_jsonc(file)
+      || files::glob_set(&self.allow_comments).is_ok_and(|set| set.is_match(rel))
+  }
+
+  // schema of `file`, globs without a `/` match the file name anywhere in the workspace
+  pub fn schema_for(&self, root: &Path, file: &Path) -> Option<String> {
+    let rel = file.strip_prefix(root).unwrap_or(file);
+    self.schemas.iter().find_map(|assoc| {
+      if !files::glob_set(&assoc.file_match).ok()?.is_match(rel) {
+        return None;
+      }
+      if assoc.schema.contains("://") {
+        Some(assoc.schema.clone())
+      } else {
+        Some(
+          root
+            .join(assoc.schema.trim_start_matches("./"))
+            .to_string_lossy()
+            .into(),
+        )
+      }
+    })
+  }
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct TreeEntry {
+  pub name: String,
+  pub path: String,
+  pub is_dir: bool,
+  pub size: Option<u64>,
+}
+
+// Direct children of `dir` for the file tree: folders first, then JSON files, ignored entries left out
+pub fn list_dir(root: &Path, dir: &Path) -> Result<Vec<TreeEntry>> {
+  // `..` and symlinks are resolved first, `starts_with` only compares path components
+  let root = root.canonicalize()?;
+  let dir = dir.canonicalize()?;
+  if !dir.starts_with(&root) {
+    bail!("{} is outside of the workspace", dir.display());
+  }
+  let ignores = Ignores::for_dir(&root, &dir);
+  let mut entries: Vec<TreeEntry> = WalkDir::new(&dir)
+    .min_depth(1)
+    .max_depth(1)
+    .into_iter()
+    .filter_map(|entry| entry.ok())
+    .filter(|entry| {
+      let is_dir = entry.file_type().is_dir();
+      !ignores.is_ignored(entry.path(), is_dir) && (is_dir || files::is_json_file(entry.path()))
+    })
+    .map(|entry| TreeEntry {
+      name: entry.file_name().to_string_lossy().into(),
+      path: entry.path().to_string_lossy().into(),
+      is_dir: entry.file_type().is_dir(),
+      size: entry
+        .metadata()
+        .ok()
+        .filter(|m| m.is_file())
+        .map(|m| m.len()),
+    })
+    .collect();
+  entries.sort_by(|a, b| {
+    b.is_dir
+      .cmp(&a.is_dir)
+      .then_with(|| a.name.to_lowercase().cmp(&b.name.to_lowercase()))
+  });
+  Ok(entries)
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct Workspace {
+  pub root: String,
+  pub settings: WorkspaceSettings,
+  pub entries: Vec<TreeEntry>,
+}
+
+pub fn open(root: &Path) -> Result<Workspace> {
+  if !root.is_dir() {
+    bail!("{} is not a folder", root.display());
+  }
+  let root: PathBuf = root.canonicalize()?;
+  Ok(Workspace {
+    settings: WorkspaceSettings::load(&root)?,
+    entries: list_dir(&root, &root)?,
+    root: root.to_string_lossy().into(),
+  })
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::engine::TempDir;
+
+  #[test]
+  fn lists_folders_first_then_json_files() {
+    let root = TempDir::new("list");
+    for file in ["b.json", "A.jsonc", "notes.txt", "z/c.json", "out/d.json"] {
+      let path = root.join(file);
+      fs::create_dir_all(path.parent().unwrap()).unwrap();
+      fs::write(path, "{}").unwrap();
+    }
+    fs::write(root.join(".gitignore"), "out/\n").unwrap();
+    let names: Vec<String> = list_dir(&root, &root)
+      .unwrap()
+      .into_iter()
+      .map(|e| e.name)
+      .collect();
+    assert_eq!(names, ["z", "A.jsonc", "b.json"]);
+  }
+
+  #[test]
+  fn refuses_folders_outside_the_root() {
+    let root = TempDir::new("escape");
+    let sub = root.join("sub");
+    fs::create_dir_all(&sub).unwrap();
+    assert!(list_dir(&root, &sub).is_ok());
+    assert!(list_dir(&sub, &sub.join("..")).is_err());
+    assert!(list_dir(&sub, &root.join("sub/../..")).is_err());
+    #[cfg(unix)]
+    {
+      std::os::unix::fs::symlink(&root, sub.join("up")).unwrap();
+      assert!(list_dir(&sub, &sub.join("up")).is_err());
+    }
+  }
+
+  #[test]
+  fn schema_associations() {
+    let settings: WorkspaceSettings = serde_json::from_str(
+      r#"{ "schemas": [
+        { "fileMatch": ["config/*.json"], "schema": "./schemas/config.json" },
+        { "fileMatch": ["*.pkg.json"], "schema": "https://example.com/pkg.json" }
+      ] }"#,
+    )
+    .unwrap();
+    let root = Path::new("/ws");
+    assert_eq!(
+      settings.schema_for(root, Path::new("/ws/config/app.json")),
+      Some(
+        Path::new("/ws/schemas/config.json")
+          .to_string_lossy()
+          .into()
+      )
+    );
+    assert_eq!(
+      settings
+        .schema_for(root, Path::new("/ws/a/b/x.pkg.json"))
+        .as_deref(),
+      Some("https://example.com/pkg.json")
+    );
+    assert_eq!(settings.schema_for(root, Path::new("/ws/other.json")), None);
+  }
+}
@@ -4,18 +4,58 @@
 )]
 
 mod app;
+mod cli;
 mod conf;
+mod deep_link;
+mod engine;
 mod utils;
 
-use app::{cmd, menu, setup};
+use app::{cmd, instance, menu, popup, setup, theme, tray, window_state};
 use conf::AppConf;
-use log::info;
+use log::{error, info};
+use tauri::Manager;
 
 use tauri_plugin_autostart::MacosLauncher;
 use tauri_plugin_log::LogTarget;
 
 fn main() {
-  let app_conf = AppConf::read().write();
+  // headless commands exit here, without touching the config or starting the GUI
+  let documents = match cli::parse(std::env::args().skip(1).collect()) {
+    Ok(cli::Command::Open(inputs)) => match cli::documents(&inputs) {
+      Ok(documents) => documents,
+      Err(e) => {
+        cli::attach_console();
+        eprintln!("json-editor: {:#}", e);
+        std::process::exit(cli::EXIT_ERROR);
+      }
+    },
+    Ok(command) => {
+      cli::attach_console();
+      std::process::exit(cli::run(command))
+    }
+    Err(e) => {
+      cli::attach_console();
+      eprintln!("json-editor: {:#}\n\n{}", e, cli::USAGE);
+      std::process::exit(cli::EXIT_ERROR);
+    }
+  };
+
+  // a running instance opens the documents itself, otherwise this process becomes it
+  let primary = match instance::start(&documents) {
+    Ok(instance::Launch::Primary(primary)) => primary,
+    Ok(instance::Launch::Forwarded) => std::process::exit(cli::EXIT_OK),
+    Err(e) => {
+      cli::attach_console();
+      eprintln!("json-editor: {:#}", e);
+      std::process::exit(cli::EXIT_ERROR);
+    }
+  };
+
+  // saving right away writes the defaults of new settings and migrations back
+  let app_conf = AppConf::read();
+  if let Err(e) = app_conf.save() {
+    error!("conf_save: {:#}", e);
+  }
 
   let context = tauri::generate_context!();
 
@@ -35,8 +75,74 @@ fn main() {
       MacosLauncher::LaunchAgent,
       None,
     ))
-    .invoke_handler(tauri::generate_handler![cmd::run_check_update,])
-    .setup(setup::init)
+    .invoke_handler(tauri::generate_handler![
+      cmd::run_check_update,
+      cmd::transform_document,
+      cmd::table_columns,
+      cmd::export_table,
+      cmd::import_table,
+      cmd::search_document,
+      cmd::selection_pointer,
+      cmd::replace_in_document,
+      cmd::launch_document,
+      cmd::open_window,
+      cmd::control_reply,
+      cmd::open_document,
+      cmd::tray_state,
+      cmd::format_json,
+      cmd::open_tray_document,
+      cmd::refresh_popup_search,
+      cmd::popup_search,
+      cmd::open_popup_hit,
+      cmd::list_snippets,
+      cmd::save_snippet,
+      cmd::delete_snippet,
+      cmd::get_dashboard,
+      cmd::pin_document,
+      cmd::save_buffer,
+      cmd::recover_buffer,
+      cmd::discard_buffer,
+      cmd::read_template,
+      cmd::save_template,
+      cmd::delete_template,
+      cmd::clipboard_json,
+      cmd::search_folder,
+      cmd::cancel_folder_search,
+      cmd::lint_folder,
+      cmd::export_lint_report,
+      cmd::fetch_url,
+      cmd::list_collections,
+      cmd::save_collection,
+      cmd::delete_collection,
+      cmd::get_environments,
+      cmd::save_environments,
+      cmd::send_request,
+      cmd::request_history,
+      cmd::clear_request_history,
+      cmd::diff_responses,
+      cmd::import_requests,
+      cmd::read_har,
+      cmd::list_pipelines,
+      cmd::save_pipeline,
+      cmd::delete_pipeline,
+      cmd::preview_pipeline,
+      cmd::apply_pipeline,
+      cmd::list_backups,
+      cmd::undo_backup,
+      cmd::start_mock,
+      cmd::stop_mock,
+      cmd::update_mock_document,
+      cmd::mock_status,
+      cmd::mock_port,
+      cmd::open_workspace,
+      cmd::list_workspace_dir,
+      cmd::close_workspace,
+      cmd::last_workspace,
+      cmd::get_conf,
+      cmd::set_conf,
+      cmd::get_theme,
+    ])
+    .setup(move |app| setup::init(app, documents, primary))
     .menu(menu::init());
 
   if app_conf.tray {
@@ -47,6 +153,30 @@ fn main() {
     .on_system_tray_event(menu::tray_handler)
     .on_window_event(move |event| {
       info!("on_window_event...");
+      // the tray popover and the search popup hide like menus when they lose focus
+      if [tray::LABEL, popup::LABEL].contains(&event.window().label()) {
+        match event.event() {
+          tauri::WindowEvent::Focused(false) => event.window().hide().unwrap(),
+          tauri::WindowEvent::CloseRequested { api, .. } => {
+            event.window().hide().unwrap();
+            api.prevent_close();
+          }
+          _ => (),
+        }
+        return;
+      }
+      if matches!(
+        event.event(),
+        tauri::WindowEvent::Moved(_)
+          | tauri::WindowEvent::Resized(_)
+          | tauri::WindowEvent::CloseRequested { .. }
+      ) {
+        window_state::record(event.window());
+      }
+      // windows that follow the OS theme are told when it switches
+      if let tauri::WindowEvent::ThemeChanged(_) = event.event() {
+        theme::refresh(&event.window().app_handle());
+      }
       if let tauri::WindowEvent::CloseRequested { api, .. } = event.event() {
         let win = event.window().clone();
         let app_conf = AppConf::read();
@@ -57,9 +187,14 @@ fn main() {
               "",
               "Do you want to exit the application when you click the [x] button?",
               move |is_ok| {
-                app_conf
-                  .amend(serde_json::json!({ "isinit" : false, "main_close": is_ok }))
-                  .write();
+                let patch = conf::AppConfPatch {
+                  isinit: Some(false),
+                  main_close: Some(is_ok),
+                  ..Default::default()
+                };
+                if let Err(e) = AppConf::set(patch) {
+                  error!("conf_set: {}", e);
+                }
                 if is_ok {
                   std::process::exit(0);
                 } else {
@@ -73,13 +208,23 @@ fn main() {
             win.minimize().unwrap();
           }
         } else {
+          app::dashboard::discard_window_buffer(win.label());
+          app::workspace::unwatch(win.label());
+          window_state::forget(win.label());
           event.window().close().unwrap();
         }
         api.prevent_close();
       }
     })
-    .run(context)
+    .build(context)
     .expect("error while running JsonEditor application")
+    .run(|_app, _event| {
+      #[cfg(target_os = "macos")]
+      if let tauri::RunEvent::Opened { urls } = _event {
+        let links: Vec<String> = urls.iter().map(|url| url.to_string()).collect();
+        app::launch::open_links(_app, &links);
+      }
+    });
   // here `"quit".to_string()` defines the menu item id, and the second parameter is the menu item label.
   // let quit = CustomMenuItem::new("quit".to_string(), "Quit");
   // let hide = CustomMenuItem::new("hide".to_string(), "Hide");
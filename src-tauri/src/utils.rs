@@ -1,11 +1,7 @@
 use anyhow::Result;
-use log::{info};
+use log::info;
 
-use std::{
-  fs::{self, File},
-  path::{Path, PathBuf},
-  process::Command,
-};
+use std::{fs, path::PathBuf, process::Command};
 
 use tauri::updater::UpdateResponse;
 use tauri::{utils::config::Config, AppHandle, Manager, Wry};
@@ -20,10 +16,6 @@ pub fn get_tauri_conf() -> Option<Config> {
   Some(config)
 }
 
-pub fn exists(path: &Path) -> bool {
-  Path::new(path).exists()
-}
-
 pub fn convert_path(path_str: &str) -> String {
   if cfg!(target_os = "windows") {
     path_str.replace('/', "\\")
@@ -32,13 +24,6 @@ pub fn convert_path(path_str: &str) -> String {
   }
 }
 
-pub fn create_file(path: &Path) -> Result<File> {
-  if let Some(p) = path.parent() {
-    fs::create_dir_all(p)?
-  }
-  File::create(path).map_err(Into::into)
-}
-
 pub fn open_file(path: PathBuf) {
   let pathname = convert_path(path.to_str().unwrap());
   info!("open_file: {}", pathname);
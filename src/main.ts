@@ -1,9 +1,16 @@
 import { createApp } from "vue";
 import "./style.css";
 import App from "./App.vue";
+import TrayWindow from "./components/TrayWindow.vue";
+import SearchPopup from "./components/SearchPopup.vue";
 import JsonEditor from 'vue3-ts-jsoneditor';
+import { getCurrent } from "@tauri-apps/api/window";
+import followTheme from "./utils/theme";
 
-const app = createApp(App);
+// the tray popover and the search popup share the bundle, they only differ by their window label
+const roots: Record<string, any> = { tray: TrayWindow, search: SearchPopup };
+const app = createApp(roots[getCurrent().label] ?? App);
+followTheme();
 
 app.use(JsonEditor, {
     componentName: 'JsonEditor', // Default: 'JsonEditor',
@@ -0,0 +1,16 @@
+export interface Column {
+    key: string
+    header: string | null
+}
+
+// "id, user.name as Name" -> [{key: 'id', header: null}, {key: 'user.name', header: 'Name'}]
+export default function parseColumns(text: string): Column[] {
+    return text
+        .split(',')
+        .map((part) => part.trim())
+        .filter((part) => part.length > 0)
+        .map((part) => {
+            const [key, header] = part.split(/\s+as\s+/i)
+            return {key: key.trim(), header: header ? header.trim() : null}
+        })
+}
@@ -0,0 +1,16 @@
+export interface Header {
+    name: string
+    value: string
+}
+
+// "Accept: application/json\nX-Token: abc" -> [{name: 'Accept', value: 'application/json'}, ...]
+export default function parseHeaders(text: string): Header[] {
+    return text
+        .split('\n')
+        .map((line) => line.trim())
+        .filter((line) => line.length > 0 && line.includes(':'))
+        .map((line) => {
+            const index = line.indexOf(':')
+            return {name: line.slice(0, index).trim(), value: line.slice(index + 1).trim()}
+        })
+}
@@ -0,0 +1,10 @@
+// JSON Pointer -> editor path, "/a/0/b~1c" -> ['a', '0', 'b/c']
+export default function parsePointer(pointer: string): string[] {
+    if (!pointer) {
+        return []
+    }
+    return pointer
+        .slice(1)
+        .split('/')
+        .map((token) => token.replace(/~1/g, '/').replace(/~0/g, '~'))
+}
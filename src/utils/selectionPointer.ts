@@ -0,0 +1,8 @@
+// Convert an editor selection (key/value selection path) to a JSON Pointer, null for the whole document
+export default function selectionPointer(selection: any): string | null {
+    const path: (string | number)[] | undefined = selection?.path
+    if (!path || !path.length || (selection.type !== 'key' && selection.type !== 'value')) {
+        return null
+    }
+    return path.map((p) => '/' + String(p).replace(/~/g, '~0').replace(/\//g, '~1')).join('')
+}
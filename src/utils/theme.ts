@@ -0,0 +1,17 @@
+import { ref } from 'vue'
+import { invoke } from '@tauri-apps/api/tauri'
+import { listen } from '@tauri-apps/api/event'
+
+// whether the window is dark, for the editor's `darkTheme` and the `dark` class on <html>
+export const dark = ref(false)
+
+function apply(theme: string) {
+  dark.value = theme === 'dark'
+  document.documentElement.classList.toggle('dark', dark.value)
+}
+
+// Follow the effective theme: the `theme` setting, or the OS mode while it is "system"
+export default async function followTheme() {
+  apply(await invoke<string>('get_theme'))
+  await listen<string>('theme-changed', (e) => apply(e.payload))
+}